@@ -1,16 +1,17 @@
 use crate::compiler::Compiler;
 use crate::eval::Evaluator;
 use crate::lexer::Lexer;
+use crate::module::ModuleLoader;
 use crate::parser::Parser;
 use crate::vm::VM;
 
 use anyhow::Result;
 use std::fs::File;
 use std::io::prelude::*;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::time::Instant;
 
-pub fn benchmark_file(path: String) -> Result<()> {
+pub fn benchmark_file(path: String, import_path: Vec<PathBuf>) -> Result<()> {
     // Open the file
     let path = Path::new(&path);
     let mut file = File::open(path)?;
@@ -38,6 +39,7 @@ pub fn benchmark_file(path: String) -> Result<()> {
 
     // Evaluate the file
     let mut evaluator = Evaluator::default();
+    evaluator.set_module_loader(ModuleLoader::new(import_path.clone(), Some(path))?);
 
     let start = Instant::now(); // Start timer
     let evaluated = evaluator.eval(&program);
@@ -50,6 +52,7 @@ pub fn benchmark_file(path: String) -> Result<()> {
 
     // Compile the file
     let mut compiler = Compiler::new();
+    compiler.set_module_loader(ModuleLoader::new(import_path, Some(path))?);
 
     let start = Instant::now(); // Start timer
     let result = compiler.compile(&program);
@@ -10,6 +10,7 @@ pub type InstructionsSlice<'a> = &'a [u8];
 
 #[derive(Debug, PartialEq, Copy, Clone)]
 #[repr(u8)]
+#[allow(clippy::enum_variant_names)]
 pub enum Opcode {
     OpConstant,
     OpPop,
@@ -42,6 +43,29 @@ pub enum Opcode {
     OpClosure,
     OpGetFree,
     OpCurrentClosure,
+    OpSet,
+    OpIn,
+    OpTuple,
+    OpDestructure,
+    OpDestructureHash,
+    OpMatchArray,
+    OpMatchTuple,
+    OpMatchHash,
+    OpMatchFail,
+    OpTry,
+    OpEndTry,
+    OpThrow,
+    OpJumpNotError,
+    OpModule,
+    OpMember,
+    OpGetBuiltinModule,
+    OpCallMethod,
+    OpMatchConstructor,
+    OpField,
+    OpDefault,
+    OpCallKeywords,
+    OpCallMethodKeywords,
+    OpUndefined,
 }
 
 pub struct Definition {
@@ -175,6 +199,98 @@ impl Opcode {
             Opcode::OpCurrentClosure => Definition {
                 name: "OpCurrentClosure",
                 operand_widths: vec![]
+            },
+            Opcode::OpSet => Definition {
+                name: "OpSet",
+                operand_widths: vec![2]
+            },
+            Opcode::OpIn => Definition {
+                name: "OpIn",
+                operand_widths: vec![]
+            },
+            Opcode::OpTuple => Definition {
+                name: "OpTuple",
+                operand_widths: vec![2]
+            },
+            Opcode::OpDestructure => Definition {
+                name: "OpDestructure",
+                operand_widths: vec![2]
+            },
+            Opcode::OpDestructureHash => Definition {
+                name: "OpDestructureHash",
+                operand_widths: vec![2]
+            },
+            Opcode::OpMatchArray => Definition {
+                name: "OpMatchArray",
+                operand_widths: vec![2, 1]
+            },
+            Opcode::OpMatchTuple => Definition {
+                name: "OpMatchTuple",
+                operand_widths: vec![2, 1]
+            },
+            Opcode::OpMatchHash => Definition {
+                name: "OpMatchHash",
+                operand_widths: vec![]
+            },
+            Opcode::OpMatchFail => Definition {
+                name: "OpMatchFail",
+                operand_widths: vec![]
+            },
+            Opcode::OpTry => Definition {
+                name: "OpTry",
+                operand_widths: vec![2]
+            },
+            Opcode::OpEndTry => Definition {
+                name: "OpEndTry",
+                operand_widths: vec![]
+            },
+            Opcode::OpThrow => Definition {
+                name: "OpThrow",
+                operand_widths: vec![]
+            },
+            Opcode::OpJumpNotError => Definition {
+                name: "OpJumpNotError",
+                operand_widths: vec![2]
+            },
+            Opcode::OpModule => Definition {
+                name: "OpModule",
+                operand_widths: vec![2]
+            },
+            Opcode::OpMember => Definition {
+                name: "OpMember",
+                operand_widths: vec![2, 2]
+            },
+            Opcode::OpGetBuiltinModule => Definition {
+                name: "OpGetBuiltinModule",
+                operand_widths: vec![1]
+            },
+            Opcode::OpCallMethod => Definition {
+                name: "OpCallMethod",
+                operand_widths: vec![2, 1]
+            },
+            Opcode::OpMatchConstructor => Definition {
+                name: "OpMatchConstructor",
+                operand_widths: vec![2]
+            },
+            Opcode::OpField => Definition {
+                name: "OpField",
+                operand_widths: vec![2]
+            },
+            Opcode::OpDefault => Definition {
+                name: "OpDefault",
+                operand_widths: vec![1, 2]
+            },
+            Opcode::OpCallKeywords => Definition {
+                name: "OpCallKeywords",
+                operand_widths: vec![1, 2]
+            },
+            Opcode::OpCallMethodKeywords => Definition {
+                name: "OpCallMethodKeywords",
+                operand_widths: vec![2, 1, 2]
+            },
+            Opcode::OpUndefined => Definition {
+                name: "OpUndefined",
+                operand_widths: vec![2]
             }
         }
     }
@@ -190,11 +306,11 @@ impl TryFrom<u8> for Opcode {
     type Error = Error;
 
     fn try_from(value: u8) -> Result<Self, Self::Error> {
-        if value >= Opcode::OpConstant as u8 && value <= Opcode::OpCurrentClosure as u8 {
+        if value >= Opcode::OpConstant as u8 && value <= Opcode::OpUndefined as u8 {
             // Sadly, this is unsafe, but using a match would be verbose / slow
-            return Ok(unsafe { std::mem::transmute(value) });
+            Ok(unsafe { std::mem::transmute::<u8, Opcode>(value) })
         } else {
-            return Err(anyhow!("Invalid opcode: {}", value));
+            Err(anyhow!("Invalid opcode: {}", value))
         }
     }
 }
@@ -204,7 +320,7 @@ pub fn make(op: Opcode, operands: Vec<u64>) -> Instructions {
 
     let instruction_len = def.operand_widths.iter().sum::<usize>() + 1;
 
-    let mut instruction: Instructions = vec![0; instruction_len].into();
+    let mut instruction: Instructions = vec![0; instruction_len];
     instruction[0] = op as u8;
 
     let mut offset = 1;
@@ -223,7 +339,7 @@ pub fn make(op: Opcode, operands: Vec<u64>) -> Instructions {
         offset += width;
     }
 
-    return instruction;
+    instruction
 }
 
 pub fn instructions_string(instructions: &Instructions) -> String {
@@ -248,7 +364,7 @@ pub fn instructions_string(instructions: &Instructions) -> String {
         i += 1 + read;
     }
 
-    return out;
+    out
 }
 
 pub fn format_instruction(def: &Definition, operands: Vec<u64>) -> String {
@@ -263,16 +379,19 @@ pub fn format_instruction(def: &Definition, operands: Vec<u64>) -> String {
 
     match operand_count {
         0 => {
-            return def.name.to_string();
+            def.name.to_string()
         }
         1 => {
-            return format!("{} {}", def.name, operands[0]);
+            format!("{} {}", def.name, operands[0])
         }
         2 => {
-            return format!("{} {} {}", def.name, operands[0], operands[1]);
+            format!("{} {} {}", def.name, operands[0], operands[1])
+        }
+        3 => {
+            format!("{} {} {} {}", def.name, operands[0], operands[1], operands[2])
         }
         _ => {
-            return format!("ERROR: unhandled operand_count for {}\n", def.name);
+            format!("ERROR: unhandled operand_count for {}\n", def.name)
         }
     }
 }
@@ -296,7 +415,7 @@ pub fn read_operands(def: &Definition, instructions: InstructionsSlice) -> (Vec<
         offset += width;
     }
 
-    return (operands, offset);
+    (operands, offset)
 }
 
 pub fn read_u16(instructions: InstructionsSlice) -> u16 {
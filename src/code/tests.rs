@@ -45,6 +45,7 @@ fn test_instructions_string() {
         make!(OpConstant, [2]),
         make!(OpConstant, [65535]),
         make!(OpClosure, [65535, 255]),
+        make!(OpCallMethodKeywords, [65535, 3, 7]),
     ];
 
     let expected = String::from(
@@ -52,7 +53,8 @@ fn test_instructions_string() {
          0001 OpGetLocal 1\n\
          0003 OpConstant 2\n\
          0006 OpConstant 65535\n\
-         0009 OpClosure 65535 255\n",
+         0009 OpClosure 65535 255\n\
+         0013 OpCallMethodKeywords 65535 3 7\n",
     );
 
     let mut concatted = Instructions::new();
@@ -89,7 +91,7 @@ fn test_read_operands() {
     ];
 
     for test in tests {
-        let instruction = make(test.op.clone(), test.operands.clone());
+        let instruction = make(test.op, test.operands.clone());
         let def = test.op.lookup();
         let (operands_read, n) = read_operands(&def, &instruction[1..]);
         assert_eq!(test.bytes_read, n);
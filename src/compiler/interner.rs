@@ -0,0 +1,65 @@
+use std::{borrow::Borrow, collections::HashMap, rc::Rc};
+
+// An interned string, identified by the order it was first interned in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StringId(u32);
+
+// The interner stores each distinct string once. Symbol tables key their
+// definitions by id, so resolving a name through nested scopes hashes it once
+// and then only compares ids, and the compiler hands out the shared string for
+// repeated constants instead of allocating a copy for each one.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Interner {
+    ids: HashMap<Key, StringId>,
+    strings: Vec<Rc<String>>,
+}
+
+// Lets the id map be queried with a &str without allocating
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Key(Rc<String>);
+
+impl Borrow<str> for Key {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Interner {
+    // Create an empty interner
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Intern a string, returning the id of the existing copy if there is one
+    pub fn intern(&mut self, value: &str) -> StringId {
+        if let Some(id) = self.ids.get(value) {
+            return *id;
+        }
+
+        let id = StringId(self.strings.len() as u32);
+        let string = Rc::new(value.to_string());
+        self.ids.insert(Key(string.clone()), id);
+        self.strings.push(string);
+        id
+    }
+
+    // Get the id of a string if it has been interned
+    pub fn get(&self, value: &str) -> Option<StringId> {
+        self.ids.get(value).copied()
+    }
+
+    // Get the shared string for an id
+    pub fn resolve(&self, id: StringId) -> &Rc<String> {
+        &self.strings[id.0 as usize]
+    }
+
+    // Get the number of distinct strings interned
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    // Check if no strings have been interned
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+}
@@ -1,17 +1,24 @@
+pub mod interner;
 pub mod symbol_table;
 
 use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::PathBuf;
 use std::rc::Rc;
 
 use crate::code::{make, Instructions, Opcode};
 use crate::lexer::token::Token;
-use crate::object::{builtins::BUILTINS, CompiledFunction, Object};
-use crate::parser::ast::{Expression, Node, Program, Statement};
+use crate::module::{module_name, ModuleLoader};
+use crate::object::builtins::{builtin_member_index, BUILTINS, BUILTIN_MODULES};
+use crate::object::{CompiledFunction, Object, Shape};
+use crate::parser::ast::{Binding, Expression, MatchArm, Node, Parameter, Pattern, Program, Statement};
+use crate::raise;
 use crate::token;
 use anyhow::{anyhow, Result};
-use symbol_table::SymbolTable;
+use interner::{Interner, StringId};
+use symbol_table::{Symbol, SymbolTable};
 
-use self::symbol_table::{BUILTIN_SCOPE, FREE_SCOPE, GLOBAL_SCOPE, LOCAL_SCOPE, FUNCTION_SCOPE};
+use self::symbol_table::{BUILTIN_MODULE_SCOPE, BUILTIN_SCOPE, FREE_SCOPE, GLOBAL_SCOPE, LOCAL_SCOPE, FUNCTION_SCOPE};
 
 #[cfg(test)]
 mod tests;
@@ -25,12 +32,26 @@ macro_rules! emit {
     };
 }
 
+// String constants up to this many bytes are interned, so repeated literals
+// share one constant; longer ones are rare enough to not be worth keeping
+const MAX_INTERNED_STRING_LEN: usize = 64;
+
+// The number of field cache slots OpMember's two-byte operand can name. Slots
+// are reused from 0 once they run out.
+const FIELD_CACHE_SLOTS: usize = 1 << 16;
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct Compiler {
     constants: Vec<Object>,
     symbol_table: Rc<RefCell<SymbolTable>>,
+    interner: Rc<RefCell<Interner>>,
+    string_constants: HashMap<StringId, usize>,
     scopes: Vec<CompilationScope>,
     scope_index: usize,
+    // Imported modules, by the global slot their module object is stored in
+    modules: ModuleLoader<usize>,
+    // The field cache slot for the next OpMember instruction
+    field_caches: usize,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -45,44 +66,70 @@ struct EmittedInstruction {
     position: usize,
 }
 
+// A step from a matched value to one of its parts
+enum Access {
+    Index(Expression),
+    Rest(usize),
+    Field(usize),
+}
+
+// A try expression being compiled. A return from inside it first removes its
+// handler (if one is installed where the return is) and runs its finally block.
+#[derive(Debug, PartialEq, Clone)]
+struct TryBlock {
+    handler: bool,
+    finally: Option<Statement>,
+}
+
 #[derive(Debug, PartialEq, Clone)]
 struct CompilationScope {
     instructions: Instructions,
     last_instruction: Option<EmittedInstruction>,
     previous_instruction: Option<EmittedInstruction>,
+    try_blocks: Vec<TryBlock>,
 }
 
 impl Compiler {
     // Create a new compiler
     pub fn new() -> Self {
         let symbol_table = SymbolTable::new(None);
-
-        for (i, builtin) in BUILTINS.iter().enumerate() {
-            symbol_table.borrow_mut().define_builtin(i, builtin);
-        }
+        define_builtins(&mut symbol_table.borrow_mut());
+        let interner = symbol_table.borrow().interner.clone();
 
         let main_scope = CompilationScope {
             instructions: vec![],
             last_instruction: None,
             previous_instruction: None,
+            try_blocks: vec![],
         };
         Self {
             constants: vec![],
             symbol_table: symbol_table.clone(),
+            interner,
+            string_constants: HashMap::new(),
             scopes: vec![main_scope],
             scope_index: 0,
+            modules: ModuleLoader::default(),
+            field_caches: 0,
         }
     }
 
+    // Set the loader used to find imported modules
+    pub fn set_module_loader(&mut self, loader: ModuleLoader<usize>) {
+        self.modules = loader;
+    }
+
     // Reset compiler for reuse (for REPL)
     pub fn reset(&mut self) {
         let main_scope = CompilationScope {
             instructions: vec![],
             last_instruction: None,
             previous_instruction: None,
+            try_blocks: vec![],
         };
         self.scopes = vec![main_scope];
         self.scope_index = 0;
+        self.field_caches = 0;
     }
 
     // Enter a new scope
@@ -91,6 +138,7 @@ impl Compiler {
             instructions: vec![],
             last_instruction: None,
             previous_instruction: None,
+            try_blocks: vec![],
         };
         self.scopes.push(scope);
         self.scope_index += 1;
@@ -109,12 +157,18 @@ impl Compiler {
             None => old_symbol_table.clone(), // This should never happen
         };
 
-        return scope.instructions;
+        scope.instructions
     }
 
-    // Compile a full program
+    // Compile a full program. Modules imported by a program that fails to
+    // compile are forgotten, as their code never runs.
     pub fn compile(&mut self, program: &Program) -> Result<()> {
-        self.compile_node(&Node::Program(program))
+        let modules = self.modules.clone();
+        let result = self.compile_node(&Node::Program(program));
+        if result.is_err() {
+            self.modules = modules;
+        }
+        result
     }
 
     // Compile from an AST node
@@ -129,8 +183,22 @@ impl Compiler {
 
     // Compile a program AST node
     fn compile_program(&mut self, program: &Program) -> Result<()> {
-        for statement in &program.statements {
-            self.compile_node(&Node::Statement(&statement))?;
+        self.compile_statements(&program.statements)
+    }
+
+    // Compile a list of statements. Only the last statement's value can be
+    // used (as a function's implicit return value, or shown by the REPL), so
+    // any if before it is compiled as a statement that leaves nothing behind.
+    fn compile_statements(&mut self, statements: &[Statement]) -> Result<()> {
+        for (i, statement) in statements.iter().enumerate() {
+            match statement {
+                Statement::Expression(Expression::If(branches, alternative))
+                    if i + 1 < statements.len() =>
+                {
+                    self.compile_if(branches, alternative, false)?
+                }
+                _ => self.compile_node(&Node::Statement(statement))?,
+            }
         }
         Ok(())
     }
@@ -142,28 +210,124 @@ impl Compiler {
                 self.compile_node(&Node::Expression(expression))?;
                 emit!(self, Opcode::OpPop);
             }
-            Statement::Block(statements) => {
-                for statement in statements {
-                    self.compile_node(&Node::Statement(statement))?;
-                }
-            }
-            Statement::Let(name, expression) => {
+            Statement::Block(statements) => self.compile_statements(statements)?,
+            Statement::Let(Binding::Name(name), expression) => {
                 let symbol = self.symbol_table.borrow_mut().define(name);
                 self.compile_node(&Node::Expression(expression))?;
-                match symbol.scope {
-                    GLOBAL_SCOPE => emit!(self, Opcode::OpSetGlobal, [symbol.index as u64]),
-                    LOCAL_SCOPE => emit!(self, Opcode::OpSetLocal, [symbol.index as u64]),
-                    _ => Err(anyhow!("unknown scope: {}", symbol.scope))?,
+                self.store_symbol(symbol)?;
+            }
+            Statement::Let(binding, expression) => {
+                self.compile_node(&Node::Expression(expression))?;
+
+                // Destructuring leaves the values on the stack, first name on top
+                let names = binding.names();
+                match binding {
+                    Binding::Hash(_) => {
+                        for name in names {
+                            let constant = self.add_string_constant(name);
+                            emit!(self, Opcode::OpConstant, [constant as u64]);
+                        }
+                        emit!(self, Opcode::OpDestructureHash, [names.len() as u64]);
+                    }
+                    _ => {
+                        emit!(self, Opcode::OpDestructure, [names.len() as u64]);
+                    }
                 };
+
+                // Names are defined once the value has been compiled, so it can
+                // still refer to outer bindings of the same names
+                for name in names {
+                    let symbol = self.symbol_table.borrow_mut().define(name);
+                    self.store_symbol(symbol)?;
+                }
             }
             Statement::Return(expression) => {
                 self.compile_node(&Node::Expression(expression))?;
+                self.leave_try_blocks()?;
                 emit!(self, Opcode::OpReturnValue);
             }
+            Statement::Throw(expression) => {
+                self.compile_node(&Node::Expression(expression))?;
+                emit!(self, Opcode::OpThrow);
+            }
+            Statement::Struct(name, fields) => {
+                let shape = Shape {
+                    name: name.clone(),
+                    fields: fields.clone(),
+                    enum_name: None,
+                };
+                let constant = self.add_constant(Object::StructType(Rc::new(shape)));
+                emit!(self, Opcode::OpConstant, [constant as u64]);
+                let symbol = self.symbol_table.borrow_mut().define(name);
+                self.store_symbol(symbol)?;
+            }
+            Statement::Enum(name, variants) => {
+                for (variant, fields) in variants {
+                    let constructor = Shape::variant(name, variant, fields);
+                    let constant = self.add_constant(constructor);
+                    emit!(self, Opcode::OpConstant, [constant as u64]);
+                    let symbol = self.symbol_table.borrow_mut().define(variant);
+                    self.store_symbol(symbol)?;
+                }
+            }
+            Statement::Import(path, name) => {
+                let path = self.modules.resolve(path)?;
+                let index = match self.modules.get(&path) {
+                    Some(index) => index,
+                    None => self.compile_module(path)?,
+                };
+                let symbol = self.symbol_table.borrow_mut().define(name);
+                emit!(self, Opcode::OpGetGlobal, [index as u64]);
+                self.store_symbol(symbol)?;
+            }
         }
         Ok(())
     }
 
+    // Compile an imported module inline, returning the global slot its module
+    // object is stored in. The module's top level gets its own global symbol
+    // table, with its slots allocated after those already in use, so its names
+    // don't clash with the importer's.
+    fn compile_module(&mut self, path: PathBuf) -> Result<usize> {
+        let program = self.modules.enter(&path)?;
+
+        let symbol_table = SymbolTable::with_interner(self.interner.clone());
+        define_builtins(&mut symbol_table.borrow_mut());
+        symbol_table.borrow_mut().num_definitions = self.symbol_table.borrow().num_definitions;
+        let importer = std::mem::replace(&mut self.symbol_table, symbol_table);
+
+        let result = self.compile_statements(&program.statements);
+        let symbol_table = std::mem::replace(&mut self.symbol_table, importer);
+        self.modules.leave();
+        result?;
+
+        // Build the module from its globals, ordered by name
+        let mut members = symbol_table
+            .borrow()
+            .store
+            .values()
+            .filter(|symbol| symbol.scope == GLOBAL_SCOPE)
+            .cloned()
+            .collect::<Vec<_>>();
+        members.sort_by(|left, right| left.name.cmp(&right.name));
+        let constant = self.add_string_constant(&module_name(&path));
+        emit!(self, Opcode::OpConstant, [constant as u64]);
+        for member in &members {
+            let constant = self.add_string_constant(&member.name);
+            emit!(self, Opcode::OpConstant, [constant as u64]);
+            emit!(self, Opcode::OpGetGlobal, [member.index as u64]);
+        }
+        emit!(self, Opcode::OpModule, [members.len() as u64]);
+
+        // The module object gets a slot with no name, after the module's own
+        let index = symbol_table.borrow().num_definitions;
+        self.symbol_table.borrow_mut().num_definitions = index + 1;
+        emit!(self, Opcode::OpSetGlobal, [index as u64]);
+
+        self.modules.insert(path, index);
+        Ok(index)
+    }
+
     // Compile an expression AST node
     fn compile_expression(&mut self, expression: &Expression) -> Result<()> {
         match expression {
@@ -179,8 +343,7 @@ impl Compiler {
                 };
             }
             Expression::String(value) => {
-                let string = Object::String(value.clone());
-                let constant = self.add_constant(string);
+                let constant = self.add_string_constant(value);
                 emit!(self, Opcode::OpConstant, [constant as u64]);
             }
             Expression::Hash(pairs) => {
@@ -196,6 +359,18 @@ impl Compiler {
                 }
                 emit!(self, Opcode::OpArray, [elements.len() as u64]);
             }
+            Expression::Tuple(elements) => {
+                for element in elements {
+                    self.compile_node(&Node::Expression(element))?;
+                }
+                emit!(self, Opcode::OpTuple, [elements.len() as u64]);
+            }
+            Expression::Set(elements) => {
+                for element in elements {
+                    self.compile_node(&Node::Expression(element))?;
+                }
+                emit!(self, Opcode::OpSet, [elements.len() as u64]);
+            }
             Expression::Infix(op, left, right) => {
                 if op == &token!(<) {
                     // Reverse the order of the operands
@@ -214,6 +389,7 @@ impl Compiler {
                     token!(==) => emit!(self, Opcode::OpEqual),
                     token!(!=) => emit!(self, Opcode::OpNotEqual),
                     token!(>) => emit!(self, Opcode::OpGreaterThan),
+                    token!(IN) => emit!(self, Opcode::OpIn),
                     _ => Err(anyhow!("unknown operator: {}", op))?,
                 };
             }
@@ -225,39 +401,37 @@ impl Compiler {
                     _ => Err(anyhow!("unknown operator: {}", op))?,
                 };
             }
-            Expression::If(condition, consequence, alternative) => {
-                self.compile_node(&Node::Expression(condition))?;
-
-                // Emit an OpJumpNotTruthy with a bogus value
-                let jump_not_truthy_pos = emit!(self, Opcode::OpJumpNotTruthy, [9999]);
-
-                self.compile_node(&Node::Statement(consequence))?;
-
-                if self.last_instruction_is(Opcode::OpPop) {
-                    self.remove_last_pop();
-                }
-
-                // Emit an OpJump with a bogus value
-                let jump_pos = emit!(self, Opcode::OpJump, [9999]);
-
-                let after_consequence_pos = self.current_instructions().len();
-                self.change_operand(jump_not_truthy_pos, after_consequence_pos as u64);
-
-                if let Some(alternative) = alternative {
-                    let after_consequence_pos = self.current_instructions().len();
-                    self.change_operand(jump_not_truthy_pos, after_consequence_pos as u64);
-
-                    self.compile_node(&Node::Statement(alternative))?;
-
-                    if self.last_instruction_is(Opcode::OpPop) {
-                        self.remove_last_pop();
-                    }
-                } else {
-                    emit!(self, Opcode::OpNull);
+            Expression::If(branches, alternative) => {
+                self.compile_if(branches, alternative, true)?;
+            }
+            Expression::Try(block, catch, finally) => {
+                self.compile_try(block, catch, finally)?;
+            }
+            Expression::Member(object, name) => {
+                // Members of builtin modules are resolved here, to the builtin itself
+                if let (true, Expression::Identifier(module)) = (self.is_builtin_module(object), object.as_ref()) {
+                    let index = builtin_member_index(module, name)
+                        .ok_or_else(|| raise!(Name, "module {} has no member {}", module, name))?;
+                    emit!(self, Opcode::OpGetBuiltin, [index as u64]);
+                    return Ok(());
                 }
+                self.compile_node(&Node::Expression(object))?;
+                let constant = self.add_string_constant(name);
+                // Each member access gets its own slot for the VM to cache
+                // the index of a struct field in
+                let cache = self.field_caches;
+                self.field_caches = (self.field_caches + 1) % FIELD_CACHE_SLOTS;
+                emit!(self, Opcode::OpMember, [constant as u64, cache as u64]);
+            }
+            Expression::Propagate(value) => {
+                // Return the value if it is an error, as a return statement would
+                self.compile_node(&Node::Expression(value))?;
+                let jump_pos = emit!(self, Opcode::OpJumpNotError, [9999]);
+                self.leave_try_blocks()?;
+                emit!(self, Opcode::OpReturnValue);
 
-                let after_alternative_pos = self.current_instructions().len();
-                self.change_operand(jump_pos, after_alternative_pos as u64);
+                let after_return_pos = self.current_instructions().len();
+                self.change_operand(jump_pos, after_return_pos as u64);
             }
             Expression::Identifier(name) => {
                 let symbol = self
@@ -296,7 +470,18 @@ impl Compiler {
                 }
 
                 for param in params {
-                    self.symbol_table.borrow_mut().define(param);
+                    self.symbol_table.borrow_mut().define(param.name());
+                }
+
+                // Fill in the parameters left out of the call with their defaults
+                for (index, param) in params.iter().enumerate() {
+                    if let Parameter::Optional(_, default) = param {
+                        let jump = emit!(self, Opcode::OpDefault, [index as u64, 9999]);
+                        self.compile_node(&Node::Expression(default))?;
+                        emit!(self, Opcode::OpSetLocal, [index as u64]);
+                        let after = self.current_instructions().len() as u64;
+                        self.replace_instruction(jump, make(Opcode::OpDefault, vec![index as u64, after]));
+                    }
                 }
 
                 self.compile_node(&Node::Statement(body))?;
@@ -310,7 +495,16 @@ impl Compiler {
 
                 let free_symbols = self.symbol_table.borrow().free_symbols.clone();
                 let num_locals = self.symbol_table.borrow().num_definitions;
-                let num_parameters = params.len();
+                let parameters = params
+                    .iter()
+                    .filter(|param| !matches!(param, Parameter::Rest(_)))
+                    .map(|param| param.name().clone())
+                    .collect::<Vec<String>>();
+                let min_arity = params.iter().filter(|param| matches!(param, Parameter::Required(_))).count();
+                let max_arity = match params.last() {
+                    Some(Parameter::Rest(_)) => None,
+                    _ => Some(parameters.len()),
+                };
                 let instructions = self.leave_scope();
 
                 let free_symbol_count = free_symbols.len();
@@ -318,11 +512,13 @@ impl Compiler {
                     self.load_symbol(free);
                 }
 
-                let compiled_fn = Object::CompiledFunction(CompiledFunction {
+                let compiled_fn = Object::CompiledFunction(Rc::new(CompiledFunction {
                     instructions,
                     num_locals,
-                    num_parameters,
-                });
+                    parameters,
+                    min_arity,
+                    max_arity,
+                }));
                 let constant = self.add_constant(compiled_fn);
                 emit!(
                     self,
@@ -330,14 +526,371 @@ impl Compiler {
                     [constant as u64, free_symbol_count as u64]
                 );
             }
-            Expression::Call(function, args) => {
+            Expression::Match(subject, arms) => self.compile_match(subject, arms)?,
+            Expression::Call(function, args, keywords) => {
+                if let Expression::Member(object, name) = function.as_ref() {
+                    if !self.is_builtin_module(object) {
+                        return self.compile_method_call(object, name, args, keywords);
+                    }
+                }
                 self.compile_node(&Node::Expression(function))?;
+                let num_args = self.compile_arguments(args, keywords)?;
+                match self.keyword_names(keywords) {
+                    Some(names) => emit!(self, Opcode::OpCallKeywords, [num_args as u64, names as u64]),
+                    None => emit!(self, Opcode::OpCall, [num_args as u64]),
+                };
+            }
+        }
+        Ok(())
+    }
+
+    // Check if an expression names a builtin module
+    fn is_builtin_module(&mut self, expression: &Expression) -> bool {
+        match expression {
+            Expression::Identifier(name) => matches!(
+                self.symbol_table.borrow_mut().resolve(name),
+                Some(Symbol { scope: BUILTIN_MODULE_SCOPE, .. })
+            ),
+            _ => false,
+        }
+    }
 
-                for arg in args {
-                    self.compile_node(&Node::Expression(arg))?;
+    // Compile a method call `object.name(args)`. Which function is called
+    // depends on the receiver (see `Object::method`), so the function named
+    // `name` in scope, if any, is passed along for the VM to choose.
+    fn compile_method_call(
+        &mut self,
+        object: &Expression,
+        name: &str,
+        args: &[Expression],
+        keywords: &[(String, Expression)],
+    ) -> Result<()> {
+        let symbol = self.symbol_table.borrow_mut().resolve(name);
+        match symbol {
+            Some(symbol) if symbol.scope != BUILTIN_MODULE_SCOPE => self.load_symbol(symbol),
+            _ => {
+                emit!(self, Opcode::OpNull);
+            }
+        };
+        self.compile_node(&Node::Expression(object))?;
+        let num_args = self.compile_arguments(args, keywords)?;
+
+        let constant = self.add_string_constant(name);
+        match self.keyword_names(keywords) {
+            Some(names) => emit!(
+                self,
+                Opcode::OpCallMethodKeywords,
+                [constant as u64, num_args as u64, names as u64]
+            ),
+            None => emit!(self, Opcode::OpCallMethod, [constant as u64, num_args as u64]),
+        };
+        Ok(())
+    }
+
+    // Compile the arguments of a call, returning how many there are. Keyword
+    // arguments follow the positional ones.
+    fn compile_arguments(&mut self, args: &[Expression], keywords: &[(String, Expression)]) -> Result<usize> {
+        for arg in args {
+            self.compile_node(&Node::Expression(arg))?;
+        }
+        for (_, arg) in keywords {
+            self.compile_node(&Node::Expression(arg))?;
+        }
+        Ok(args.len() + keywords.len())
+    }
+
+    // Add a constant with the names of a call's keyword arguments, if it has any
+    fn keyword_names(&mut self, keywords: &[(String, Expression)]) -> Option<usize> {
+        if keywords.is_empty() {
+            return None;
+        }
+        let names = keywords.iter().map(|(name, _)| Object::from(name.as_str())).collect();
+        Some(self.add_constant(Object::array(names)))
+    }
+
+    // Compile an if / else if chain as a flat sequence of branches, each
+    // jumping straight to the end once taken. As an expression, the taken
+    // branch's value (or null without an else) is left on the stack; as a
+    // statement, nothing is.
+    fn compile_if(
+        &mut self,
+        branches: &[(Expression, Statement)],
+        alternative: &Option<Box<Statement>>,
+        value: bool,
+    ) -> Result<()> {
+        let mut end_jumps = vec![];
+        for (i, (condition, consequence)) in branches.iter().enumerate() {
+            self.compile_node(&Node::Expression(condition))?;
+            let jump_not_truthy_pos = emit!(self, Opcode::OpJumpNotTruthy, [9999]);
+
+            self.compile_node(&Node::Statement(consequence))?;
+            if value {
+                self.keep_last_value();
+            }
+
+            // Nothing follows the last branch of a statement without an else
+            if value || alternative.is_some() || i + 1 < branches.len() {
+                end_jumps.push(emit!(self, Opcode::OpJump, [9999]));
+            }
+
+            let next_branch_pos = self.current_instructions().len();
+            self.change_operand(jump_not_truthy_pos, next_branch_pos as u64);
+        }
+
+        match alternative {
+            Some(alternative) => {
+                self.compile_node(&Node::Statement(alternative))?;
+                if value {
+                    self.keep_last_value();
                 }
+            }
+            None if value => {
+                emit!(self, Opcode::OpNull);
+            }
+            None => {}
+        }
+
+        let end_pos = self.current_instructions().len();
+        for jump in end_jumps {
+            self.change_operand(jump, end_pos as u64);
+        }
+        Ok(())
+    }
+
+    // Leave the value of a just compiled block on the stack: the value of its
+    // last expression statement, or null if it did not end with one
+    fn keep_last_value(&mut self) {
+        if self.last_instruction_is(Opcode::OpPop) {
+            self.remove_last_pop();
+        } else {
+            emit!(self, Opcode::OpNull);
+        }
+    }
+
+    // Compile a try expression. OpTry installs a handler that continues at the
+    // catch block with the error on the stack, and OpEndTry removes it once the
+    // try block finishes. With a finally block, the catch block has a handler
+    // too, and the finally block is compiled twice: once after the value is
+    // produced, and once before rethrowing an error that was not caught.
+    fn compile_try(
+        &mut self,
+        block: &Statement,
+        catch: &Option<(String, Box<Statement>)>,
+        finally: &Option<Box<Statement>>,
+    ) -> Result<()> {
+        let try_pos = emit!(self, Opcode::OpTry, [9999]);
+        self.scopes[self.scope_index].try_blocks.push(TryBlock {
+            handler: true,
+            finally: finally.as_deref().cloned(),
+        });
+        self.compile_node(&Node::Statement(block))?;
+        self.keep_last_value();
+        emit!(self, Opcode::OpEndTry);
+
+        // Without a catch block, errors go straight to the rethrow
+        let mut rethrow_pos = try_pos;
+        if let Some((name, body)) = catch {
+            let jump_pos = emit!(self, Opcode::OpJump, [9999]);
+            let catch_pos = self.current_instructions().len();
+            self.change_operand(try_pos, catch_pos as u64);
+
+            let symbol = self.symbol_table.borrow_mut().define(name);
+            self.store_symbol(symbol)?;
+
+            if finally.is_some() {
+                rethrow_pos = emit!(self, Opcode::OpTry, [9999]);
+            }
+            if let Some(try_block) = self.scopes[self.scope_index].try_blocks.last_mut() {
+                try_block.handler = finally.is_some();
+            }
+            self.compile_node(&Node::Statement(body))?;
+            self.keep_last_value();
+            if finally.is_some() {
+                emit!(self, Opcode::OpEndTry);
+            }
+
+            let after_catch_pos = self.current_instructions().len();
+            self.change_operand(jump_pos, after_catch_pos as u64);
+        }
+        self.scopes[self.scope_index].try_blocks.pop();
+
+        if let Some(finally) = finally {
+            self.compile_node(&Node::Statement(finally))?;
+            let jump_pos = emit!(self, Opcode::OpJump, [9999]);
+
+            let rethrow = self.current_instructions().len();
+            self.change_operand(rethrow_pos, rethrow as u64);
+            self.compile_node(&Node::Statement(finally))?;
+            emit!(self, Opcode::OpThrow);
+
+            let end_pos = self.current_instructions().len();
+            self.change_operand(jump_pos, end_pos as u64);
+        }
+        Ok(())
+    }
+
+    // Before a return, remove the handlers of the try blocks it leaves and run
+    // their finally blocks, innermost first
+    fn leave_try_blocks(&mut self) -> Result<()> {
+        let try_blocks = self.scopes[self.scope_index].try_blocks.clone();
+        for (i, try_block) in try_blocks.iter().enumerate().rev() {
+            if try_block.handler {
+                emit!(self, Opcode::OpEndTry);
+            }
+            if let Some(finally) = &try_block.finally {
+                // A return inside the finally block only leaves the outer ones
+                self.scopes[self.scope_index].try_blocks.truncate(i);
+                self.compile_node(&Node::Statement(finally))?;
+            }
+        }
+        self.scopes[self.scope_index].try_blocks = try_blocks;
+        Ok(())
+    }
+
+    // Compile a match expression. The subject is kept in a hidden slot and
+    // each arm tests it in turn, jumping to the next arm on the first failed
+    // test; if no arm matches, OpMatchFail raises an error naming the value.
+    // Each arm is a block, so the names it binds are only visible to its guard
+    // and body.
+    fn compile_match(&mut self, subject: &Expression, arms: &[MatchArm]) -> Result<()> {
+        self.compile_node(&Node::Expression(subject))?;
+        let subject = self.symbol_table.borrow_mut().define_hidden();
+        self.store_symbol(subject.clone())?;
 
-                emit!(self, Opcode::OpCall, [args.len() as u64]);
+        let mut end_jumps = vec![];
+        for arm in arms {
+            let names = self.symbol_table.borrow().begin_block();
+            let mut next_jumps = vec![];
+            self.compile_pattern(&arm.pattern, &subject, &mut vec![], &mut next_jumps)?;
+            if let Some(guard) = &arm.guard {
+                self.compile_node(&Node::Expression(guard))?;
+                next_jumps.push(emit!(self, Opcode::OpJumpNotTruthy, [9999]));
+            }
+
+            // Leave the value of the body on the stack
+            self.compile_node(&Node::Statement(&arm.body))?;
+            self.keep_last_value();
+            end_jumps.push(emit!(self, Opcode::OpJump, [9999]));
+            self.symbol_table.borrow_mut().end_block(names);
+
+            let next_arm_pos = self.current_instructions().len();
+            for jump in next_jumps {
+                self.change_operand(jump, next_arm_pos as u64);
+            }
+        }
+
+        self.load_symbol(subject.clone());
+        emit!(self, Opcode::OpMatchFail);
+        self.symbol_table.borrow_mut().release_hidden(subject);
+
+        let end_pos = self.current_instructions().len();
+        for jump in end_jumps {
+            self.change_operand(jump, end_pos as u64);
+        }
+        Ok(())
+    }
+
+    // Compile the tests for a pattern against the part of the subject at
+    // `path`, adding the jumps taken when a test fails to `next_jumps`
+    fn compile_pattern(
+        &mut self,
+        pattern: &Pattern,
+        subject: &Symbol,
+        path: &mut Vec<Access>,
+        next_jumps: &mut Vec<usize>,
+    ) -> Result<()> {
+        match pattern {
+            Pattern::Wildcard => {}
+            Pattern::Identifier(name) => {
+                self.load_path(subject, path)?;
+                let symbol = self.symbol_table.borrow_mut().define(name);
+                self.store_symbol(symbol)?;
+            }
+            Pattern::Literal(literal) => {
+                self.load_path(subject, path)?;
+                self.compile_node(&Node::Expression(literal))?;
+                emit!(self, Opcode::OpEqual);
+                next_jumps.push(emit!(self, Opcode::OpJumpNotTruthy, [9999]));
+            }
+            Pattern::Array(elements, rest) | Pattern::Tuple(elements, rest) => {
+                let opcode = match pattern {
+                    Pattern::Array(..) => Opcode::OpMatchArray,
+                    _ => Opcode::OpMatchTuple,
+                };
+                self.load_path(subject, path)?;
+                self.emit(opcode, vec![elements.len() as u64, rest.is_some() as u64]);
+                next_jumps.push(emit!(self, Opcode::OpJumpNotTruthy, [9999]));
+
+                for (i, element) in elements.iter().enumerate() {
+                    path.push(Access::Index(Expression::Integer(i as i64)));
+                    self.compile_pattern(element, subject, path, next_jumps)?;
+                    path.pop();
+                }
+                if let Some(rest) = rest {
+                    path.push(Access::Rest(elements.len()));
+                    self.compile_pattern(rest, subject, path, next_jumps)?;
+                    path.pop();
+                }
+            }
+            Pattern::Constructor(name, fields) => {
+                self.load_path(subject, path)?;
+                // A constructor that isn't defined is an error only when the
+                // arm is tried, as it is in the evaluator
+                let symbol = self.symbol_table.borrow_mut().resolve(name);
+                match symbol {
+                    Some(symbol) => self.load_symbol(symbol),
+                    None => {
+                        let name = self.add_string_constant(name);
+                        emit!(self, Opcode::OpUndefined, [name as u64]);
+                    }
+                }
+                emit!(self, Opcode::OpMatchConstructor, [fields.len() as u64]);
+                next_jumps.push(emit!(self, Opcode::OpJumpNotTruthy, [9999]));
+
+                for (i, field) in fields.iter().enumerate() {
+                    path.push(Access::Field(i));
+                    self.compile_pattern(field, subject, path, next_jumps)?;
+                    path.pop();
+                }
+            }
+            Pattern::Hash(pairs) => {
+                self.load_path(subject, path)?;
+                emit!(self, Opcode::OpMatchHash);
+                next_jumps.push(emit!(self, Opcode::OpJumpNotTruthy, [9999]));
+
+                for (key, pattern) in pairs {
+                    self.compile_node(&Node::Expression(key))?;
+                    self.load_path(subject, path)?;
+                    emit!(self, Opcode::OpIn);
+                    next_jumps.push(emit!(self, Opcode::OpJumpNotTruthy, [9999]));
+
+                    path.push(Access::Index(key.clone()));
+                    self.compile_pattern(pattern, subject, path, next_jumps)?;
+                    path.pop();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Load the part of the subject at `path`
+    fn load_path(&mut self, subject: &Symbol, path: &[Access]) -> Result<()> {
+        self.load_symbol(subject.clone());
+        for access in path {
+            match access {
+                Access::Index(index) => {
+                    self.compile_node(&Node::Expression(index))?;
+                    emit!(self, Opcode::OpIndex);
+                }
+                Access::Rest(start) => {
+                    let constant = self.add_constant(Object::Integer(*start as i64));
+                    emit!(self, Opcode::OpConstant, [constant as u64]);
+                    emit!(self, Opcode::OpNull);
+                    emit!(self, Opcode::OpSliceIndex);
+                }
+                Access::Field(index) => {
+                    emit!(self, Opcode::OpField, [*index as u64]);
+                }
             }
         }
         Ok(())
@@ -346,7 +899,23 @@ impl Compiler {
     // Add a constant to the compiler
     fn add_constant(&mut self, object: Object) -> usize {
         self.constants.push(object);
-        return self.constants.len() - 1;
+        self.constants.len() - 1
+    }
+
+    // Add a string constant, reusing the constant for an identical short string
+    fn add_string_constant(&mut self, value: &str) -> usize {
+        if value.len() > MAX_INTERNED_STRING_LEN {
+            return self.add_constant(Object::from(value));
+        }
+
+        let id = self.interner.borrow_mut().intern(value);
+        if let Some(constant) = self.string_constants.get(&id) {
+            return *constant;
+        }
+        let string = Object::String(self.interner.borrow().resolve(id).clone());
+        let constant = self.add_constant(string);
+        self.string_constants.insert(id, constant);
+        constant
     }
 
     // Emit an instruction
@@ -356,7 +925,7 @@ impl Compiler {
 
         self.set_last_instruction(opcode, position);
 
-        return position;
+        position
     }
 
     // Get instructions from the current scope
@@ -373,7 +942,7 @@ impl Compiler {
     fn add_instruction(&mut self, instruction: Instructions) -> usize {
         let position = self.current_instructions().len();
         self.current_instructions_mut().extend(instruction);
-        return position;
+        position
     }
 
     // Set the last instruction
@@ -389,7 +958,7 @@ impl Compiler {
         if let Some(instruction) = &self.scopes[self.scope_index].last_instruction {
             return instruction.opcode == opcode;
         }
-        return false;
+        false
     }
 
     // Remove the last instruction
@@ -434,15 +1003,26 @@ impl Compiler {
         self.replace_instruction(position, new_instruction);
     }
 
+    // Store the value on top of the stack in a symbol
+    fn store_symbol(&mut self, symbol: symbol_table::Symbol) -> Result<()> {
+        match symbol.scope {
+            GLOBAL_SCOPE => emit!(self, Opcode::OpSetGlobal, [symbol.index as u64]),
+            LOCAL_SCOPE => emit!(self, Opcode::OpSetLocal, [symbol.index as u64]),
+            _ => Err(anyhow!("unknown scope: {}", symbol.scope))?,
+        };
+        Ok(())
+    }
+
     // Load a symbol
     fn load_symbol(&mut self, symbol: symbol_table::Symbol) {
         match symbol.scope {
             GLOBAL_SCOPE => emit!(self, Opcode::OpGetGlobal, [symbol.index as u64]),
             LOCAL_SCOPE => emit!(self, Opcode::OpGetLocal, [symbol.index as u64]),
             BUILTIN_SCOPE => emit!(self, Opcode::OpGetBuiltin, [symbol.index as u64]),
+            BUILTIN_MODULE_SCOPE => emit!(self, Opcode::OpGetBuiltinModule, [symbol.index as u64]),
             FREE_SCOPE => emit!(self, Opcode::OpGetFree, [symbol.index as u64]),
             FUNCTION_SCOPE => emit!(self, Opcode::OpCurrentClosure),
-            _ => Err(anyhow!("unknown scope: {}", symbol.scope)).unwrap(),
+            _ => panic!("unknown scope: {}", symbol.scope),
         };
     }
 
@@ -454,3 +1034,15 @@ impl Compiler {
         }
     }
 }
+
+// Define the unqualified builtins and the builtin modules in a global table
+fn define_builtins(symbol_table: &mut SymbolTable) {
+    for (i, (name, _)) in BUILTINS.iter().enumerate() {
+        if !name.contains('.') {
+            symbol_table.define_builtin(i, name);
+        }
+    }
+    for (i, name) in BUILTIN_MODULES.iter().enumerate() {
+        symbol_table.define_builtin_module(i, name);
+    }
+}
@@ -1,5 +1,7 @@
 use std::{collections::HashMap, cell::RefCell, rc::Rc};
 
+use super::interner::{Interner, StringId};
+
 #[cfg(test)]
 mod tests;
 
@@ -9,101 +11,195 @@ type OuterSymbolTable = Rc<RefCell<SymbolTable>>;
 pub const GLOBAL_SCOPE: SymbolScope = "GLOBAL";
 pub const LOCAL_SCOPE: SymbolScope = "LOCAL";
 pub const BUILTIN_SCOPE: SymbolScope = "BUILTIN";
+pub const BUILTIN_MODULE_SCOPE: SymbolScope = "BUILTIN_MODULE";
 pub const FREE_SCOPE: SymbolScope = "FREE";
 pub const FUNCTION_SCOPE: SymbolScope = "FUNCTION";
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Symbol {
-    pub name: String,
+    pub name: Rc<String>,
     pub scope: SymbolScope,
     pub index: usize,
 }
 
+// Names are interned in an interner shared by a global table and all of its
+// nested tables, and each store is keyed by the interned id
 #[derive(Debug, PartialEq, Clone)]
 pub struct SymbolTable {
     pub outer: Option<Rc<RefCell<SymbolTable>>>,
-    pub store: HashMap<String, Symbol>,
+    pub store: HashMap<StringId, Symbol>,
     pub num_definitions: usize,
     pub free_symbols: Vec<Symbol>,
+    pub interner: Rc<RefCell<Interner>>,
+    hidden_slots: Vec<usize>,
 }
 
 impl SymbolTable {
     pub fn new(outer: Option<OuterSymbolTable>) -> Rc<RefCell<Self>> {
         let store = HashMap::new();
+        let interner = match &outer {
+            Some(outer) => outer.borrow().interner.clone(),
+            None => Rc::new(RefCell::new(Interner::new())),
+        };
         Rc::new(RefCell::new(SymbolTable {
             outer,
             store,
             num_definitions: 0,
             free_symbols: vec![],
+            interner,
+            hidden_slots: vec![],
         }))
     }
 
+    // Create a global table that shares an existing interner, for a module
+    // compiled alongside the main program
+    pub fn with_interner(interner: Rc<RefCell<Interner>>) -> Rc<RefCell<Self>> {
+        Rc::new(RefCell::new(SymbolTable {
+            outer: None,
+            store: HashMap::new(),
+            num_definitions: 0,
+            free_symbols: vec![],
+            interner,
+            hidden_slots: vec![],
+        }))
+    }
+
+    // Intern a name, returning its id and shared string
+    fn intern(&self, name: &str) -> (StringId, Rc<String>) {
+        let mut interner = self.interner.borrow_mut();
+        let id = interner.intern(name);
+        (id, interner.resolve(id).clone())
+    }
+
+    // The scope of the slots defined in this table
+    fn slot_scope(&self) -> SymbolScope {
+        match self.outer {
+            Some(_) => LOCAL_SCOPE,
+            None => GLOBAL_SCOPE,
+        }
+    }
+
     pub fn define(&mut self, name: &str) -> Symbol {
+        let (id, name) = self.intern(name);
         let symbol = Symbol {
-            name: name.to_string(),
-            scope: match self.outer {
-                Some(_) => LOCAL_SCOPE,
-                None => GLOBAL_SCOPE,
-            },
+            name,
+            scope: self.slot_scope(),
             index: self.num_definitions,
         };
-        self.store.insert(name.to_string(), symbol.clone());
+        self.store.insert(id, symbol.clone());
         self.num_definitions += 1;
-        return symbol;
+        symbol
+    }
+
+    // Define a slot with no name, for a value the compiler keeps between
+    // instructions. It is never resolved or exported from a module, and its
+    // index is reused by the next hidden slot once it is released.
+    pub fn define_hidden(&mut self) -> Symbol {
+        let index = self.hidden_slots.pop().unwrap_or_else(|| {
+            self.num_definitions += 1;
+            self.num_definitions - 1
+        });
+        Symbol {
+            name: Rc::default(),
+            scope: self.slot_scope(),
+            index,
+        }
+    }
+
+    pub fn release_hidden(&mut self, symbol: Symbol) {
+        self.hidden_slots.push(symbol.index);
+    }
+
+    // Start a block whose names are forgotten again by `end_block`, returning
+    // the names in scope before it
+    pub fn begin_block(&self) -> HashMap<StringId, Symbol> {
+        self.store.clone()
+    }
+
+    // End a block, restoring the names its definitions shadowed. The slots it
+    // defined stay allocated, and free variables it resolved stay captured.
+    pub fn end_block(&mut self, mut saved: HashMap<StringId, Symbol>) {
+        for (id, symbol) in self.store.drain() {
+            if symbol.scope == FREE_SCOPE {
+                saved.entry(id).or_insert(symbol);
+            }
+        }
+        self.store = saved;
     }
 
     pub fn define_builtin(&mut self, index: usize, name: &str) -> Symbol {
+        let (id, name) = self.intern(name);
         let symbol = Symbol {
-            name: name.to_string(),
+            name,
             scope: BUILTIN_SCOPE,
             index,
         };
-        self.store.insert(name.to_string(), symbol.clone());
-        return symbol;
+        self.store.insert(id, symbol.clone());
+        symbol
+    }
+
+    pub fn define_builtin_module(&mut self, index: usize, name: &str) -> Symbol {
+        let (id, name) = self.intern(name);
+        let symbol = Symbol {
+            name,
+            scope: BUILTIN_MODULE_SCOPE,
+            index,
+        };
+        self.store.insert(id, symbol.clone());
+        symbol
     }
 
     pub fn define_free(&mut self, original: &Symbol) -> Symbol {
+        let (id, _) = self.intern(&original.name);
+        self.define_free_id(id, original)
+    }
+
+    fn define_free_id(&mut self, id: StringId, original: &Symbol) -> Symbol {
         self.free_symbols.push(original.clone());
         let symbol = Symbol {
             name: original.name.clone(),
             scope: FREE_SCOPE,
             index: self.free_symbols.len() - 1,
         };
-        self.store.insert(original.name.clone(), symbol.clone());
-        return symbol;
+        self.store.insert(id, symbol.clone());
+        symbol
     }
 
     pub fn define_function_name(&mut self, name: &str) -> Symbol {
+        let (id, name) = self.intern(name);
         let symbol = Symbol {
-            name: name.to_string(),
+            name,
             scope: FUNCTION_SCOPE,
             index: 0,
         };
-        self.store.insert(name.to_string(), symbol.clone());
-        return symbol;
+        self.store.insert(id, symbol.clone());
+        symbol
     }
 
     pub fn resolve(&mut self, name: &str) -> Option<Symbol> {
-        let sym = self.store.get(name);
+        // A name that was never interned was never defined in any scope
+        let id = self.interner.borrow().get(name)?;
+        self.resolve_id(id)
+    }
+
+    fn resolve_id(&mut self, id: StringId) -> Option<Symbol> {
+        let sym = self.store.get(&id);
 
         if let Some(sym) = sym {
             return Some(sym.clone());
         }
 
         let sym = match &self.outer {
-            Some(outer) => match outer.borrow_mut().resolve(name) {
-                Some(sym) => sym,
-                None => return None,
-            },
+            Some(outer) => outer.borrow_mut().resolve_id(id)?,
             None => return None,
         };
 
-        if sym.scope == GLOBAL_SCOPE || sym.scope == BUILTIN_SCOPE {
+        if sym.scope == GLOBAL_SCOPE || sym.scope == BUILTIN_SCOPE || sym.scope == BUILTIN_MODULE_SCOPE {
             return Some(sym);
         }
 
-        let free = self.define_free(&sym);
+        let free = self.define_free_id(id, &sym);
 
-        return Some(free);
+        Some(free)
     }
-}
\ No newline at end of file
+}
@@ -4,32 +4,32 @@ use super::*;
 fn test_define() {
     let expected = HashMap::from([
         ("a".to_string(), Symbol {
-            name: "a".to_string(),
+            name: Rc::new("a".to_string()),
             scope: GLOBAL_SCOPE,
             index: 0,
         }),
         ("b".to_string(), Symbol {
-            name: "b".to_string(),
+            name: Rc::new("b".to_string()),
             scope: GLOBAL_SCOPE,
             index: 1,
         }),
         ("c".to_string(), Symbol {
-            name: "c".to_string(),
+            name: Rc::new("c".to_string()),
             scope: LOCAL_SCOPE,
             index: 0,
         }),
         ("d".to_string(), Symbol {
-            name: "d".to_string(),
+            name: Rc::new("d".to_string()),
             scope: LOCAL_SCOPE,
             index: 1,
         }),
         ("e".to_string(), Symbol {
-            name: "e".to_string(),
+            name: Rc::new("e".to_string()),
             scope: LOCAL_SCOPE,
             index: 0,
         }),
         ("f".to_string(), Symbol {
-            name: "f".to_string(),
+            name: Rc::new("f".to_string()),
             scope: LOCAL_SCOPE,
             index: 1,
         }),
@@ -68,12 +68,12 @@ fn test_resolve_global() {
 
     let expected = vec![
         Symbol {
-            name: "a".to_string(),
+            name: Rc::new("a".to_string()),
             scope: GLOBAL_SCOPE,
             index: 0,
         },
         Symbol {
-            name: "b".to_string(),
+            name: Rc::new("b".to_string()),
             scope: GLOBAL_SCOPE,
             index: 1,
         }
@@ -97,22 +97,22 @@ fn test_resolve_local() {
 
     let expected = vec![
         Symbol {
-            name: "a".to_string(),
+            name: Rc::new("a".to_string()),
             scope: GLOBAL_SCOPE,
             index: 0,
         },
         Symbol {
-            name: "b".to_string(),
+            name: Rc::new("b".to_string()),
             scope: GLOBAL_SCOPE,
             index: 1,
         },
         Symbol {
-            name: "c".to_string(),
+            name: Rc::new("c".to_string()),
             scope: LOCAL_SCOPE,
             index: 0,
         },
         Symbol {
-            name: "d".to_string(),
+            name: Rc::new("d".to_string()),
             scope: LOCAL_SCOPE,
             index: 1,
         }
@@ -140,22 +140,22 @@ fn test_resolve_nested_local() {
 
     let expected1 = vec![
         Symbol {
-            name: "a".to_string(),
+            name: Rc::new("a".to_string()),
             scope: GLOBAL_SCOPE,
             index: 0,
         },
         Symbol {
-            name: "b".to_string(),
+            name: Rc::new("b".to_string()),
             scope: GLOBAL_SCOPE,
             index: 1,
         },
         Symbol {
-            name: "c".to_string(),
+            name: Rc::new("c".to_string()),
             scope: LOCAL_SCOPE,
             index: 0,
         },
         Symbol {
-            name: "d".to_string(),
+            name: Rc::new("d".to_string()),
             scope: LOCAL_SCOPE,
             index: 1,
         }
@@ -163,22 +163,22 @@ fn test_resolve_nested_local() {
 
     let expected2 = vec![
         Symbol {
-            name: "a".to_string(),
+            name: Rc::new("a".to_string()),
             scope: GLOBAL_SCOPE,
             index: 0,
         },
         Symbol {
-            name: "b".to_string(),
+            name: Rc::new("b".to_string()),
             scope: GLOBAL_SCOPE,
             index: 1,
         },
         Symbol {
-            name: "e".to_string(),
+            name: Rc::new("e".to_string()),
             scope: LOCAL_SCOPE,
             index: 0,
         },
         Symbol {
-            name: "f".to_string(),
+            name: Rc::new("f".to_string()),
             scope: LOCAL_SCOPE,
             index: 1,
         }
@@ -201,24 +201,24 @@ fn test_define_resolve_builtins() {
     let local1 = SymbolTable::new(Some(global.clone()));
     let local2 = SymbolTable::new(Some(local1.clone()));
 
-    let expected = vec![
+    let expected = [
         Symbol {
-            name: "a".to_string(),
+            name: Rc::new("a".to_string()),
             scope: BUILTIN_SCOPE,
             index: 0,
         },
         Symbol {
-            name: "c".to_string(),
+            name: Rc::new("c".to_string()),
             scope: BUILTIN_SCOPE,
             index: 1,
         },
         Symbol {
-            name: "e".to_string(),
+            name: Rc::new("e".to_string()),
             scope: BUILTIN_SCOPE,
             index: 2,
         },
         Symbol {
-            name: "f".to_string(),
+            name: Rc::new("f".to_string()),
             scope: BUILTIN_SCOPE,
             index: 3,
         },
@@ -228,7 +228,7 @@ fn test_define_resolve_builtins() {
         global.borrow_mut().define_builtin(i, &sym.name);
     }
 
-    for table in vec![global, local1, local2] {
+    for table in [global, local1, local2] {
         for sym in expected.iter() {
             let result = table.borrow_mut().resolve(&sym.name);
             assert_eq!(result, Some(sym.clone()));
@@ -236,6 +236,25 @@ fn test_define_resolve_builtins() {
     }
 }
 
+#[test]
+fn test_define_resolve_builtin_modules() {
+    let global = SymbolTable::new(None);
+    let local = SymbolTable::new(Some(global.clone()));
+
+    let expected = Symbol {
+        name: Rc::new("math".to_string()),
+        scope: BUILTIN_MODULE_SCOPE,
+        index: 0,
+    };
+    global.borrow_mut().define_builtin_module(0, "math");
+
+    for table in [global, local.clone()] {
+        let result = table.borrow_mut().resolve("math");
+        assert_eq!(result, Some(expected.clone()));
+    }
+    assert!(local.borrow().free_symbols.is_empty());
+}
+
 #[test]
 fn test_resolve_free() {
     let global = SymbolTable::new(None);
@@ -261,22 +280,22 @@ fn test_resolve_free() {
             table: local1.clone(),
             expected_symbols: vec![
                 Symbol {
-                    name: "a".to_string(),
+                    name: Rc::new("a".to_string()),
                     scope: GLOBAL_SCOPE,
                     index: 0,
                 },
                 Symbol {
-                    name: "b".to_string(),
+                    name: Rc::new("b".to_string()),
                     scope: GLOBAL_SCOPE,
                     index: 1,
                 },
                 Symbol {
-                    name: "c".to_string(),
+                    name: Rc::new("c".to_string()),
                     scope: LOCAL_SCOPE,
                     index: 0,
                 },
                 Symbol {
-                    name: "d".to_string(),
+                    name: Rc::new("d".to_string()),
                     scope: LOCAL_SCOPE,
                     index: 1,
                 },
@@ -287,44 +306,44 @@ fn test_resolve_free() {
             table: local2.clone(),
             expected_symbols: vec![
                 Symbol {
-                    name: "a".to_string(),
+                    name: Rc::new("a".to_string()),
                     scope: GLOBAL_SCOPE,
                     index: 0,
                 },
                 Symbol {
-                    name: "b".to_string(),
+                    name: Rc::new("b".to_string()),
                     scope: GLOBAL_SCOPE,
                     index: 1,
                 },
                 Symbol {
-                    name: "c".to_string(),
+                    name: Rc::new("c".to_string()),
                     scope: FREE_SCOPE,
                     index: 0,
                 },
                 Symbol {
-                    name: "d".to_string(),
+                    name: Rc::new("d".to_string()),
                     scope: FREE_SCOPE,
                     index: 1,
                 },
                 Symbol {
-                    name: "e".to_string(),
+                    name: Rc::new("e".to_string()),
                     scope: LOCAL_SCOPE,
                     index: 0,
                 },
                 Symbol {
-                    name: "f".to_string(),
+                    name: Rc::new("f".to_string()),
                     scope: LOCAL_SCOPE,
                     index: 1,
                 },
             ],
             expected_free_symbols: vec![
                 Symbol {
-                    name: "c".to_string(),
+                    name: Rc::new("c".to_string()),
                     scope: LOCAL_SCOPE,
                     index: 0,
                 },
                 Symbol {
-                    name: "d".to_string(),
+                    name: Rc::new("d".to_string()),
                     scope: LOCAL_SCOPE,
                     index: 1,
                 },
@@ -364,22 +383,22 @@ fn test_resolve_unresolvable_free() {
 
     let expected = vec![
         Symbol {
-            name: "a".to_string(),
+            name: Rc::new("a".to_string()),
             scope: GLOBAL_SCOPE,
             index: 0,
         },
         Symbol {
-            name: "c".to_string(),
+            name: Rc::new("c".to_string()),
             scope: FREE_SCOPE,
             index: 0,
         },
         Symbol {
-            name: "e".to_string(),
+            name: Rc::new("e".to_string()),
             scope: LOCAL_SCOPE,
             index: 0,
         },
         Symbol {
-            name: "f".to_string(),
+            name: Rc::new("f".to_string()),
             scope: LOCAL_SCOPE,
             index: 1,
         },
@@ -408,7 +427,7 @@ fn test_define_and_resolve_function_name() {
 
     let expected = vec![
         Symbol {
-            name: "a".to_string(),
+            name: Rc::new("a".to_string()),
             scope: FUNCTION_SCOPE,
             index: 0,
         },
@@ -428,7 +447,7 @@ fn test_shadowing_function_name() {
 
     let expected = vec![
         Symbol {
-            name: "a".to_string(),
+            name: Rc::new("a".to_string()),
             scope: GLOBAL_SCOPE,
             index: 0,
         },
@@ -438,4 +457,63 @@ fn test_shadowing_function_name() {
         let result = global.borrow_mut().resolve(&sym.name);
         assert_eq!(result, Some(sym));
     }
-}
\ No newline at end of file
+}
+#[test]
+fn test_names_are_interned() {
+    let global = SymbolTable::new(None);
+    let local = SymbolTable::new(Some(global.clone()));
+
+    let a = global.borrow_mut().define("a");
+    let b = local.borrow_mut().define("a");
+
+    // Every nested table shares the global table's interner
+    assert!(Rc::ptr_eq(&global.borrow().interner, &local.borrow().interner));
+    assert!(Rc::ptr_eq(&a.name, &b.name));
+    assert_eq!(global.borrow().interner.borrow().len(), 1);
+
+    // Names that were never interned are not resolved or interned
+    assert_eq!(local.borrow_mut().resolve("b"), None);
+    assert_eq!(global.borrow().interner.borrow().len(), 1);
+}
+
+#[test]
+fn test_hidden_slots() {
+    let global = SymbolTable::new(None);
+    let local = SymbolTable::new(Some(global.clone()));
+
+    let a = local.borrow_mut().define_hidden();
+    let b = local.borrow_mut().define_hidden();
+    local.borrow_mut().define("c");
+    assert_eq!((a.scope, a.index, b.index), (LOCAL_SCOPE, 0, 1));
+    assert_eq!(local.borrow().num_definitions, 3);
+
+    // Hidden slots have no name, and a released slot is reused
+    assert!(local.borrow().store.values().all(|symbol| symbol.name.as_str() == "c"));
+    local.borrow_mut().release_hidden(b);
+    assert_eq!(local.borrow_mut().define_hidden().index, 1);
+    assert_eq!(local.borrow().num_definitions, 3);
+}
+
+#[test]
+fn test_blocks() {
+    let global = SymbolTable::new(None);
+    let outer = SymbolTable::new(Some(global.clone()));
+    let local = SymbolTable::new(Some(outer.clone()));
+    outer.borrow_mut().define("a");
+    let b = local.borrow_mut().define("b");
+
+    let names = local.borrow().begin_block();
+    local.borrow_mut().define("b");
+    local.borrow_mut().define("c");
+    let a = local.borrow_mut().resolve("a").unwrap();
+    assert_eq!(a.scope, FREE_SCOPE);
+    local.borrow_mut().end_block(names);
+
+    // Names defined in the block are forgotten, but their slots and the free
+    // variables resolved in it are kept
+    assert_eq!(local.borrow_mut().resolve("b"), Some(b));
+    assert_eq!(local.borrow_mut().resolve("c"), None);
+    assert_eq!(local.borrow_mut().resolve("a"), Some(a));
+    assert_eq!(local.borrow().num_definitions, 3);
+    assert_eq!(local.borrow().free_symbols.len(), 1);
+}
@@ -5,6 +5,7 @@ use super::*;
 use crate::code::make;
 use crate::compiler::Compiler;
 use crate::lexer::Lexer;
+use crate::object::error::{ErrorKind, RuntimeError};
 use crate::parser::Parser;
 
 macro_rules! make_test {
@@ -19,11 +20,13 @@ macro_rules! make_test {
 
 macro_rules! make_compiled_function {
     ($instructions:expr) => {
-        Object::CompiledFunction(CompiledFunction {
+        Object::CompiledFunction(Rc::new(CompiledFunction {
             instructions: concat_instructions($instructions),
             num_locals:  0,
-            num_parameters: 0,
-        })
+            parameters: vec![],
+            min_arity: 0,
+            max_arity: Some(0),
+        }))
     };
 }
 
@@ -192,10 +195,8 @@ fn test_conditionals() {
             Object::Integer(10),
             Object::Integer(3333);
             make!(OpTrue),
-            make!(OpJumpNotTruthy, [10]),
+            make!(OpJumpNotTruthy, [8]),
             make!(OpConstant, [0]),
-            make!(OpJump, [11]),
-            make!(OpNull),
             make!(OpPop),
             make!(OpConstant, [1]),
             make!(OpPop)
@@ -206,14 +207,41 @@ fn test_conditionals() {
             Object::Integer(20),
             Object::Integer(3333);
             make!(OpTrue),
-            make!(OpJumpNotTruthy, [10]),
+            make!(OpJumpNotTruthy, [11]),
             make!(OpConstant, [0]),
-            make!(OpJump, [13]),
+            make!(OpPop),
+            make!(OpJump, [15]),
             make!(OpConstant, [1]),
             make!(OpPop),
             make!(OpConstant, [2]),
             make!(OpPop)
         ),
+        make_test!(
+            "if (true) { 10 };";
+            Object::Integer(10);
+            make!(OpTrue),
+            make!(OpJumpNotTruthy, [10]),
+            make!(OpConstant, [0]),
+            make!(OpJump, [11]),
+            make!(OpNull),
+            make!(OpPop)
+        ),
+        make_test!(
+            "if (true) { 10 } else if (false) { 20 } else { 30 };";
+            Object::Integer(10),
+            Object::Integer(20),
+            Object::Integer(30);
+            make!(OpTrue),
+            make!(OpJumpNotTruthy, [10]),
+            make!(OpConstant, [0]),
+            make!(OpJump, [23]),
+            make!(OpFalse),
+            make!(OpJumpNotTruthy, [20]),
+            make!(OpConstant, [1]),
+            make!(OpJump, [23]),
+            make!(OpConstant, [2]),
+            make!(OpPop)
+        ),
     ];
 
     run_compiler_tests(tests);
@@ -259,24 +287,54 @@ fn test_string_expressions() {
     let tests = vec![
         make_test!(
             "\"monkey\"";
-            Object::String(String::from("monkey"));
+            Object::from("monkey");
             make!(OpConstant, [0]),
             make!(OpPop)
         ),
         make_test!(
             "\"mon\" + \"key\"";
-            Object::String(String::from("mon")),
-            Object::String(String::from("key"));
+            Object::from("mon"),
+            Object::from("key");
             make!(OpConstant, [0]),
             make!(OpConstant, [1]),
             make!(OpAdd),
             make!(OpPop)
         ),
+        make_test!(
+            "\"key\" + \"key\"";
+            Object::from("key");
+            make!(OpConstant, [0]),
+            make!(OpConstant, [0]),
+            make!(OpAdd),
+            make!(OpPop)
+        ),
     ];
 
     run_compiler_tests(tests);
 }
 
+#[test]
+fn test_string_constants_are_interned() {
+    let long = "x".repeat(MAX_INTERNED_STRING_LEN + 1);
+    let input = format!("[\"a\", \"b\", \"a\", \"{long}\", \"{long}\"]");
+    let program = parse(input);
+    let mut compiler = Compiler::new();
+    compiler.compile(&program).unwrap();
+    let constants = compiler.bytecode().constants;
+
+    // Short strings are shared, long ones get a constant each
+    assert_eq!(constants.len(), 4);
+    let interner = compiler.interner.borrow();
+    match &constants[0] {
+        Object::String(value) => {
+            let id = interner.get("a").unwrap();
+            assert!(Rc::ptr_eq(value, interner.resolve(id)));
+        }
+        _ => panic!("expected a string constant"),
+    }
+    assert_eq!(constants[2], constants[3]);
+}
+
 #[test]
 fn test_array_literals() {
     let tests = vec![
@@ -372,6 +430,169 @@ fn test_hash_literals() {
     run_compiler_tests(tests);
 }
 
+#[test]
+fn test_set_literals() {
+    let tests = vec![
+        make_test!(
+            "#{}";
+            ;
+            make!(OpSet, [0]),
+            make!(OpPop)
+        ),
+        make_test!(
+            "#{1, 2 + 3}";
+            Object::Integer(1),
+            Object::Integer(2),
+            Object::Integer(3);
+            make!(OpConstant, [0]),
+            make!(OpConstant, [1]),
+            make!(OpConstant, [2]),
+            make!(OpAdd),
+            make!(OpSet, [2]),
+            make!(OpPop)
+        ),
+        make_test!(
+            "1 in #{1}";
+            Object::Integer(1),
+            Object::Integer(1);
+            make!(OpConstant, [0]),
+            make!(OpConstant, [1]),
+            make!(OpSet, [1]),
+            make!(OpIn),
+            make!(OpPop)
+        ),
+    ];
+
+    run_compiler_tests(tests);
+}
+
+#[test]
+fn test_destructuring_let() {
+    let tests = vec![
+        make_test!(
+            "let [a, b] = (1, 2);";
+            Object::Integer(1),
+            Object::Integer(2);
+            make!(OpConstant, [0]),
+            make!(OpConstant, [1]),
+            make!(OpTuple, [2]),
+            make!(OpDestructure, [2]),
+            make!(OpSetGlobal, [0]),
+            make!(OpSetGlobal, [1])
+        ),
+        make_test!(
+            "let {x} = {}; x";
+            Object::from("x");
+            make!(OpHash, [0]),
+            make!(OpConstant, [0]),
+            make!(OpDestructureHash, [1]),
+            make!(OpSetGlobal, [0]),
+            make!(OpGetGlobal, [0]),
+            make!(OpPop)
+        ),
+    ];
+
+    run_compiler_tests(tests);
+}
+
+#[test]
+fn test_match_expressions() {
+    let tests = vec![
+        make_test!(
+            "match (1) { 1 => 2, _ => 3 }";
+            Object::Integer(1),
+            Object::Integer(1),
+            Object::Integer(2),
+            Object::Integer(3);
+            make!(OpConstant, [0]),
+            make!(OpSetGlobal, [0]),
+            make!(OpGetGlobal, [0]),
+            make!(OpConstant, [1]),
+            make!(OpEqual),
+            make!(OpJumpNotTruthy, [22]),
+            make!(OpConstant, [2]),
+            make!(OpJump, [32]),
+            make!(OpConstant, [3]),
+            make!(OpJump, [32]),
+            make!(OpGetGlobal, [0]),
+            make!(OpMatchFail),
+            make!(OpPop)
+        ),
+        make_test!(
+            "match ([]) { [x, ..] => x }";
+            Object::Integer(0);
+            make!(OpArray, [0]),
+            make!(OpSetGlobal, [0]),
+            make!(OpGetGlobal, [0]),
+            make!(OpMatchArray, [1, 1]),
+            make!(OpJumpNotTruthy, [32]),
+            make!(OpGetGlobal, [0]),
+            make!(OpConstant, [0]),
+            make!(OpIndex),
+            make!(OpSetGlobal, [1]),
+            make!(OpGetGlobal, [1]),
+            make!(OpJump, [36]),
+            make!(OpGetGlobal, [0]),
+            make!(OpMatchFail),
+            make!(OpPop)
+        ),
+    ];
+
+    run_compiler_tests(tests);
+}
+
+#[test]
+fn test_try_expressions() {
+    let tests = vec![
+        make_test!(
+            "try { 1 } catch (e) { 2 }";
+            Object::Integer(1),
+            Object::Integer(2);
+            make!(OpTry, [10]),
+            make!(OpConstant, [0]),
+            make!(OpEndTry),
+            make!(OpJump, [16]),
+            make!(OpSetGlobal, [0]),
+            make!(OpConstant, [1]),
+            make!(OpPop)
+        ),
+        make_test!(
+            "try { 1 } finally { 2 }";
+            Object::Integer(1),
+            Object::Integer(2),
+            Object::Integer(2);
+            make!(OpTry, [14]),
+            make!(OpConstant, [0]),
+            make!(OpEndTry),
+            make!(OpConstant, [1]),
+            make!(OpPop),
+            make!(OpJump, [19]),
+            make!(OpConstant, [2]),
+            make!(OpPop),
+            make!(OpThrow),
+            make!(OpPop)
+        ),
+        make_test!(
+            "throw 1";
+            Object::Integer(1);
+            make!(OpConstant, [0]),
+            make!(OpThrow)
+        ),
+        make_test!(
+            "string.parse_int(\"x\")?";
+            Object::from("x");
+            make!(OpGetBuiltin, [28]),
+            make!(OpConstant, [0]),
+            make!(OpCall, [1]),
+            make!(OpJumpNotError, [11]),
+            make!(OpReturnValue),
+            make!(OpPop)
+        ),
+    ];
+
+    run_compiler_tests(tests);
+}
+
 #[test]
 fn test_index_expressions() {
     let tests = vec![
@@ -410,7 +631,7 @@ fn test_index_expressions() {
         // String indexing, not supported in the book
         make_test!(
             "\"monkey\"[1]";
-            Object::String(String::from("monkey")),
+            Object::from("monkey"),
             Object::Integer(1);
             make!(OpConstant, [0]),
             make!(OpConstant, [1]),
@@ -481,7 +702,7 @@ fn test_index_expressions() {
         // String slicing, not supported in the book
         make_test!(
             "\"monkey\"[1:2]";
-            Object::String(String::from("monkey")),
+            Object::from("monkey"),
             Object::Integer(1),
             Object::Integer(2);
             make!(OpConstant, [0]),
@@ -750,6 +971,176 @@ fn test_builtins() {
             make!(OpClosure, [0, 0]),
             make!(OpPop)
         ),
+        make_test!(
+            "math.max(1, 2); string";
+            Object::Integer(1),
+            Object::Integer(2);
+            make!(OpGetBuiltin, [11]),
+            make!(OpConstant, [0]),
+            make!(OpConstant, [1]),
+            make!(OpCall, [2]),
+            make!(OpPop),
+            make!(OpGetBuiltinModule, [1]),
+            make!(OpPop)
+        ),
+        make_test!(
+            "let math = {}; math.max";
+            Object::from("max");
+            make!(OpHash, [0]),
+            make!(OpSetGlobal, [0]),
+            make!(OpGetGlobal, [0]),
+            make!(OpMember, [0, 0]),
+            make!(OpPop)
+        ),
+    ];
+
+    run_compiler_tests(tests);
+
+    let program = Parser::new(Lexer::new("math.nope".to_string())).parse_program();
+    let err = Compiler::new().compile(&program).unwrap_err();
+    assert_eq!(err.to_string(), "module math has no member nope");
+    assert_eq!(err.downcast_ref::<RuntimeError>().map(|err| err.kind), Some(ErrorKind::Name));
+}
+
+#[test]
+fn test_structs() {
+    let shape = Rc::new(Shape {
+        name: "Point".to_string(),
+        fields: vec!["x".to_string(), "y".to_string()],
+        enum_name: None,
+    });
+    let tests = vec![make_test!(
+        "struct Point { x, y }; Point(1, 2).x";
+        Object::StructType(shape),
+        Object::Integer(1),
+        Object::Integer(2),
+        Object::from("x");
+        make!(OpConstant, [0]),
+        make!(OpSetGlobal, [0]),
+        make!(OpGetGlobal, [0]),
+        make!(OpConstant, [1]),
+        make!(OpConstant, [2]),
+        make!(OpCall, [2]),
+        make!(OpMember, [3, 0]),
+        make!(OpPop)
+    )];
+
+    run_compiler_tests(tests);
+}
+
+#[test]
+fn test_enums() {
+    let circle = Shape::variant("Shape", "Circle", &["r".to_string()]);
+    let empty = Shape::variant("Shape", "Empty", &[]);
+    let tests = vec![make_test!(
+        "enum Shape { Circle(r), Empty }; match (Empty) { Circle(r) => r, Empty => 0 }";
+        circle,
+        empty,
+        Object::Integer(0);
+        make!(OpConstant, [0]),
+        make!(OpSetGlobal, [0]),
+        make!(OpConstant, [1]),
+        make!(OpSetGlobal, [1]),
+        make!(OpGetGlobal, [1]),
+        make!(OpSetGlobal, [2]),
+        make!(OpGetGlobal, [2]),
+        make!(OpGetGlobal, [0]),
+        make!(OpMatchConstructor, [1]),
+        make!(OpJumpNotTruthy, [45]),
+        make!(OpGetGlobal, [2]),
+        make!(OpField, [0]),
+        make!(OpSetGlobal, [3]),
+        make!(OpGetGlobal, [3]),
+        make!(OpJump, [67]),
+        make!(OpGetGlobal, [2]),
+        make!(OpGetGlobal, [1]),
+        make!(OpMatchConstructor, [0]),
+        make!(OpJumpNotTruthy, [63]),
+        make!(OpConstant, [2]),
+        make!(OpJump, [67]),
+        make!(OpGetGlobal, [2]),
+        make!(OpMatchFail),
+        make!(OpPop)
+    ), make_test!(
+        "match (1) { Missing => 0 }";
+        Object::Integer(1),
+        Object::from("Missing"),
+        Object::Integer(0);
+        make!(OpConstant, [0]),
+        make!(OpSetGlobal, [0]),
+        make!(OpGetGlobal, [0]),
+        make!(OpUndefined, [1]),
+        make!(OpMatchConstructor, [0]),
+        make!(OpJumpNotTruthy, [24]),
+        make!(OpConstant, [2]),
+        make!(OpJump, [28]),
+        make!(OpGetGlobal, [0]),
+        make!(OpMatchFail),
+        make!(OpPop)
+    )];
+
+    run_compiler_tests(tests);
+}
+
+#[test]
+fn test_optional_parameters() {
+    let function = Object::CompiledFunction(Rc::new(CompiledFunction {
+        instructions: concat_instructions(vec![
+            make!(OpDefault, [0, 9]),
+            make!(OpConstant, [0]),
+            make!(OpSetLocal, [0]),
+            make!(OpGetLocal, [0]),
+            make!(OpReturnValue),
+        ]),
+        num_locals: 1,
+        parameters: vec!["a".to_string()],
+        min_arity: 0,
+        max_arity: Some(1),
+    }));
+    let tests = vec![make_test!(
+        "fn(a = 1) { a }(a = 2)";
+        Object::Integer(1),
+        function,
+        Object::Integer(2),
+        Object::array(vec![Object::from("a")]);
+        make!(OpClosure, [1, 0]),
+        make!(OpConstant, [2]),
+        make!(OpCallKeywords, [1, 3]),
+        make!(OpPop)
+    )];
+
+    run_compiler_tests(tests);
+}
+
+#[test]
+fn test_method_calls() {
+    let tests = vec![
+        make_test!(
+            "[1].len()";
+            Object::Integer(1),
+            Object::from("len");
+            make!(OpGetBuiltin, [0]),
+            make!(OpConstant, [0]),
+            make!(OpArray, [1]),
+            make!(OpCallMethod, [1, 0]),
+            make!(OpPop)
+        ),
+        make_test!(
+            "[].sort()";
+            Object::from("sort");
+            make!(OpNull),
+            make!(OpArray, [0]),
+            make!(OpCallMethod, [0, 0]),
+            make!(OpPop)
+        ),
+        make_test!(
+            "string.upper(\"a\")";
+            Object::from("a");
+            make!(OpGetBuiltin, [16]),
+            make!(OpConstant, [0]),
+            make!(OpCall, [1]),
+            make!(OpPop)
+        ),
     ];
 
     run_compiler_tests(tests);
@@ -1,41 +1,103 @@
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use std::{
+    cell::RefCell,
+    cmp::Ordering,
+    fmt::{self, Display, Formatter},
+    mem,
+    rc::Rc,
+};
 
-use crate::object::{environment::Environment, HashKey, Object, builtins};
+use crate::object::{
+    arguments::Signature, builtins, collector::Collector, environment::Environment, error::{self, Thrown}, ordered_map::OrderedMap, sequence_index, slice_bounds, Function,
+    HashKey, Module, Object, Shape,
+};
 use crate::token;
+use crate::raise;
 use crate::{
     lexer::token::Token,
-    parser::ast::{Expression, Node, Program, Statement},
+    module::{module_name, ModuleLoader},
+    parser::ast::{Binding, Expression, MatchArm, Node, Parameter, Pattern, Program, Statement},
 };
-use anyhow::{anyhow, Result};
+use anyhow::Result;
 
 #[cfg(test)]
 mod tests;
 
+// An error value returned early by `?`. It unwinds through expressions as an
+// Err, passing catch blocks by, until the enclosing function call (or the
+// program) turns it back into the return value.
+#[derive(Debug)]
+struct EarlyReturn(Thrown);
+
+impl Display for EarlyReturn {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "early return of {}", self.0)
+    }
+}
+
+impl std::error::Error for EarlyReturn {}
+
+// Turn an error value returned early by `?` back into a value
+fn early_return(result: Result<Object>) -> Result<Object> {
+    match result {
+        Err(err) => match err.downcast::<EarlyReturn>() {
+            Ok(EarlyReturn(thrown)) => Ok(Object::Error(thrown.take())),
+            Err(err) => Err(err),
+        },
+        result => result,
+    }
+}
+
 pub struct Evaluator {
     env: Rc<RefCell<Environment>>,
+    collector: Rc<RefCell<Collector>>,
+    modules: Rc<RefCell<ModuleLoader<Object>>>,
 }
 
 impl Default for Evaluator {
     // Create a new evaluator with a default environment
     fn default() -> Self {
-        Self::new(Rc::new(RefCell::new(Environment::default())))
+        let mut collector = Collector::new();
+        let env = collector.track(Environment::default());
+        Evaluator {
+            env,
+            collector: Rc::new(RefCell::new(collector)),
+            modules: Rc::new(RefCell::new(ModuleLoader::default())),
+        }
+    }
+}
+
+impl Drop for Evaluator {
+    // Free the environment cycles left behind once the last evaluator sharing
+    // the collector goes away
+    fn drop(&mut self) {
+        if Rc::strong_count(&self.collector) == 1 {
+            drop(mem::take(&mut self.env));
+            drop(mem::take(&mut *self.modules.borrow_mut()));
+            self.collector.borrow_mut().collect();
+        }
     }
 }
 
 impl Evaluator {
-    pub fn new(env: Rc<RefCell<Environment>>) -> Self {
-        Evaluator { env }
+    // Set the loader used to find and cache imported modules
+    pub fn set_module_loader(&mut self, loader: ModuleLoader<Object>) {
+        *self.modules.borrow_mut() = loader;
+    }
+
+    // Get the number of environments that are still alive
+    pub fn live_environments(&self) -> usize {
+        self.collector.borrow().live()
     }
 
     // Entry-point to evaluate a program
     pub fn eval(&mut self, program: &Program) -> Result<Object> {
-        self.eval_node(Node::Program(program))
+        early_return(self.eval_node(Node::Program(program)))
     }
 
     // Evaluate a AST node
     fn eval_node(&mut self, node: Node) -> Result<Object> {
         stacker::maybe_grow(32 * 1024, 1024 * 1024, || match node {
-            Node::Program(program) => self.eval_program(&program),
+            Node::Program(program) => self.eval_program(program),
             Node::Statement(statement) => self.eval_statement(statement),
             Node::Expression(expression) => self.eval_expression(expression),
         })
@@ -46,7 +108,7 @@ impl Evaluator {
         let mut result = Ok(Object::Null);
 
         for statement in &program.statements {
-            result = self.eval_node(Node::Statement(&statement));
+            result = self.eval_node(Node::Statement(statement));
 
             match result {
                 Ok(Object::ReturnValue(value)) => return Ok(*value),
@@ -63,7 +125,7 @@ impl Evaluator {
         let mut result = Ok(Object::Null);
 
         for statement in statements {
-            result = self.eval_node(Node::Statement(&statement));
+            result = self.eval_node(Node::Statement(statement));
 
             match result {
                 Ok(Object::ReturnValue(_)) | Err(_) => return result,
@@ -78,63 +140,147 @@ impl Evaluator {
     fn eval_statement(&mut self, statement: &Statement) -> Result<Object> {
         match statement {
             Statement::Expression(expression) => self.eval_node(Node::Expression(expression)),
-            Statement::Block(block) => self.eval_block_statement(&block),
+            Statement::Block(block) => self.eval_block_statement(block),
             Statement::Return(expression) => Ok(Object::ReturnValue(Box::new(
                 self.eval_node(Node::Expression(expression))?,
             ))),
-            Statement::Let(name, expression) => self.eval_let_statement(name, expression),
+            Statement::Throw(expression) => {
+                Err(error::throw(self.eval_node(Node::Expression(expression))?))
+            }
+            Statement::Let(binding, expression) => self.eval_let_statement(binding, expression),
+            Statement::Import(path, name) => {
+                let module = self.import_module(path)?;
+                self.env.borrow_mut().set(name, module);
+                Ok(Object::Null)
+            }
+            Statement::Struct(name, fields) => {
+                let shape = Shape {
+                    name: name.clone(),
+                    fields: fields.clone(),
+                    enum_name: None,
+                };
+                self.env.borrow_mut().set(name, Object::StructType(Rc::new(shape)));
+                Ok(Object::Null)
+            }
+            Statement::Enum(name, variants) => {
+                for (variant, fields) in variants {
+                    let constructor = Shape::variant(name, variant, fields);
+                    self.env.borrow_mut().set(variant, constructor);
+                }
+                Ok(Object::Null)
+            }
+        }
+    }
+
+    // Import a module, evaluating its file in a new environment the first time
+    fn import_module(&mut self, path: &str) -> Result<Object> {
+        let path = self.modules.borrow().resolve(path)?;
+        if let Some(module) = self.modules.borrow().get(&path) {
+            return Ok(module);
         }
+
+        let program = self.modules.borrow_mut().enter(&path)?;
+        let mut evaluator = Evaluator {
+            env: self.collector.borrow_mut().track(Environment::default()),
+            collector: self.collector.clone(),
+            modules: self.modules.clone(),
+        };
+        let result = evaluator.eval(&program);
+        self.modules.borrow_mut().leave();
+        result?;
+
+        let members = evaluator.env.borrow().bindings();
+        let module = Object::Module(Rc::new(Module {
+            name: module_name(&path),
+            members: members.into_iter().collect(),
+        }));
+        self.modules.borrow_mut().insert(path, module.clone());
+        Ok(module)
     }
 
     // Evaluate a let statement
-    fn eval_let_statement(&mut self, name: &String, expression: &Expression) -> Result<Object> {
+    fn eval_let_statement(&mut self, binding: &Binding, expression: &Expression) -> Result<Object> {
         let value = self.eval_node(Node::Expression(expression))?;
-        self.env.borrow_mut().set(name, value.clone());
+        let values = match binding {
+            Binding::Name(_) => vec![value],
+            Binding::Sequence(names) => value.destructure_sequence(names.len())?,
+            Binding::Hash(names) => {
+                let keys = names.iter().map(String::as_str).collect::<Vec<_>>();
+                value.destructure_hash(&keys)?
+            }
+        };
+
+        let mut env = self.env.borrow_mut();
+        for (name, value) in binding.names().iter().zip(values) {
+            env.set(name, value);
+        }
         Ok(Object::Null)
     }
 
     // Evaluate an expression node
     fn eval_expression(&mut self, expression: &Expression) -> Result<Object> {
         match expression {
-            Expression::Integer(value) => Ok(Object::Integer(value.clone())),
-            Expression::Boolean(value) => Ok(Object::Boolean(value.clone())),
-            Expression::String(value) => Ok(Object::String(value.clone())),
+            Expression::Integer(value) => Ok(Object::Integer(*value)),
+            Expression::Boolean(value) => Ok(Object::Boolean(*value)),
+            Expression::String(value) => Ok(value.as_str().into()),
             Expression::Array(value) => self.eval_array_literal_expression(value),
+            Expression::Tuple(value) => self.eval_tuple_literal_expression(value),
             Expression::Hash(value) => self.eval_hash_literal_expression(value),
+            Expression::Set(value) => self.eval_set_literal_expression(value),
             Expression::Prefix(op, right) => self.eval_prefix_expression(op, right),
             Expression::Infix(op, left, right) => self.eval_infix_expression(op, left, right),
-            Expression::If(condition, consequence, alternative) => {
-                self.eval_if_expression(condition, consequence, alternative)
-            }
+            Expression::If(branches, alternative) => self.eval_if_expression(branches, alternative),
             Expression::Identifier(name) => self.eval_identifier_expression(name),
-            Expression::Function(params, body, _) => Ok(Object::Function(
-                params.clone(),
-                body.clone(),
-                self.env.clone(),
-            )),
-            Expression::Call(function, args) => self.eval_function_call_expression(function, args),
+            Expression::Function(params, body, _) => Ok(Object::Function(Rc::new(Function {
+                parameters: params.clone(),
+                body: *body.clone(),
+                env: self.env.clone(),
+            }))),
+            Expression::Call(function, args, keywords) => {
+                self.eval_function_call_expression(function, args, keywords)
+            }
             Expression::Index(left, index) => self.eval_index_expression(left, index),
             Expression::SliceIndex(left, start, stop) => {
                 self.eval_slice_index_expression(left, start, stop)
             }
+            Expression::Match(subject, arms) => self.eval_match_expression(subject, arms),
+            Expression::Try(block, catch, finally) => {
+                self.eval_try_expression(block, catch, finally)
+            }
+            Expression::Member(object, name) => {
+                self.eval_node(Node::Expression(object))?.member(name)
+            }
+            Expression::Propagate(value) => match self.eval_node(Node::Expression(value))? {
+                Object::Error(error) => Err(EarlyReturn(Thrown::new(error)).into()),
+                value => Ok(value),
+            },
         }
     }
 
     // Evaluate an array literal expression
-    fn eval_array_literal_expression(&mut self, elements: &Vec<Expression>) -> Result<Object> {
+    fn eval_array_literal_expression(&mut self, elements: &[Expression]) -> Result<Object> {
+        let elements = elements
+            .iter()
+            .map(|e| self.eval_node(Node::Expression(e)))
+            .collect::<Result<Vec<Object>>>()?;
+        Ok(Object::array(elements))
+    }
+
+    // Evaluate a tuple literal expression
+    fn eval_tuple_literal_expression(&mut self, elements: &[Expression]) -> Result<Object> {
         let elements = elements
             .iter()
             .map(|e| self.eval_node(Node::Expression(e)))
             .collect::<Result<Vec<Object>>>()?;
-        Ok(Object::Array(elements))
+        Ok(Object::tuple(elements))
     }
 
     // Evaluate a hash literal expression
     fn eval_hash_literal_expression(
         &mut self,
-        elements: &Vec<(Expression, Expression)>,
+        elements: &[(Expression, Expression)],
     ) -> Result<Object> {
-        let mut pairs = HashMap::new();
+        let mut pairs = OrderedMap::new();
 
         for (key, value) in elements {
             let key = self.eval_node(Node::Expression(key))?;
@@ -143,13 +289,25 @@ impl Evaluator {
             let key_type = key.type_name();
             let key: HashKey = match key.into() {
                 Some(key) => key,
-                None => return Err(anyhow!("unusable as hash key: {}", key_type)),
+                None => return Err(raise!(Type, "unusable as hash key: {}", key_type)),
             };
 
             pairs.insert(key, value);
         }
 
-        Ok(Object::Hash(pairs))
+        Ok(Object::hash(pairs))
+    }
+
+    // Evaluate a set literal expression
+    fn eval_set_literal_expression(&mut self, elements: &[Expression]) -> Result<Object> {
+        let mut set = OrderedMap::new();
+
+        for element in elements {
+            let element = self.eval_node(Node::Expression(element))?;
+            set.insert(element.to_set_element()?, ());
+        }
+
+        Ok(Object::set(set))
     }
 
     // Evaluate a prefix expression
@@ -159,9 +317,9 @@ impl Evaluator {
             token!(!) => Ok(Object::Boolean(!right.is_truthy())),
             token!(-) => match right {
                 Object::Integer(value) => Ok(Object::Integer(-value)),
-                _ => Err(anyhow!("unknown operator: {}{}", op, right.type_name())),
+                _ => Err(raise!(Type, "unknown operator: {}{}", op, right.type_name())),
             },
-            _ => Err(anyhow!("unknown operator: {}{}", op, right.type_name())),
+            _ => Err(raise!(Type, "unknown operator: {}{}", op, right.type_name())),
         }
     }
 
@@ -176,25 +334,28 @@ impl Evaluator {
         let right = self.eval_node(Node::Expression(right))?;
 
         match (op, &left, &right) {
+            (token!(==), left, right) => Ok(Object::Boolean(left == right)),
+            (token!(!=), left, right) => Ok(Object::Boolean(left != right)),
+            (token!(<), left, right) => Ok(Object::Boolean(left.compare(right)? == Ordering::Less)),
+            (token!(>), left, right) => {
+                Ok(Object::Boolean(left.compare(right)? == Ordering::Greater))
+            }
+            (token!(IN), left, right) => Ok(Object::Boolean(right.contains(left)?)),
             (op, Object::Integer(left), Object::Integer(right)) => {
                 self.eval_integer_infix_expression(op, *left, *right)
             }
             (op, Object::String(left), Object::String(right)) => {
                 self.eval_string_infix_expression(op, left, right)
             }
-            (token!(==), Object::Boolean(left), Object::Boolean(right)) => {
-                Ok(Object::Boolean(left == right))
-            }
-            (token!(!=), Object::Boolean(left), Object::Boolean(right)) => {
-                Ok(Object::Boolean(left != right))
-            }
-            (_, left, right) if left.type_name() != right.type_name() => Err(anyhow!(
+            (_, left, right) if left.type_name() != right.type_name() => Err(raise!(
+                Type,
                 "type mismatch: {} {} {}",
                 left.type_name(),
                 op,
                 right.type_name()
             )),
-            _ => Err(anyhow!(
+            _ => Err(raise!(
+                Type,
                 "unknown operator: {} {} {}",
                 left.type_name(),
                 op,
@@ -215,11 +376,7 @@ impl Evaluator {
             token!(-) => Ok(Object::Integer(left - right)),
             token!(/) => Ok(Object::Integer(left / right)),
             token!(*) => Ok(Object::Integer(left * right)),
-            token!(<) => Ok(Object::Boolean(left < right)),
-            token!(>) => Ok(Object::Boolean(left > right)),
-            token!(==) => Ok(Object::Boolean(left == right)),
-            token!(!=) => Ok(Object::Boolean(left != right)),
-            _ => Err(anyhow!("unknown operator: INTEGER {} INTEGER", op)),
+            _ => Err(raise!(Type, "unknown operator: INTEGER {} INTEGER", op)),
         }
     }
 
@@ -227,34 +384,159 @@ impl Evaluator {
     fn eval_string_infix_expression(
         &mut self,
         op: &Token,
-        left: &String,
-        right: &String,
+        left: &str,
+        right: &str,
     ) -> Result<Object> {
         match op {
-            token!(+) => Ok(Object::String(left.to_owned() + right)),
-            token!(==) => Ok(Object::Boolean(left == right)),
-            token!(!=) => Ok(Object::Boolean(left != right)),
-            _ => Err(anyhow!("unknown operator: STRING {} STRING", op)),
+            token!(+) => Ok((left.to_owned() + right).into()),
+            _ => Err(raise!(Type, "unknown operator: STRING {} STRING", op)),
         }
     }
 
     // Evaluate an if expression
     fn eval_if_expression(
         &mut self,
-        condition: &Expression,
-        consequence: &Statement,
+        branches: &[(Expression, Statement)],
         alternative: &Option<Box<Statement>>,
     ) -> Result<Object> {
-        let condition = self.eval_node(Node::Expression(condition))?;
+        // Take the first branch whose condition is truthy
+        for (condition, consequence) in branches {
+            if self.eval_node(Node::Expression(condition))?.is_truthy() {
+                return self.eval_node(Node::Statement(consequence));
+            }
+        }
+
+        match alternative {
+            Some(alternative) => self.eval_node(Node::Statement(alternative)),
+            None => Ok(Object::Null),
+        }
+    }
+
+    // Evaluate a match expression. Each arm is tried in an environment of its
+    // own, so the names its pattern binds (and any let in its body) are only
+    // visible to its guard and body, and a failed arm leaves no trace.
+    fn eval_match_expression(&mut self, subject: &Expression, arms: &[MatchArm]) -> Result<Object> {
+        let subject = self.eval_node(Node::Expression(subject))?;
+
+        for arm in arms {
+            let env = Environment::new(Some(self.env.clone()));
+            let mut evaluator = Evaluator {
+                env: self.collector.borrow_mut().track(env),
+                collector: self.collector.clone(),
+                modules: self.modules.clone(),
+            };
+            if !evaluator.match_pattern(&arm.pattern, &subject)? {
+                continue;
+            }
+            if let Some(guard) = &arm.guard {
+                if !evaluator.eval_node(Node::Expression(guard))?.is_truthy() {
+                    continue;
+                }
+            }
+            return evaluator.eval_node(Node::Statement(&arm.body));
+        }
+
+        Err(raise!(Match, "no match arm for value: {}", subject))
+    }
+
+    // Evaluate a try expression. The catch block's name is set in the current
+    // environment, as with let. The finally block runs however the other
+    // blocks finish, and a return from it replaces their result.
+    fn eval_try_expression(
+        &mut self,
+        block: &Statement,
+        catch: &Option<(String, Box<Statement>)>,
+        finally: &Option<Box<Statement>>,
+    ) -> Result<Object> {
+        let result = match (self.eval_node(Node::Statement(block)), catch) {
+            (Err(err), Some((name, body))) if !err.is::<EarlyReturn>() => {
+                self.env.borrow_mut().set(name, error::catch(err));
+                self.eval_node(Node::Statement(body))
+            }
+            (result, _) => result,
+        };
+
+        if let Some(finally) = finally {
+            let value = self.eval_node(Node::Statement(finally))?;
+            if let Object::ReturnValue(_) = value {
+                return Ok(value);
+            }
+        }
+        result
+    }
+
+    // Check if a value matches a pattern, binding the names it captures
+    fn match_pattern(&mut self, pattern: &Pattern, value: &Object) -> Result<bool> {
+        match (pattern, value) {
+            (Pattern::Wildcard, _) => Ok(true),
+            (Pattern::Identifier(name), _) => {
+                self.env.borrow_mut().set(name, value.clone());
+                Ok(true)
+            }
+            (Pattern::Literal(literal), _) => {
+                Ok(self.eval_node(Node::Expression(literal))? == *value)
+            }
+            (Pattern::Array(elements, rest), Object::Array(values)) => {
+                self.match_sequence(elements, rest, values, Object::array)
+            }
+            (Pattern::Tuple(elements, rest), Object::Tuple(values)) => {
+                self.match_sequence(elements, rest, values, Object::tuple)
+            }
+            (Pattern::Constructor(name, patterns), _) => {
+                let constructor = self.eval_identifier_expression(name)?;
+                match value.constructed_by(&constructor)? {
+                    Some(fields) if fields.len() == patterns.len() => {
+                        for (pattern, value) in patterns.iter().zip(fields) {
+                            if !self.match_pattern(pattern, value)? {
+                                return Ok(false);
+                            }
+                        }
+                        Ok(true)
+                    }
+                    _ => Ok(false),
+                }
+            }
+            (Pattern::Hash(pairs), Object::Hash(values)) => {
+                for (key, pattern) in pairs {
+                    let key = self.eval_node(Node::Expression(key))?;
+                    let value = Option::<HashKey>::from(key).and_then(|key| values.get(&key));
+                    match value {
+                        Some(value) if self.match_pattern(pattern, value)? => {}
+                        _ => return Ok(false),
+                    }
+                }
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    // Check if the elements of an array or tuple match element patterns and a
+    // rest pattern, which is matched against the remaining elements
+    fn match_sequence(
+        &mut self,
+        elements: &[Pattern],
+        rest: &Option<Box<Pattern>>,
+        values: &[Object],
+        wrap: fn(Vec<Object>) -> Object,
+    ) -> Result<bool> {
+        let length_matches = match rest {
+            Some(_) => values.len() >= elements.len(),
+            None => values.len() == elements.len(),
+        };
+        if !length_matches {
+            return Ok(false);
+        }
 
-        if condition.is_truthy() {
-            self.eval_node(Node::Statement(consequence))
-        } else {
-            match alternative {
-                Some(alternative) => self.eval_node(Node::Statement(alternative)),
-                None => Ok(Object::Null),
+        for (pattern, value) in elements.iter().zip(values) {
+            if !self.match_pattern(pattern, value)? {
+                return Ok(false);
             }
         }
+        match rest {
+            Some(rest) => self.match_pattern(rest, &wrap(values[elements.len()..].to_vec())),
+            None => Ok(true),
+        }
     }
 
     // Evaluate an identifier expression
@@ -263,7 +545,8 @@ impl Evaluator {
             Some(value) => Ok(value.clone()),
             None => match builtins::get_builtin(name) {
                 Some(builtin) => Ok(Object::BuiltInFunction(builtin)),
-                None => Err(anyhow!("identifier not found: {}", name)),
+                None => builtins::get_builtin_module(name)
+                    .ok_or_else(|| raise!(Name, "identifier not found: {}", name)),
             },
         }
     }
@@ -272,31 +555,87 @@ impl Evaluator {
     fn eval_function_call_expression(
         &mut self,
         function: &Expression,
-        args: &Vec<Expression>,
+        args: &[Expression],
+        keywords: &[(String, Expression)],
     ) -> Result<Object> {
-        let function = self.eval_node(Node::Expression(function))?;
-        let args = args
-            .iter()
-            .map(|a| self.eval_node(Node::Expression(a)))
+        // A method call passes its receiver as the first argument, unless the
+        // method is a module member or hash field
+        let (function, receiver) = match function {
+            Expression::Member(object, name) => {
+                let receiver = self.eval_node(Node::Expression(object))?;
+                let in_scope = match self.env.borrow().get(name) {
+                    Some(value) => value,
+                    None => builtins::get_builtin(name).map_or(Object::Null, Object::BuiltInFunction),
+                };
+                match receiver.method(name, in_scope)? {
+                    (function, true) => (function, Some(receiver)),
+                    (function, false) => (function, None),
+                }
+            }
+            function => (self.eval_node(Node::Expression(function))?, None),
+        };
+        let args = receiver
+            .into_iter()
+            .map(Ok)
+            .chain(args.iter().map(|a| self.eval_node(Node::Expression(a))))
             .collect::<Result<Vec<Object>>>()?;
+        let keywords = keywords
+            .iter()
+            .map(|(name, a)| Ok((name, self.eval_node(Node::Expression(a))?)))
+            .collect::<Result<Vec<_>>>()?;
 
         // Get the function's parameters, body, and environment
-        let (params, body, env) = match function {
-            Object::Function(params, body, env) => (params, body, env),
-            // Built-in functions are called directly
-            Object::BuiltInFunction(builtin) => return builtin(args),
-            _ => return Err(anyhow!("not a function: {}", function.type_name())),
+        let function = match function {
+            Object::Function(function) => function,
+            // Built-in functions and struct constructors are called directly
+            Object::BuiltInFunction(builtin) if keywords.is_empty() => return builtin(args),
+            Object::BuiltInFunction(_) => {
+                return Err(raise!(Argument, "keyword arguments not supported by builtin functions"))
+            }
+            Object::StructType(shape) => return shape.construct(args, keywords),
+            _ => return Err(raise!(Type, "not a function: {}", function.type_name())),
         };
 
-        // Extend the environment with the function's arguments
-        let mut env = Environment::new(Some(env));
-        for (param, arg) in params.iter().zip(args) {
-            env.set(param, arg);
+        // Bind the arguments to the parameters other than the rest parameter
+        let (rest_parameter, parameters) = match function.parameters.split_last() {
+            Some((Parameter::Rest(name), parameters)) => (Some(name), parameters),
+            _ => (None, &function.parameters[..]),
+        };
+        let names = parameters.iter().map(Parameter::name).collect::<Vec<_>>();
+        let signature = Signature {
+            name: None,
+            parameters: &names,
+            min_arity: parameters.iter().filter(|p| matches!(p, Parameter::Required(_))).count(),
+            max_arity: rest_parameter.is_none().then_some(names.len()),
+        };
+        let (bound, rest) = signature.bind(args, keywords)?;
+
+        // Extend the environment with the function's arguments, leaving
+        // parameters that weren't given null until their defaults are evaluated
+        let mut env = Environment::new(Some(function.env.clone()));
+        let mut defaults = vec![];
+        for (param, arg) in parameters.iter().zip(bound) {
+            if let (Parameter::Optional(name, default), None) = (param, &arg) {
+                defaults.push((name, default));
+            }
+            env.set(param.name(), arg.unwrap_or_default());
+        }
+        if let Some(name) = rest_parameter {
+            env.set(name, Object::array(rest));
         }
 
-        // Evaluate the function's body in the extended environment
-        let mut evaluator = Evaluator::new(Rc::new(RefCell::new(env)));
-        let evaluated = evaluator.eval_node(Node::Statement(&body));
+        // Evaluate the defaults and then the function's body in the extended
+        // environment, so defaults can refer to the parameters before them
+        let mut evaluator = Evaluator {
+            env: self.collector.borrow_mut().track(env),
+            collector: self.collector.clone(),
+            modules: self.modules.clone(),
+        };
+        for (name, default) in defaults {
+            let value = evaluator.eval_node(Node::Expression(default))?;
+            evaluator.env.borrow_mut().set(name, value);
+        }
+        let evaluated = early_return(evaluator.eval_node(Node::Statement(&function.body)));
 
         // Unwrap the return value if it exists
         match evaluated {
@@ -311,40 +650,32 @@ impl Evaluator {
         let index = self.eval_node(Node::Expression(index))?;
 
         match (&left, &index) {
-            (Object::Array(elements), Object::Integer(index)) => {
-                let index = if *index < 0 {
-                    elements.len() as i64 + *index
-                } else {
-                    *index
-                };
-                match elements.get(index as usize) {
-                    Some(element) => Ok(element.clone()),
+            (Object::Array(elements) | Object::Tuple(elements), Object::Integer(index)) => {
+                match sequence_index(elements.len(), *index) {
+                    Some(index) => Ok(elements[index].clone()),
                     None => Ok(Object::Null),
                 }
             }
             (Object::String(string), Object::Integer(index)) => {
-                let index = if *index < 0 {
-                    string.len() as i64 + *index
-                } else {
-                    *index
-                };
-                match string.chars().nth(index as usize) {
-                    Some(char) => Ok(Object::String(char.to_string())),
+                match sequence_index(string.chars().count(), *index) {
+                    Some(index) => Ok(string.chars().skip(index).take(1).collect::<String>().into()),
                     None => Ok(Object::Null),
                 }
             }
+            (Object::Error(error), Object::String(field)) => Ok(error.field(field)),
             (Object::Hash(values), _) => {
                 let index_type = index.type_name();
                 let key = match index.into() {
                     Some(key) => key,
-                    None => return Err(anyhow!("unusable as hash key: {}", index_type)),
+                    None => return Err(raise!(Type, "unusable as hash key: {}", index_type)),
                 };
                 match values.get(&key) {
                     Some(value) => Ok(value.clone()),
                     None => Ok(Object::Null),
                 }
             }
-            _ => Err(anyhow!(
+            _ => Err(raise!(
+                Type,
                 "index operator not supported: {}[{}]",
                 left.type_name(),
                 index.type_name()
@@ -369,56 +700,37 @@ impl Evaluator {
             None => None,
         };
 
+        let start = match start {
+            Some(Object::Integer(start)) => Some(start),
+            Some(_) => return Err(raise!(Type, "slice start must be an integer")),
+            None => None,
+        };
+        let stop = match stop {
+            Some(Object::Integer(stop)) => Some(stop),
+            Some(_) => return Err(raise!(Type, "slice stop must be an integer")),
+            None => None,
+        };
+
         match left {
             Object::Array(elements) => {
-                let start = match start {
-                    Some(Object::Integer(start)) if start < 0 => elements.len() as i64 + start,
-                    Some(Object::Integer(start)) if start > elements.len() as i64 => {
-                        elements.len() as i64
-                    }
-                    Some(Object::Integer(start)) => start,
-                    Some(_) => return Err(anyhow!("slice start must be an integer")),
-                    None => 0,
-                };
-                let stop = match stop {
-                    Some(Object::Integer(stop)) if stop < 0 => elements.len() as i64 + stop,
-                    Some(Object::Integer(stop)) if stop > elements.len() as i64 => {
-                        elements.len() as i64
-                    }
-                    Some(Object::Integer(stop)) => stop,
-                    Some(_) => return Err(anyhow!("slice stop must be an integer")),
-                    None => elements.len() as i64,
-                };
-                match elements.get(start as usize..stop as usize) {
-                    Some(elements) => Ok(Object::Array(elements.to_vec())),
-                    None => Ok(Object::Array(vec![])),
-                }
+                let (start, stop) = slice_bounds(elements.len(), start, stop);
+                Ok(Object::array(elements[start..stop].to_vec()))
+            }
+            Object::Tuple(elements) => {
+                let (start, stop) = slice_bounds(elements.len(), start, stop);
+                Ok(Object::tuple(elements[start..stop].to_vec()))
             }
             Object::String(string) => {
-                let start = match start {
-                    Some(Object::Integer(start)) if start < 0 => string.len() as i64 + start,
-                    Some(Object::Integer(start)) if start > string.len() as i64 => {
-                        string.len() as i64
-                    }
-                    Some(Object::Integer(start)) => start,
-                    Some(_) => return Err(anyhow!("slice start must be an integer")),
-                    None => 0,
-                };
-                let stop = match stop {
-                    Some(Object::Integer(stop)) if stop < 0 => string.len() as i64 + stop,
-                    Some(Object::Integer(stop)) if stop > string.len() as i64 => {
-                        string.len() as i64
-                    }
-                    Some(Object::Integer(stop)) => stop,
-                    Some(_) => return Err(anyhow!("slice stop must be an integer")),
-                    None => string.len() as i64,
-                };
-                match string.get(start as usize..stop as usize) {
-                    Some(string) => Ok(Object::String(string.to_string())),
-                    None => Ok(Object::String("".to_string())),
-                }
+                let (start, stop) = slice_bounds(string.chars().count(), start, stop);
+                Ok(string
+                    .chars()
+                    .skip(start)
+                    .take(stop - start)
+                    .collect::<String>()
+                    .into())
             }
-            _ => Err(anyhow!(
+            _ => Err(raise!(
+                Type,
                 "slice operator not supported: {}",
                 left.type_name()
             )),
@@ -1,7 +1,8 @@
 use crate::lexer::Lexer;
+use crate::module::ModuleLoader;
 use crate::object::HashKey;
 use crate::parser::Parser;
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 
 use super::*;
 
@@ -97,6 +98,9 @@ fn test_if_else_expressions() {
         ("if (1 > 2) { 10 }", "null"),
         ("if (1 > 2) { 10 } else { 20 }", "20"),
         ("if (1 < 2) { 10 } else { 20 }", "10"),
+        ("if (1 > 2) { 10 } else if (2 > 1) { 20 } else { 30 }", "20"),
+        ("if (1 > 2) { 10 } else if (2 > 3) { 20 } else { 30 }", "30"),
+        ("if (1 > 2) { 10 } else if (2 > 3) { 20 }", "null"),
     ];
 
     for (input, expected) in tests {
@@ -127,6 +131,8 @@ fn test_let_statements() {
         ("let a = 5 * 5; a;", 25),
         ("let a = 5; let b = a; b;", 5),
         ("let a = 5; let b = a; let c = a + b + 5; c;", 15),
+        ("let x1 = 5; let x2 = x1 * 2; x2;", 10),
+        ("let café = 3; let 数 = 4; café + 数;", 7),
     ];
 
     for (input, expected) in tests {
@@ -179,9 +185,9 @@ fn test_function_object() {
     let evaluated = eval_test(input).unwrap();
 
     assert!(matches!(evaluated,
-        Object::Function(params, body, _)
-            if params == vec!["x".to_string()]
-            && body.to_string() == "(x + 2)"
+        Object::Function(function)
+            if function.parameters == vec![Parameter::Required("x".to_string())]
+            && function.body.to_string() == "(x + 2)"
     ));
 }
 
@@ -202,6 +208,47 @@ fn test_function_application() {
     }
 }
 
+#[test]
+fn test_optional_parameters() {
+    let tests = vec![
+        (r#"let f = fn(a, b = 10) { a + b }; [f(1), f(1, 2)]"#, "[11, 3]"),
+        (r#"let f = fn(a, b = a * 2) { [a, b] }; f(3)"#, "[3, 6]"),
+        (r#"let f = fn(xs = []) { push(xs, 1) }; [f(), f()]"#, "[[1], [1]]"),
+        (r#"let n = 5; let f = fn(a = n) { a }; f()"#, "5"),
+        (r#"let make = fn(n) { fn(a = n + 1) { a } }; make(1)()"#, "2"),
+        (r#"let f = fn(a, ...rest) { [a, rest] }; [f(1), f(1, 2, 3)]"#, "[[1, []], [1, [2, 3]]]"),
+        (r#"let f = fn(a, b = 2, ...rest) { [a, b, rest] }; [f(1), f(1, 5, 6, 7)]"#, "[[1, 2, []], [1, 5, [6, 7]]]"),
+        (r#"let f = fn(a, b = 2, c = 3) { [a, b, c] }; [f(1, c = 30), f(b = 20, a = 10)]"#, "[[1, 2, 30], [10, 20, 3]]"),
+        (r#"struct Point { x, y }; Point(y = 2, x = 1)"#, "Point { x: 1, y: 2 }"),
+        (r#"let sum = fn(xs, acc = 0) { if (len(xs) == 0) { acc } else { sum(rest(xs), acc + first(xs)) } }; sum([1, 2, 3])"#, "6"),
+        (r#"let scale = fn(x, by = 2) { x * by }; [3.scale(), 3.scale(by = 3)]"#, "[6, 9]"),
+        (r#"let f = fn(a) { a }; try { f(b = 1) } catch (e) { e.kind }"#, "ArgumentError"),
+        // A failed method lookup leaves no keyword names behind for the next call
+        (r#"let h = {"x": 1}; [try { h.nosuch(y = 1) } catch (e) { e }, len([1])]"#, "[TypeError: unknown method: HASH.nosuch, 1]"),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = eval_test(input.to_string()).unwrap();
+        assert_eq!(evaluated.to_string(), expected);
+    }
+
+    let errors = vec![
+        (r#"fn(a, b = 1) { a }(1, 2, 3)"#, "wrong number of arguments: want=1..2, got=3"),
+        (r#"fn(a, ...rest) { a }()"#, "wrong number of arguments: want>=1, got=0"),
+        (r#"fn(a, b = 1) { a }(b = 2)"#, "missing argument a"),
+        (r#"fn(a, b = 1) { a }(1, a = 2)"#, "multiple values for argument a"),
+        (r#"fn(a, b = 2, ...rest) { b }(1, 2, 3, b = 4)"#, "multiple values for argument b"),
+        (r#"fn(...rest) { rest }(a = 1)"#, "unexpected keyword argument a"),
+        (r#"len(x = 1)"#, "keyword arguments not supported by builtin functions"),
+        (r#"struct Point { x, y }; Point(1, z = 2)"#, "unexpected keyword argument z"),
+    ];
+
+    for (input, expected) in errors {
+        let err = eval_test(input.to_string()).unwrap_err();
+        assert_eq!(err.to_string(), expected);
+    }
+}
+
 #[test]
 fn test_closures() {
     let input = String::from(
@@ -223,7 +270,7 @@ fn test_string_literal() {
     let input = String::from("\"Hello World!\"");
 
     let evaluated = eval_test(input).unwrap();
-    assert_eq!(evaluated, Object::String("Hello World!".to_string()));
+    assert_eq!(evaluated, Object::from("Hello World!"));
 }
 
 #[test]
@@ -231,7 +278,7 @@ fn test_string_concatenation() {
     let input = String::from("\"Hello\" + \" \" + \"World!\"");
 
     let evaluated = eval_test(input).unwrap();
-    assert_eq!(evaluated, Object::String("Hello World!".to_string()));
+    assert_eq!(evaluated, Object::from("Hello World!"));
 }
 
 #[test]
@@ -280,10 +327,10 @@ fn test_builtin_functions() {
         ),
         (
             "rest([1, 2, 3])",
-            Ok(Object::Array(vec![Object::Integer(2), Object::Integer(3)])),
+            Ok(Object::array(vec![Object::Integer(2), Object::Integer(3)])),
         ),
         ("rest([])", Ok(Object::Null)),
-        ("push([], 1)", Ok(Object::Array(vec![Object::Integer(1)]))),
+        ("push([], 1)", Ok(Object::array(vec![Object::Integer(1)]))),
         (
             "push(1, 1)",
             Err(anyhow!("argument to `push` must be ARRAY, got INTEGER")),
@@ -301,6 +348,13 @@ fn test_builtin_functions() {
     }
 }
 
+#[test]
+fn test_builtin_modules() {
+    // Qualified builtins are not in scope by their bare names
+    let evaluated = eval_test(r#"split("a,b", ",")"#.to_string());
+    assert_eq!(evaluated.unwrap_err().to_string(), "identifier not found: split");
+}
+
 #[test]
 fn test_array_literals() {
     let input = "[1, 2 * 2, 3 + 3]";
@@ -309,7 +363,7 @@ fn test_array_literals() {
 
     assert_eq!(
         evaluated,
-        Object::Array(vec![
+        Object::array(vec![
             Object::Integer(1),
             Object::Integer(4),
             Object::Integer(6),
@@ -338,7 +392,7 @@ fn test_array_indexing() {
         ("[1, 2, 3][-1]", Object::Integer(3)),
         (
             "[1, 2, 3][:]",
-            Object::Array(vec![
+            Object::array(vec![
                 Object::Integer(1),
                 Object::Integer(2),
                 Object::Integer(3),
@@ -346,24 +400,24 @@ fn test_array_indexing() {
         ),
         (
             "[1, 2, 3][1:]",
-            Object::Array(vec![Object::Integer(2), Object::Integer(3)]),
+            Object::array(vec![Object::Integer(2), Object::Integer(3)]),
         ),
-        ("[1, 2, 3][:1]", Object::Array(vec![Object::Integer(1)])),
-        ("[1, 2, 3][-1:]", Object::Array(vec![Object::Integer(3)])),
+        ("[1, 2, 3][:1]", Object::array(vec![Object::Integer(1)])),
+        ("[1, 2, 3][-1:]", Object::array(vec![Object::Integer(3)])),
         (
             "[1, 2, 3][:-1]",
-            Object::Array(vec![Object::Integer(1), Object::Integer(2)]),
+            Object::array(vec![Object::Integer(1), Object::Integer(2)]),
         ),
-        ("[1, 2, 3][1:2]", Object::Array(vec![Object::Integer(2)])),
+        ("[1, 2, 3][1:2]", Object::array(vec![Object::Integer(2)])),
         (
             "[1, 2, 3][1:3]",
-            Object::Array(vec![Object::Integer(2), Object::Integer(3)]),
+            Object::array(vec![Object::Integer(2), Object::Integer(3)]),
         ),
         (
             "[1, 2, 3][1:4]",
-            Object::Array(vec![Object::Integer(2), Object::Integer(3)]),
+            Object::array(vec![Object::Integer(2), Object::Integer(3)]),
         ),
-        ("[1, 2, 3][4:5]", Object::Array(vec![])),
+        ("[1, 2, 3][4:5]", Object::array(vec![])),
     ];
 
     for (input, expected) in tests {
@@ -375,23 +429,45 @@ fn test_array_indexing() {
 #[test]
 fn test_string_indexing() {
     let tests = vec![
-        ("\"Hello\"[0]", Object::String("H".to_string())),
-        ("\"Hello\"[1]", Object::String("e".to_string())),
-        ("\"Hello\"[2]", Object::String("l".to_string())),
-        ("\"Hello\"[3]", Object::String("l".to_string())),
-        ("\"Hello\"[4]", Object::String("o".to_string())),
+        ("\"Hello\"[0]", Object::from("H")),
+        ("\"Hello\"[1]", Object::from("e")),
+        ("\"Hello\"[2]", Object::from("l")),
+        ("\"Hello\"[3]", Object::from("l")),
+        ("\"Hello\"[4]", Object::from("o")),
         ("\"Hello\"[5]", Object::Null),
-        ("\"Hello\"[-1]", Object::String("o".to_string())),
-        ("\"Hello\"[1:]", Object::String("ello".to_string())),
-        ("\"Hello\"[:1]", Object::String("H".to_string())),
-        ("\"Hello\"[-1:]", Object::String("o".to_string())),
-        ("\"Hello\"[:-1]", Object::String("Hell".to_string())),
-        ("\"Hello\"[:]", Object::String("Hello".to_string())),
-        ("\"Hello\"[1:2]", Object::String("e".to_string())),
-        ("\"Hello\"[1:3]", Object::String("el".to_string())),
-        ("\"Hello\"[1:4]", Object::String("ell".to_string())),
-        ("\"Hello\"[1:5]", Object::String("ello".to_string())),
-        ("\"Hello\"[1:6]", Object::String("ello".to_string())),
+        ("\"Hello\"[-1]", Object::from("o")),
+        ("\"Hello\"[1:]", Object::from("ello")),
+        ("\"Hello\"[:1]", Object::from("H")),
+        ("\"Hello\"[-1:]", Object::from("o")),
+        ("\"Hello\"[:-1]", Object::from("Hell")),
+        ("\"Hello\"[:]", Object::from("Hello")),
+        ("\"Hello\"[1:2]", Object::from("e")),
+        ("\"Hello\"[1:3]", Object::from("el")),
+        ("\"Hello\"[1:4]", Object::from("ell")),
+        ("\"Hello\"[1:5]", Object::from("ello")),
+        ("\"Hello\"[1:6]", Object::from("ello")),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = eval_test(input.to_string()).unwrap();
+        assert_eq!(evaluated, expected);
+    }
+}
+
+#[test]
+fn test_unicode_strings() {
+    let tests = vec![
+        ("len(\"héllo\")", Object::Integer(5)),
+        ("len(\"日本語\")", Object::Integer(3)),
+        ("len(\"🐵🙈\")", Object::Integer(2)),
+        ("\"héllo\"[1]", Object::from("é")),
+        ("\"日本語\"[-1]", Object::from("語")),
+        ("\"日本語\"[3]", Object::Null),
+        ("\"héllo\"[1:3]", Object::from("él")),
+        ("\"日本語\"[1:]", Object::from("本語")),
+        ("\"🐵🙈🙉\"[:-1]", Object::from("🐵🙈")),
+        ("\"日本語\"[-10:2]", Object::from("日本")),
+        ("\"日本語\"[2:1]", Object::from("")),
     ];
 
     for (input, expected) in tests {
@@ -417,9 +493,9 @@ fn test_hash_literals() {
     );
 
     let tests = vec![
-        (Object::String("one".to_string()), Object::Integer(1)),
-        (Object::String("two".to_string()), Object::Integer(2)),
-        (Object::String("three".to_string()), Object::Integer(3)),
+        (Object::from("one"), Object::Integer(1)),
+        (Object::from("two"), Object::Integer(2)),
+        (Object::from("three"), Object::Integer(3)),
         (Object::Integer(4), Object::Integer(4)),
         (Object::Boolean(true), Object::Integer(5)),
         (Object::Boolean(false), Object::Integer(6)),
@@ -437,6 +513,385 @@ fn test_hash_literals() {
     }
 }
 
+#[test]
+fn test_shared_collections() {
+    // Collections are shared between bindings, so updates must never be visible
+    // through another reference
+    let tests = vec![
+        (r#"let a = [1, 2]; let b = push(a, 3); [a, b]"#, "[[1, 2], [1, 2, 3]]"),
+        (r#"let a = [1, 2]; let b = a; let c = array.pop(b); [a, b, c]"#, "[[1, 2], [1, 2], [1]]"),
+        (r#"let a = [3, 1]; let b = [a, array.insert(a, 0, 2), array.remove(a, 0)]; [a, b]"#, "[[3, 1], [[3, 1], [2, 3, 1], [1]]]"),
+        (r#"let h = {"a": 1}; let g = hash.merge(h, {"b": 2}); [h, g, hash.delete(g, "a"), g]"#, "[{a: 1}, {a: 1, b: 2}, {b: 2}, {a: 1, b: 2}]"),
+        (r#"let f = fn(xs) { push(xs, 0) }; let a = [1]; [f(a), f(a), a]"#, "[[1, 0], [1, 0], [1]]"),
+        (r#"push(push(push([], 1), 2), 3)"#, "[1, 2, 3]"),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = eval_test(input.to_string()).unwrap();
+        assert_eq!(evaluated.to_string(), expected);
+    }
+}
+
+#[test]
+fn test_hash_ordering() {
+    let tests = vec![
+        (r#"{"b": 1, "a": 2, 3: 3, true: 4}"#, r#"{b: 1, a: 2, 3: 3, true: 4}"#),
+        (r#"{"a": 1, "b": 2, "a": 3}"#, r#"{a: 3, b: 2}"#),
+        (r#"hash.delete({"c": 1, "b": 2, "a": 3}, "b")"#, r#"{c: 1, a: 3}"#),
+        (r#"hash.merge({"z": 1, "y": 2}, {"x": 3, "z": 4})"#, r#"{z: 4, y: 2, x: 3}"#),
+        (r#"hash.keys({"z": 1, "a": 2, "m": 3})"#, r#"[z, a, m]"#),
+        (r#"array.contains([{"b": 1, "a": 2}], {"a": 2, "b": 1})"#, "true"),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = eval_test(input.to_string()).unwrap();
+        assert_eq!(evaluated.to_string(), expected);
+    }
+}
+
+#[test]
+fn test_sets() {
+    let tests = vec![
+        (r#"#{3, 1, 2, 1}"#, "#{3, 1, 2}"),
+        (r#"#{}"#, "#{}"),
+        (r#"#{1, 2} == #{2, 1}"#, "true"),
+        (r#"#{1, 2} == #{1}"#, "false"),
+        (r#"2 in #{1, 2}"#, "true"),
+        (r#""a" in #{1, 2}"#, "false"),
+        (r#"1 + 1 in #{1, 2} == true"#, "true"),
+        (r#""b" in {"a": 1, "b": 2}"#, "true"),
+        (r#"3 in [1, 2]"#, "false"),
+        (r#""ell" in "hello""#, "true"),
+        (r#"set.union(#{1, 2}, #{3, 2})"#, "#{1, 2, 3}"),
+        (r#"set.intersection(#{1, 2, 3}, #{3, 2, 4})"#, "#{2, 3}"),
+        (r#"set.difference(#{1, 2, 3}, #{2})"#, "#{1, 3}"),
+        (r#"let s = #{1}; [set.add(s, 2), set.add(s, 1), s]"#, "[#{1, 2}, #{1}, #{1}]"),
+        (r#"let s = #{1, 2}; [set.remove(s, 1), set.remove(s, 3), s]"#, "[#{2}, #{1, 2}, #{1, 2}]"),
+        (r#"[len(#{1, 2, 2}), set.contains(#{true}, true)]"#, "[2, true]"),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = eval_test(input.to_string()).unwrap();
+        assert_eq!(evaluated.to_string(), expected);
+    }
+
+    let errors = vec![
+        (r#"#{[1]}"#, "unusable as set element: ARRAY"),
+        (r#"1 in 2"#, "unsupported types for in: INTEGER INTEGER"),
+        (r#"[1] in #{1}"#, "unusable as set element: ARRAY"),
+        (r#"set.union(#{1}, [1])"#, "argument to `union` must be SET, got ARRAY"),
+    ];
+
+    for (input, expected) in errors {
+        let evaluated = eval_test(input.to_string());
+        assert_eq!(evaluated.unwrap_err().to_string(), expected);
+    }
+}
+
+#[test]
+fn test_match_expressions() {
+    let tests = vec![
+        (r#"match (1) { 1 => "one", _ => "other" }"#, "one"),
+        (r#"match (5) { 1 => "one", _ => "other" }"#, "other"),
+        (r#"match (-2) { -2 => "negative", _ => "other" }"#, "negative"),
+        (r#"match ("hi") { "hi" => true, _ => false }"#, "true"),
+        (r#"match (true) { false => 0, true => 1 }"#, "1"),
+        (r#"let sum = fn(xs) { match (xs) { [] => 0, [x, ..rest] => x + sum(rest) } }; sum([1, 2, 3, 4])"#, "10"),
+        (r#"match ([1, 2]) { [a] => a, [a, b] => a * 10 + b }"#, "12"),
+        (r#"match ([1, [2, 3]]) { [_, [x, y]] => x + y, _ => 0 }"#, "5"),
+        (r#"match ([1, 2, 3]) { [x, ..] => x }"#, "1"),
+        (r#"match ([1, 2, 3]) { [first, ..rest] => rest }"#, "[2, 3]"),
+        (r#"match ((1, 2, 3)) { (_, ..rest) => rest }"#, "(2, 3)"),
+        (r#"match ((1, "a")) { (1, s) => s, _ => "none" }"#, "a"),
+        (r#"match ([1, 2]) { (a, b) => "tuple", [a, b] => "array" }"#, "array"),
+        (r#"let area = fn(s) { match (s) { {"type": "square", "side": n} => n * n, {"type": "rect", "w": w, "h": h} => w * h } }; [area({"type": "square", "side": 3}), area({"type": "rect", "w": 2, "h": 5, "extra": 1})]"#, "[9, 10]"),
+        (r#"let sign = fn(n) { match (n) { 0 => "zero", x if x < 0 => "negative", _ => "positive" } }; [sign(0), sign(-4), sign(7)]"#, "[zero, negative, positive]"),
+        (r#"match (3) { n => { let d = n * 2; d + 1 } }"#, "7"),
+        (r#"match (1) { 1 => { let x = 1; } }"#, "null"),
+        (r#"let f = fn(x) { match (x) { 1 => { return "early"; }, _ => "late" }; "after" }; [f(1), f(2)]"#, "[early, after]"),
+        (r#"match (match (2) { 2 => [2] }) { [n] if match (n) { 2 => true, _ => false } => "two", _ => "other" }"#, "two"),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = eval_test(input.to_string()).unwrap();
+        assert_eq!(evaluated.to_string(), expected);
+    }
+
+    let errors = vec![
+        (r#"match (3) { 1 => "one", 2 => "two" }"#, "no match arm for value: 3"),
+        (r#"match ([1, 2]) { [x] => x, {"a": a} => a }"#, "no match arm for value: [1, 2]"),
+    ];
+
+    for (input, expected) in errors {
+        let evaluated = eval_test(input.to_string());
+        assert_eq!(evaluated.unwrap_err().to_string(), expected);
+    }
+}
+
+#[test]
+fn test_members_and_methods() {
+    let tests = vec![
+        (r#"let h = {"name": "monkey"}; [h.name, h.missing]"#, "[monkey, null]"),
+        (r#"let h = {"a": {"b": 2}}; h.a.b + 1"#, "3"),
+        (r#"let h = {"greet": fn(x) { "hi " + x }}; h.greet("you")"#, "hi you"),
+        (r#""a,b".split(",")"#, "[a, b]"),
+        (r#"[3, 1, 2].sort().reverse()"#, "[3, 2, 1]"),
+        (r#"let n = -3; [n.abs(), [1, 2].len(), #{1}.add(2), {"a": 1}.keys()]"#, "[3, 2, #{1, 2}, [a]]"),
+        (r#"let double = fn(x) { x * 2 }; 5.double()"#, "10"),
+        (r#"let add = fn(a, b) { a + b }; let f = fn(x) { x.add(1) }; f(2)"#, "3"),
+        (r#"let len = fn(x) { "mine" }; [1].len()"#, "mine"),
+        (r#"let h = {"keys": fn() { 1 }}; h.keys()"#, "[keys]"),
+        (r#"let e = try { throw "boom"; } catch (e) { e }; [e.kind, e.message]"#, "[Error, boom]"),
+        (r#"let f = fn() { error("bad") }; f().message"#, "bad"),
+        (r#"try { {}.nope() } catch (e) { e.kind }"#, "TypeError"),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = eval_test(input.to_string()).unwrap();
+        assert_eq!(evaluated.to_string(), expected);
+    }
+
+    let errors = vec![
+        (r#"{}.nope()"#, "unknown method: HASH.nope"),
+        (r#"1.name"#, "member access not supported: INTEGER.name"),
+        (r#"true.f()"#, "unknown method: BOOLEAN.f"),
+    ];
+
+    for (input, expected) in errors {
+        let err = eval_test(input.to_string()).unwrap_err();
+        assert_eq!(err.to_string(), expected);
+    }
+}
+
+#[test]
+fn test_structs() {
+    let tests = vec![
+        (r#"struct Point { x, y }; let p = Point(1, 2); [p.x, p.y]"#, "[1, 2]"),
+        (r#"struct Point { x, y }; Point(1, "a")"#, "Point { x: 1, y: a }"),
+        (r#"struct Point { x, y }; Point"#, "<struct Point>"),
+        (r#"struct Empty {}; Empty()"#, "Empty {}"),
+        (r#"struct Point { x, y }; [Point(1, 2) == Point(1, 2), Point(1, 2) != Point(2, 1)]"#, "[true, true]"),
+        (r#"struct A { x }; struct B { x }; A(1) == B(1)"#, "false"),
+        (r#"struct Line { from, to }; struct Point { x, y }; let l = Line(Point(0, 0), Point(3, 4)); l.to.y - l.from.y"#, "4"),
+        (r#"struct Counter { step, next }; let c = Counter(2, fn(n) { n + 2 }); c.next(1)"#, "3"),
+        (r#"struct Point { x, y }; let norm = fn(p) { p.x * p.x + p.y * p.y }; Point(3, 4).norm()"#, "25"),
+        (r#"struct Point { x, y }; let make = fn(x) { Point(x, x) }; make(2) == make(2)"#, "true"),
+        (r#"struct Point { x, y }; try { Point(1, 2).z } catch (e) { e.kind }"#, "NameError"),
+        (r#"struct A { x, y }; struct B { y }; let gety = fn(v) { v.y }; [gety(A(1, 2)), gety(B(3)), gety(A(4, 5)), gety({"y": 6})]"#, "[2, 3, 5, 6]"),
+        (r#"struct A { y }; struct B { x }; let gety = fn(v) { v.y }; gety(A(1)); try { gety(B(2)) } catch (e) { e.message }"#, "struct B has no field y"),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = eval_test(input.to_string()).unwrap();
+        assert_eq!(evaluated.to_string(), expected);
+    }
+
+    let errors = vec![
+        (r#"struct Point { x, y }; Point(1, 2).z"#, "struct Point has no field z"),
+        (r#"struct Point { x, y }; Point(1)"#, "wrong number of arguments to Point: want=2, got=1"),
+        (r#"struct Point { x, y }; Point(1, 2).f()"#, "unknown method: Point.f"),
+    ];
+
+    for (input, expected) in errors {
+        let err = eval_test(input.to_string()).unwrap_err();
+        assert_eq!(err.to_string(), expected);
+    }
+}
+
+#[test]
+fn test_enums() {
+    let tests = vec![
+        (r#"enum Shape { Circle(r), Rect(w, h) }; [Circle(2), Rect(1, 2)]"#, "[Circle(2), Rect(1, 2)]"),
+        (r#"enum Shape { Circle(r), Empty }; [Circle, Empty]"#, "[<variant Shape.Circle>, Empty]"),
+        (r#"enum Shape { Circle(r), Rect(w, h) }; [Circle(1) == Circle(1), Circle(1) != Circle(2), Circle(1) == Rect(1, 1)]"#, "[true, true, false]"),
+        (r#"enum Shape { Circle(r), Empty }; [Empty == Empty, Circle(1) == Empty]"#, "[true, false]"),
+        (r#"enum A { X }; let a = X; enum B { X }; a == X"#, "false"),
+        (r#"enum Shape { Circle(r), Rect(w, h) }; let r = Rect(3, 4); [r.w, r.h]"#, "[3, 4]"),
+        (r#"enum Shape { Circle(r), Rect(w, h), Empty }; let area = fn(s) { match (s) { Circle(r) => 3 * r * r, Rect(w, h) => w * h, Empty => 0 } }; [area(Circle(2)), area(Rect(3, 4)), area(Empty)]"#, "[12, 12, 0]"),
+        (r#"enum Shape { Circle(r), Rect(w, h) }; match (Rect(2, 2)) { Rect(w, h) if w != h => "oblong", Rect(_, _) => "square" }"#, "square"),
+        (r#"enum Option { Some(value), None }; match (Some(Some(1))) { Some(None) => 0, Some(Some(x)) => x }"#, "1"),
+        (r#"enum Option { Some(value), None }; match (Some([1, 2])) { Some([a, b]) => a + b, None => 0 }"#, "3"),
+        (r#"struct Point { x, y }; match (Point(1, 2)) { Point(1, y) => y, _ => 0 }"#, "2"),
+        (r#"enum Shape { Circle(r) }; match (1) { Circle(r) => r, _ => "other" }"#, "other"),
+        (r#"enum Shape { Circle(r), Empty }; struct Point { x, y }; [type(Circle(1)), type(Empty), type(Point(1, 2)), type(1)]"#, "[Shape, Shape, Point, INTEGER]"),
+        (r#"enum Shape { Circle(r) }; try { match (Circle(1)) { Circle(0) => 0 } } catch (e) { e.kind }"#, "MatchError"),
+        (r#"match (1) { 1 => "one", Missing(x) => x }"#, "one"),
+        (r#"try { match (1) { Missing => 1, _ => 2 } } catch (e) { [e.kind, e.message] }"#, "[NameError, identifier not found: Missing]"),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = eval_test(input.to_string()).unwrap();
+        assert_eq!(evaluated.to_string(), expected);
+    }
+
+    let errors = vec![
+        (r#"enum Shape { Circle(r) }; Circle(1, 2)"#, "wrong number of arguments to Circle: want=1, got=2"),
+        (r#"enum Shape { Circle(r) }; Circle(1).d"#, "struct Circle has no field d"),
+        (r#"let Circle = 1; match (1) { Circle(r) => r }"#, "not a constructor: INTEGER"),
+        (r#"match (1) { Missing => 1 }"#, "identifier not found: Missing"),
+    ];
+
+    for (input, expected) in errors {
+        let err = eval_test(input.to_string()).unwrap_err();
+        assert_eq!(err.to_string(), expected);
+    }
+}
+
+#[test]
+fn test_exceptions() {
+    let tests = vec![
+        (r#"try { 1 } catch (e) { 2 }"#, "1"),
+        (r#"try { throw "boom"; 1 } catch (e) { e }"#, "Error: boom"),
+        (r#"try { throw "boom" } catch (e) { [e["kind"], e["message"]] }"#, "[Error, boom]"),
+        (r#"try { throw 42 } catch (e) { e["message"] }"#, "42"),
+        (r#"try { throw 42 } catch (e) { e["value"] + 1 }"#, "43"),
+        (r#"try { throw [1, "a"] } catch (e) { e.value }"#, "[1, a]"),
+        (r#"let f = try { throw fn(x) { x * 2 } } catch (e) { e.value }; f(21)"#, "42"),
+        (r#"try { try { throw {"a": 1} } catch (e) { throw e } } catch (e) { e.value["a"] }"#, "1"),
+        (r#"try { try { throw 1 } finally { try { throw 2 } catch (e) { e } } } catch (e) { e.value }"#, "1"),
+        (r#"try { 1 + true } catch (e) { e.value }"#, "null"),
+        (r#"try { 1 + true } catch (e) { e["kind"] }"#, "TypeError"),
+        (r#"try { missing } catch (e) { e["kind"] }"#, "NameError"),
+        (r#"try { 1(2) } catch (e) { e["kind"] }"#, "TypeError"),
+        (r#"try { len(1) } catch (e) { e["kind"] }"#, "ArgumentError"),
+        (r#"try { array.insert([], 5, 1) } catch (e) { e["kind"] }"#, "IndexError"),
+        (r#"try { math.pow(2, 64) } catch (e) { [e["kind"], e["message"]] }"#, "[RuntimeError, integer overflow in `pow`]"),
+        (r#"try { match (3) { 1 => 1 } } catch (e) { e["kind"] }"#, "MatchError"),
+        (r#"let f = fn(x) { if (x > 2) { throw "too big" }; x }; let g = fn(x) { f(x) * 10 }; [try { g(1) } catch (e) { 0 }, try { g(5) } catch (e) { e["message"] }]"#, "[10, too big]"),
+        (r#"try { 1 } finally { 2 }"#, "1"),
+        (r#"let f = fn() { let x = try { throw "a" } catch (e) { 1 } finally { 2 }; x }; f()"#, "1"),
+        (r#"try { try { throw "inner" } finally { 1 } } catch (e) { e["message"] }"#, "inner"),
+        (r#"try { try { throw "inner" } catch (e) { throw e["message"] + "!" } finally { 1 } } catch (e) { e["message"] }"#, "inner!"),
+        (r#"try { try { 1 + true } catch (e) { throw e } } catch (e) { e["kind"] }"#, "TypeError"),
+        (r#"let f = fn() { try { return 1; } finally { let y = 2; }; 2 }; f()"#, "1"),
+        (r#"let f = fn() { try { return 1; } finally { return 2; } }; f()"#, "2"),
+        (r#"let f = fn() { try { throw "x" } catch (e) { return e["message"]; }; "after" }; f()"#, "x"),
+        (r#"let f = fn(n) { if (n == 0) { throw "bottom" }; f(n - 1) }; try { f(50) } catch (e) { e["message"] }"#, "bottom"),
+        (r#"let e = "outer"; try { throw "inner" } catch (e) { e }; e["message"]"#, "inner"),
+        (r#"[1, try { throw "x" } catch (e) { 2 }, 3]"#, "[1, 2, 3]"),
+        (r#"try { throw "x" } catch (e) { }"#, "null"),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = eval_test(input.to_string()).unwrap();
+        assert_eq!(evaluated.to_string(), expected);
+    }
+
+    let errors = vec![
+        (r#"throw "boom""#, "Error: boom"),
+        (r#"try { throw "boom" } finally { 1 }"#, "Error: boom"),
+        (r#"try { missing } catch (e) { throw e }"#, "NameError: identifier not found: missing"),
+        (r#"try { 1 } catch (e) { 2 }; missing"#, "identifier not found: missing"),
+    ];
+
+    for (input, expected) in errors {
+        let evaluated = eval_test(input.to_string());
+        assert_eq!(evaluated.unwrap_err().to_string(), expected);
+    }
+}
+
+#[test]
+fn test_error_values() {
+    let tests = vec![
+        (r#"string.parse_int("42")"#, "42"),
+        (r#"string.parse_int(" -7 ")"#, "-7"),
+        (r#"string.parse_int("4x2")"#, "ValueError: invalid integer: 4x2"),
+        (r#"is_error(string.parse_int("x"))"#, "true"),
+        (r#"is_error(string.parse_int("1"))"#, "false"),
+        (r#"error("bad")"#, "Error: bad"),
+        (r#"error("ParseError", "bad")["kind"]"#, "ParseError"),
+        (r#"let f = fn(s) { let n = string.parse_int(s)?; n * 2 }; [f("21"), f("x")]"#, "[42, ValueError: invalid integer: x]"),
+        (r#"let f = fn(a, b) { string.parse_int(a)? + string.parse_int(b)? }; [f("1", "2"), f("1", "b")]"#, "[3, ValueError: invalid integer: b]"),
+        (r#"let f = fn(s) { [string.parse_int(s)?] }; let g = fn(s) { f(s)?[0] + 1 }; [g("1"), g("x")["message"]]"#, "[2, invalid integer: x]"),
+        (r#"let f = fn() { try { error("inner")? } catch (e) { "caught" } }; f()"#, "Error: inner"),
+        (r#"let f = fn() { try { error("inner")? } finally { 1 } }; f()"#, "Error: inner"),
+        (r#"let x = string.parse_int("x")?; 1"#, "ValueError: invalid integer: x"),
+        (r#"let f = fn(s) { -string.parse_int(s)? }; f("3")"#, "-3"),
+        (r#"is_error(io.read_file("/nonexistent/file.txt"))"#, "true"),
+        (r#"io.read_file("/nonexistent/file.txt")["kind"]"#, "IOError"),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = eval_test(input.to_string()).unwrap();
+        assert_eq!(evaluated.to_string(), expected);
+    }
+
+    let path = std::env::temp_dir().join("monkey_eval_test_error_values.txt");
+    let input = format!(
+        r#"let path = "{}"; io.write_file(path, "contents"); io.read_file(path)"#,
+        path.display()
+    );
+    assert_eq!(eval_test(input).unwrap().to_string(), "contents");
+    std::fs::remove_file(path).unwrap();
+}
+
+// Write the modules imported by the module tests into a new directory
+fn write_test_modules(dir: &std::path::Path) {
+    let _ = std::fs::remove_dir_all(dir);
+    std::fs::create_dir_all(dir.join("lib")).unwrap();
+    let marker = dir.join("marker.txt");
+    let util = format!(
+        r#"let seen = io.read_file("{0}"); io.write_file("{0}", "seen"); let name = "util"; let double = fn(x) {{ x * 2 }};"#,
+        marker.display()
+    );
+    std::fs::write(dir.join("util.monkey"), util).unwrap();
+    std::fs::write(
+        dir.join("lib").join("shapes.monkey"),
+        r#"import "../util.monkey" as u; let util_seen = u.seen; let area = fn(r) { u.double(r) * r };"#,
+    )
+    .unwrap();
+    std::fs::write(dir.join("cycle.monkey"), r#"import "main.monkey" as m;"#).unwrap();
+}
+
+#[test]
+fn test_modules() {
+    let dir = std::env::temp_dir().join("monkey_eval_test_modules");
+    write_test_modules(&dir);
+    let dir = dir.canonicalize().unwrap();
+    let tests: Vec<(&str, Result<&str, &str>)> = vec![
+        (r#"import "util.monkey" as u; u.double(21)"#, Ok("42")),
+        (r#"import "util.monkey" as u; let double = fn(x) { 0 }; u.double(21)"#, Ok("42")),
+        (r#"import "util.monkey" as u; [u.name, u]"#, Ok("[util, <module util>]")),
+        (r#"import "shapes.monkey" as s; s.area(3)"#, Ok("18")),
+        (r#"import "util.monkey" as u; import "shapes.monkey" as s; [is_error(u.seen), is_error(s.util_seen)]"#, Ok("[true, true]")),
+        (r#"import "util.monkey" as u; let name = "main"; [name, u.name]"#, Ok("[main, util]")),
+        (r#"import "cycle.monkey" as c;"#, Err("import cycle: {dir}/main.monkey -> {dir}/cycle.monkey -> {dir}/main.monkey")),
+        (r#"import "missing.monkey" as m;"#, Err("module not found: missing.monkey")),
+        (r#"import "util.monkey" as u; u.missing"#, Err("module util has no member missing")),
+        (r#"let x = 1; x.y"#, Err("member access not supported: INTEGER.y")),
+        (r#"try { let x = 1; x.y } catch (e) { e["kind"] }"#, Ok("TypeError")),
+    ];
+
+    for (input, expected) in tests {
+        let _ = std::fs::remove_file(dir.join("marker.txt"));
+        let main = dir.join("main.monkey");
+        std::fs::write(&main, input).unwrap();
+        let search_path = vec![dir.join("lib")];
+
+        let mut evaluator = Evaluator::default();
+        evaluator.set_module_loader(ModuleLoader::new(search_path, Some(&main)).unwrap());
+        let mut parser = Parser::new(Lexer::new(input.to_string()));
+        let evaluated = evaluator.eval(&parser.parse_program());
+        match expected {
+            Ok(expected) => assert_eq!(evaluated.unwrap().to_string(), expected),
+            Err(expected) => assert_eq!(
+                evaluated.unwrap_err().to_string(),
+                expected.replace("{dir}", &dir.display().to_string())
+            ),
+        }
+    }
+
+    // Names defined in a module stay in its namespace
+    let input = r#"import "util.monkey" as u; double"#;
+    std::fs::write(dir.join("main.monkey"), input).unwrap();
+    let mut evaluator = Evaluator::default();
+    evaluator.set_module_loader(ModuleLoader::new(vec![], Some(&dir.join("main.monkey"))).unwrap());
+    let mut parser = Parser::new(Lexer::new(input.to_string()));
+    let err = evaluator.eval(&parser.parse_program()).unwrap_err();
+    assert_eq!(err.to_string(), "identifier not found: double");
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
 #[test]
 fn test_hash_index() {
     let tests = vec![
@@ -454,3 +909,49 @@ fn test_hash_index() {
         assert_eq!(evaluated, expected);
     }
 }
+
+#[test]
+fn test_recursive_closures_are_collected() {
+    // Every call to spawn defines a recursive closure, whose environment holds
+    // the closure itself. Without collection each of these cycles would leak.
+    let input = r#"
+        let spawn = fn(n) {
+            let countdown = fn(x) { if (x == 0) { 0 } else { countdown(x - 1) } };
+            countdown(3);
+            if (n == 0) { 0 } else { spawn(n - 1) }
+        };
+    "#
+    .to_string()
+        + &"spawn(100);".repeat(50);
+
+    let program = Parser::new(Lexer::new(input)).parse_program();
+    let mut evaluator = Evaluator::default();
+    evaluator.eval(&program).unwrap();
+
+    let live = evaluator.live_environments();
+    assert!(live < 1000, "{} environments still alive", live);
+
+    // Dropping the evaluator frees the global environment's cycle as well
+    let env = Rc::downgrade(&evaluator.env);
+    drop(evaluator);
+    assert!(env.upgrade().is_none());
+}
+
+#[test]
+fn test_collection_keeps_reachable_closures() {
+    // Closures that are still reachable keep working across collections
+    let input = r#"
+        let make = fn(n) {
+            let count = fn(x) { if (x == 0) { n } else { count(x - 1) } };
+            count
+        };
+        let kept = [make(1), {"f": make(2)}];
+        let churn = fn(n) { if (n == 0) { 0 } else { make(n); churn(n - 1) } };
+    "#
+    .to_string()
+        + &"churn(200);".repeat(10)
+        + "[kept[0](5), kept[1][\"f\"](5)]";
+
+    let evaluated = eval_test(input).unwrap();
+    assert_eq!(evaluated.to_string(), "[1, 2]");
+}
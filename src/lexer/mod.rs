@@ -1,60 +1,81 @@
 pub mod token;
 
 use crate::token;
-use token::Token;
+use token::{Span, SpannedToken, Token};
+use unicode_xid::UnicodeXID;
 
 #[cfg(test)]
 mod tests;
 
 pub struct Lexer {
-    input: String,
+    input: Vec<char>,
     position: usize,
     read_position: usize,
     ch: char,
+    line: usize,
+    column: usize,
+    span: Span,
+    done: bool,
 }
 
 impl Lexer {
     pub fn new(input: String) -> Self {
         let mut lexer = Self {
-            input,
+            input: input.chars().collect(),
             position: 0,
             read_position: 0,
             ch: '\0',
+            line: 1,
+            column: 0,
+            span: Span { line: 1, column: 1 },
+            done: false,
         };
         // Read the first character
         lexer.read_char();
-        return lexer;
+        lexer
     }
 
     // Read the next character and advance the position
     fn read_char(&mut self) {
-        if self.read_position >= self.input.len() {
-            self.ch = '\0';
+        // Track the line and column of the next character
+        if self.ch == '\n' {
+            self.line += 1;
+            self.column = 1;
         } else {
-            self.ch = match self.input.chars().nth(self.read_position) {
-                Some(ch) => ch,
-                None => '\0',
-            };
+            self.column += 1;
         }
+        self.ch = match self.input.get(self.read_position) {
+            Some(ch) => *ch,
+            None => '\0',
+        };
         self.position = self.read_position;
         self.read_position += 1;
     }
 
     // Read the next character without advancing the position
     fn peek_char(&self) -> char {
-        if self.read_position >= self.input.len() {
-            return '\0';
-        } else {
-            return match self.input.chars().nth(self.read_position) {
-                Some(ch) => ch,
-                None => '\0',
-            };
+        match self.input.get(self.read_position) {
+            Some(ch) => *ch,
+            None => '\0',
+        }
+    }
+
+    // Read and return the next token along with its position
+    pub fn next_spanned_token(&mut self) -> SpannedToken {
+        let token = self.next_token();
+        SpannedToken {
+            token,
+            span: self.span,
         }
     }
 
     // Read and return the next token
     pub fn next_token(&mut self) -> Token {
         self.skip_whitespace();
+        self.span = Span {
+            line: self.line,
+            column: self.column,
+        };
 
         let token = match self.ch {
             // Read multi-character tokens
@@ -66,6 +87,23 @@ impl Lexer {
                 self.read_char();
                 token!(!=)
             }
+            '=' if self.peek_char() == '>' => {
+                self.read_char();
+                token!(=>)
+            }
+            '.' if self.peek_char() == '.' => {
+                self.read_char();
+                if self.peek_char() == '.' {
+                    self.read_char();
+                    token!(...)
+                } else {
+                    token!(..)
+                }
+            }
+            '#' if self.peek_char() == '{' => {
+                self.read_char();
+                token!("#{")
+            }
             // Read single character tokens
             '=' => token!(=),
             '+' => token!(+),
@@ -74,6 +112,8 @@ impl Lexer {
             ':' => token!(:),
             '-' => token!(-),
             '!' => token!(!),
+            '.' => token!(.),
+            '?' => token!(?),
             '*' => token!(*),
             '/' => token!(/),
             '<' => token!(<),
@@ -88,34 +128,34 @@ impl Lexer {
             // Read string
             '"' => self.read_string(),
             // Read identifier
-            c if is_letter(c) => return self.read_identifier(),
+            c if is_identifier_start(c) => return self.read_identifier(),
             // Read number
             c if c.is_ascii_digit() => return self.read_number(),
             _ => token!(ILLEGAL),
         };
 
         self.read_char();
-        return token;
+        token
     }
 
     // Read and return an identifier
     fn read_identifier(&mut self) -> Token {
         let position = self.position;
-        while is_letter(self.ch) {
+        while is_identifier_continue(self.ch) {
             self.read_char();
         }
-        let literal = &self.input[position..self.position];
-        return token::lookup_ident(literal);
+        let literal: String = self.input[position..self.position].iter().collect();
+        token::lookup_ident(&literal)
     }
 
-    // Read and return a number
+    // Read and return a number (digits are validated by the parser)
     fn read_number(&mut self) -> Token {
         let position = self.position;
-        while self.ch.is_ascii_digit() {
+        while self.ch.is_ascii_alphanumeric() || self.ch == '_' {
             self.read_char();
         }
-        let literal = &self.input[position..self.position];
-        return token!(INT(literal));
+        let literal: String = self.input[position..self.position].iter().collect();
+        token!(INT(literal))
     }
 
     // Read and return a string
@@ -138,7 +178,7 @@ impl Lexer {
                 return token!(ILLEGAL);
             }
         }
-        return token!(STRING(literal));
+        token!(STRING(literal))
     }
 
     // Advance the lexer past any whitespace
@@ -149,7 +189,28 @@ impl Lexer {
     }
 }
 
-// Check if a character is a letter (or underscore)
-fn is_letter(ch: char) -> bool {
-    return 'a' <= ch && ch <= 'z' || 'A' <= ch && ch <= 'Z' || ch == '_';
+impl Iterator for Lexer {
+    type Item = SpannedToken;
+
+    // Yield tokens up to and including EOF
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let token = self.next_spanned_token();
+        if token.token == token!(EOF) {
+            self.done = true;
+        }
+        Some(token)
+    }
+}
+
+// Check if a character can start an identifier (Unicode XID_Start or underscore)
+fn is_identifier_start(ch: char) -> bool {
+    ch.is_xid_start() || ch == '_'
+}
+
+// Check if a character can continue an identifier (Unicode XID_Continue, includes digits)
+fn is_identifier_continue(ch: char) -> bool {
+    ch.is_xid_continue()
 }
@@ -312,3 +312,222 @@ fn test_next_token_hash() {
     }
 }
 
+#[test]
+fn test_next_token_set() {
+    let input = String::from(
+        "
+        1 in #{1, 2};
+        ",
+    );
+
+    let tests = vec![
+        (token!(INT("1")), "1"),
+        (token!(IN), "in"),
+        (token!("#{"), "#{"),
+        (token!(INT("1")), "1"),
+        (token!(,), ","),
+        (token!(INT("2")), "2"),
+        (token!('}'), "}"),
+        (token!(;), ";"),
+        (token!(EOF), "EOF"),
+    ];
+
+    let mut lexer = Lexer::new(input);
+
+    for expect in tests {
+        let token = lexer.next_token();
+        assert_eq!(token, expect.0);
+        assert_eq!(token.to_string(), expect.1.to_string());
+    }
+}
+
+
+#[test]
+fn test_next_token_exceptions() {
+    let input = String::from(
+        "
+        try { throw e; } catch (e) { } finally { } f()?
+        ",
+    );
+
+    let tests = vec![
+        (token!(TRY), "try"),
+        (token!('{'), "{"),
+        (token!(THROW), "throw"),
+        (token!(IDENT("e")), "e"),
+        (token!(;), ";"),
+        (token!('}'), "}"),
+        (token!(CATCH), "catch"),
+        (token!('('), "("),
+        (token!(IDENT("e")), "e"),
+        (token!(')'), ")"),
+        (token!('{'), "{"),
+        (token!('}'), "}"),
+        (token!(FINALLY), "finally"),
+        (token!('{'), "{"),
+        (token!('}'), "}"),
+        (token!(IDENT("f")), "f"),
+        (token!('('), "("),
+        (token!(')'), ")"),
+        (token!(?), "?"),
+        (token!(EOF), "EOF"),
+    ];
+
+    let mut lexer = Lexer::new(input);
+
+    for expect in tests {
+        let token = lexer.next_token();
+        assert_eq!(token, expect.0);
+        assert_eq!(token.to_string(), expect.1.to_string());
+    }
+}
+
+#[test]
+fn test_next_token_modules() {
+    let input = String::from(
+        "
+        import \"util.monkey\" as u; u.f(0..1) struct enum ...rest
+        ",
+    );
+
+    let tests = vec![
+        (token!(IMPORT), "import"),
+        (token!(STRING("util.monkey")), "util.monkey"),
+        (token!(AS), "as"),
+        (token!(IDENT("u")), "u"),
+        (token!(;), ";"),
+        (token!(IDENT("u")), "u"),
+        (token!(.), "."),
+        (token!(IDENT("f")), "f"),
+        (token!('('), "("),
+        (token!(INT("0")), "0"),
+        (token!(..), ".."),
+        (token!(INT("1")), "1"),
+        (token!(')'), ")"),
+        (token!(STRUCT), "struct"),
+        (token!(ENUM), "enum"),
+        (token!(...), "..."),
+        (token!(IDENT("rest")), "rest"),
+        (token!(EOF), "EOF"),
+    ];
+
+    let mut lexer = Lexer::new(input);
+
+    for expect in tests {
+        let token = lexer.next_token();
+        assert_eq!(token, expect.0);
+        assert_eq!(token.to_string(), expect.1.to_string());
+    }
+}
+
+#[test]
+fn test_next_token_number_literals() {
+    let input = String::from(
+        "
+        1_000 0xFF 0o17 0b1010 12ab;
+        ",
+    );
+
+    let tests = vec![
+        (token!(INT("1_000")), "1_000"),
+        (token!(INT("0xFF")), "0xFF"),
+        (token!(INT("0o17")), "0o17"),
+        (token!(INT("0b1010")), "0b1010"),
+        (token!(INT("12ab")), "12ab"),
+        (token!(;), ";"),
+        (token!(EOF), "EOF"),
+    ];
+
+    let mut lexer = Lexer::new(input);
+
+    for expect in tests {
+        let token = lexer.next_token();
+        assert_eq!(token, expect.0);
+        assert_eq!(token.to_string(), expect.1.to_string());
+    }
+}
+
+#[test]
+fn test_token_spans() {
+    let input = String::from("let x = 5;\n  x + 10;");
+
+    let tests = vec![
+        (token!(LET), 1, 1),
+        (token!(IDENT("x")), 1, 5),
+        (token!(=), 1, 7),
+        (token!(INT(5)), 1, 9),
+        (token!(;), 1, 10),
+        (token!(IDENT("x")), 2, 3),
+        (token!(+), 2, 5),
+        (token!(INT(10)), 2, 7),
+        (token!(;), 2, 9),
+        (token!(EOF), 2, 10),
+    ];
+
+    let lexer = Lexer::new(input);
+    let tokens: Vec<SpannedToken> = lexer.collect();
+
+    assert_eq!(tokens.len(), tests.len());
+    for (spanned, (token, line, column)) in tokens.into_iter().zip(tests) {
+        assert_eq!(spanned.token, token);
+        assert_eq!(spanned.span, Span { line, column });
+    }
+}
+
+#[test]
+fn test_lexer_iterator_display() {
+    let lexer = Lexer::new(String::from("let x = \"hi\";"));
+    let dump: Vec<String> = lexer.map(|token| token.to_string()).collect();
+
+    assert_eq!(
+        dump,
+        vec![
+            "1:1\tLet",
+            "1:5\tIdent(\"x\")",
+            "1:7\tAssign",
+            "1:9\tString(\"hi\")",
+            "1:13\tSemicolon",
+            "1:14\tEof",
+        ]
+    );
+}
+
+#[test]
+fn test_next_token_identifiers() {
+    let input = String::from(
+        "
+        let x1 = item2;
+        café + π + 变量 + _tmp_3;
+        letter fn2 if;
+        ",
+    );
+
+    let tests = vec![
+        (token!(LET), "let"),
+        (token!(IDENT("x1")), "x1"),
+        (token!(=), "="),
+        (token!(IDENT("item2")), "item2"),
+        (token!(;), ";"),
+        (token!(IDENT("café")), "café"),
+        (token!(+), "+"),
+        (token!(IDENT("π")), "π"),
+        (token!(+), "+"),
+        (token!(IDENT("变量")), "变量"),
+        (token!(+), "+"),
+        (token!(IDENT("_tmp_3")), "_tmp_3"),
+        (token!(;), ";"),
+        (token!(IDENT("letter")), "letter"),
+        (token!(IDENT("fn2")), "fn2"),
+        (token!(IF), "if"),
+        (token!(;), ";"),
+        (token!(EOF), "EOF"),
+    ];
+
+    let mut lexer = Lexer::new(input);
+
+    for expect in tests {
+        let token = lexer.next_token();
+        assert_eq!(token, expect.0);
+        assert_eq!(token.to_string(), expect.1.to_string());
+    }
+}
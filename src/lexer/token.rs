@@ -23,6 +23,12 @@ pub enum Token {
     NotEq,
     Lt,
     Gt,
+    Question,
+
+    FatArrow,
+    Dot,
+    DotDot,
+    Ellipsis,
 
     // Delimiters
     Comma,
@@ -35,6 +41,7 @@ pub enum Token {
     RBrace,
     LBracket,
     RBracket,
+    SetOpen,
 
     // Keywords
     Function,
@@ -44,6 +51,44 @@ pub enum Token {
     If,
     Else,
     Return,
+    In,
+    Match,
+    Throw,
+    Try,
+    Catch,
+    Finally,
+    Import,
+    As,
+    Struct,
+    Enum,
+}
+
+// Source position of a token (1-based line and column)
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Display for Span {
+    // Format the span for error messages
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
+
+// A token along with the position it was read from
+#[derive(Debug, PartialEq, Clone)]
+pub struct SpannedToken {
+    pub token: Token,
+    pub span: Span,
+}
+
+impl Display for SpannedToken {
+    // Format the token with its position for token dumps
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}:{}\t{:?}", self.span.line, self.span.column, self.token)
+    }
 }
 
 impl Display for Token {
@@ -59,12 +104,17 @@ impl Display for Token {
             token!(+) => write!(f, "+"),
             token!(-) => write!(f, "-"),
             token!(!) => write!(f, "!"),
+            token!(?) => write!(f, "?"),
             token!(*) => write!(f, "*"),
             token!(/) => write!(f, "/"),
             token!(==) => write!(f, "=="),
             token!(!=) => write!(f, "!="),
             token!(<) => write!(f, "<"),
             token!(>) => write!(f, ">"),
+            token!(=>) => write!(f, "=>"),
+            token!(.) => write!(f, "."),
+            token!(..) => write!(f, ".."),
+            token!(...) => write!(f, "..."),
             token!(,) => write!(f, ","),
             token!(;) => write!(f, ";"),
             token!(:) => write!(f, ":"),
@@ -74,6 +124,7 @@ impl Display for Token {
             token!(')') => write!(f, ")"),
             token!('[') => write!(f, "["),
             token!(']') => write!(f, "]"),
+            token!("#{") => write!(f, "#{{"),
             token!(FUNCTION) => write!(f, "fn"),
             token!(LET) => write!(f, "let"),
             token!(TRUE) => write!(f, "true"),
@@ -81,6 +132,16 @@ impl Display for Token {
             token!(IF) => write!(f, "if"),
             token!(ELSE) => write!(f, "else"),
             token!(RETURN) => write!(f, "return"),
+            token!(IN) => write!(f, "in"),
+            token!(MATCH) => write!(f, "match"),
+            token!(THROW) => write!(f, "throw"),
+            token!(TRY) => write!(f, "try"),
+            token!(CATCH) => write!(f, "catch"),
+            token!(FINALLY) => write!(f, "finally"),
+            token!(IMPORT) => write!(f, "import"),
+            token!(AS) => write!(f, "as"),
+            token!(STRUCT) => write!(f, "struct"),
+            token!(ENUM) => write!(f, "enum"),
         }
     }
 }
@@ -95,6 +156,16 @@ pub fn lookup_ident(ident: &str) -> Token {
         "if" => token!(IF),
         "else" => token!(ELSE),
         "return" => token!(RETURN),
+        "in" => token!(IN),
+        "match" => token!(MATCH),
+        "throw" => token!(THROW),
+        "try" => token!(TRY),
+        "catch" => token!(CATCH),
+        "finally" => token!(FINALLY),
+        "import" => token!(IMPORT),
+        "as" => token!(AS),
+        "struct" => token!(STRUCT),
+        "enum" => token!(ENUM),
         _ => token!(IDENT(ident)),
     }
 }
@@ -129,6 +200,9 @@ macro_rules! token {
     (!) => {
         Token::Bang
     };
+    (?) => {
+        Token::Question
+    };
     (*) => {
         Token::Asterisk
     };
@@ -147,6 +221,18 @@ macro_rules! token {
     (>) => {
         Token::Gt
     };
+    (=>) => {
+        Token::FatArrow
+    };
+    (.) => {
+        Token::Dot
+    };
+    (..) => {
+        Token::DotDot
+    };
+    (...) => {
+        Token::Ellipsis
+    };
     (,) => {
         Token::Comma
     };
@@ -174,6 +260,9 @@ macro_rules! token {
     (']') => {
         Token::RBracket
     };
+    ("#{") => {
+        Token::SetOpen
+    };
     (FUNCTION) => {
         Token::Function
     };
@@ -195,4 +284,34 @@ macro_rules! token {
     (RETURN) => {
         Token::Return
     };
+    (IN) => {
+        Token::In
+    };
+    (MATCH) => {
+        Token::Match
+    };
+    (THROW) => {
+        Token::Throw
+    };
+    (TRY) => {
+        Token::Try
+    };
+    (CATCH) => {
+        Token::Catch
+    };
+    (FINALLY) => {
+        Token::Finally
+    };
+    (IMPORT) => {
+        Token::Import
+    };
+    (AS) => {
+        Token::As
+    };
+    (STRUCT) => {
+        Token::Struct
+    };
+    (ENUM) => {
+        Token::Enum
+    };
 }
@@ -1,6 +1,7 @@
 #![allow(dead_code)]
 
 use clap::Parser;
+use std::path::PathBuf;
 
 #[macro_use]
 mod lexer;
@@ -11,6 +12,7 @@ mod eval;
 mod run;
 mod code;
 mod compiler;
+mod module;
 mod vm;
 mod benchmark;
 
@@ -30,19 +32,31 @@ struct Args {
     /// Whether to run benchmark
     #[arg(short, long, default_value = "false", requires = "file", conflicts_with = "interpreter")]
     benchmark: bool,
+
+    /// Whether to print the file's tokens and their positions instead of running it
+    #[arg(short, long, default_value = "false", requires = "file", conflicts_with_all = ["interpreter", "benchmark"])]
+    tokens: bool,
+
+    /// Directory to search for imported modules, after the importing file's own (repeatable)
+    #[arg(short = 'I', long = "import-path")]
+    import_path: Vec<PathBuf>,
 }
 
 fn main() {
     // Check for file argument
     let args = Args::parse();
     if let Some(file) = args.file {
+        if args.tokens {
+            run::dump_tokens(file).unwrap();
+            return;
+        }
         if args.benchmark {
-            benchmark::benchmark_file(file).unwrap();
+            benchmark::benchmark_file(file, args.import_path).unwrap();
             return;
         }
-        run::run_file(file, args.interpreter).unwrap();
+        run::run_file(file, args.interpreter, args.import_path).unwrap();
         return;
     }
 
-    start(&mut std::io::stdin(), args.interpreter).unwrap();
+    start(&mut std::io::stdin(), args.interpreter, args.import_path).unwrap();
 }
@@ -0,0 +1,117 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    lexer::Lexer,
+    parser::{ast::Program, Parser},
+    raise,
+};
+use anyhow::Result;
+
+// Finds, parses and caches the files loaded by `import`. Each back end keeps
+// its own kind of cached value: the evaluator caches module objects, and the
+// compiler caches the global slot holding a module once its code has run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModuleLoader<T> {
+    search_path: Vec<PathBuf>,
+    // The files being loaded, outermost first
+    loading: Vec<PathBuf>,
+    cache: HashMap<PathBuf, T>,
+}
+
+impl<T> Default for ModuleLoader<T> {
+    // Create a loader that only resolves paths relative to the current directory
+    fn default() -> Self {
+        ModuleLoader {
+            search_path: vec![],
+            loading: vec![],
+            cache: HashMap::new(),
+        }
+    }
+}
+
+impl<T: Clone> ModuleLoader<T> {
+    // Create a loader that also resolves paths in the given directories. The
+    // main file, if any, is where relative imports start from, and it cannot be
+    // imported back.
+    pub fn new(search_path: Vec<PathBuf>, main: Option<&Path>) -> Result<Self> {
+        let loading = match main {
+            Some(main) => vec![main.canonicalize()?],
+            None => vec![],
+        };
+        Ok(ModuleLoader {
+            search_path,
+            loading,
+            cache: HashMap::new(),
+        })
+    }
+
+    // Resolve an import path: relative to the importing file's directory (or
+    // the current directory), then to each directory in the search path
+    pub fn resolve(&self, path: &str) -> Result<PathBuf> {
+        let base = match self.loading.last().and_then(|file| file.parent()) {
+            Some(dir) => dir.to_path_buf(),
+            None => PathBuf::from("."),
+        };
+        std::iter::once(&base)
+            .chain(&self.search_path)
+            .map(|dir| dir.join(path))
+            .find(|candidate| candidate.is_file())
+            .ok_or_else(|| raise!(Import, "module not found: {}", path))?
+            .canonicalize()
+            .map_err(|err| raise!(Import, "module not found: {}: {}", path, err))
+    }
+
+    // Get the cached value of a module that has already been loaded
+    pub fn get(&self, path: &Path) -> Option<T> {
+        self.cache.get(path).cloned()
+    }
+
+    // Start loading a module, returning its parsed program. Importing a file
+    // that is still being loaded is an error.
+    pub fn enter(&mut self, path: &Path) -> Result<Program> {
+        if let Some(start) = self.loading.iter().position(|file| file == path) {
+            let cycle = self.loading[start..]
+                .iter()
+                .chain([&path.to_path_buf()])
+                .map(|file| file.display().to_string())
+                .collect::<Vec<String>>()
+                .join(" -> ");
+            return Err(raise!(Import, "import cycle: {}", cycle));
+        }
+
+        let contents = std::fs::read_to_string(path)?;
+        let mut parser = Parser::new(Lexer::new(contents));
+        let program = parser.parse_program();
+        if !parser.errors.is_empty() {
+            return Err(raise!(
+                Import,
+                "failed to parse module {}: {}",
+                path.display(),
+                parser.errors.join(", ")
+            ));
+        }
+
+        self.loading.push(path.to_path_buf());
+        Ok(program)
+    }
+
+    // Finish loading the innermost module, whether or not it succeeded
+    pub fn leave(&mut self) {
+        self.loading.pop();
+    }
+
+    // Cache the value of a loaded module
+    pub fn insert(&mut self, path: PathBuf, value: T) {
+        self.cache.insert(path, value);
+    }
+}
+
+// Get the name of a module from its file name, without the extension
+pub fn module_name(path: &Path) -> String {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
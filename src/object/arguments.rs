@@ -0,0 +1,69 @@
+use anyhow::Result;
+use std::ops::Deref;
+
+use crate::raise;
+
+use super::Object;
+
+// The parameters of a function or constructor that arguments are bound to:
+// the names of those that can be given by position or keyword, how many of
+// them are required, and whether a rest parameter takes any further
+// positional arguments
+pub struct Signature<'a, S: AsRef<str>> {
+    // The name shown in arity errors, if any
+    pub name: Option<&'a str>,
+    pub parameters: &'a [S],
+    pub min_arity: usize,
+    // None if there's a rest parameter
+    pub max_arity: Option<usize>,
+}
+
+impl<S: AsRef<str>> Signature<'_, S> {
+    // Bind the arguments of a call to the parameters, positional arguments
+    // first and then keyword arguments by name. A parameter left out is `None`
+    // for its default to fill in, and the positional arguments past the
+    // parameters are returned for the rest parameter.
+    pub fn bind<K: Deref<Target = String>>(
+        &self,
+        mut args: Vec<Object>,
+        keywords: Vec<(K, Object)>,
+    ) -> Result<(Vec<Option<Object>>, Vec<Object>)> {
+        let got = args.len() + keywords.len();
+        if got < self.min_arity || self.max_arity.is_some_and(|max| got > max) {
+            return Err(raise!(
+                Argument,
+                "wrong number of arguments{}: want{}, got={}",
+                self.name.map_or(String::new(), |name| format!(" to {}", name)),
+                self.want(),
+                got
+            ));
+        }
+
+        let rest = args.split_off(args.len().min(self.parameters.len()));
+        let mut bound = args.into_iter().map(Some).collect::<Vec<_>>();
+        bound.resize(self.parameters.len(), None);
+
+        for (name, value) in keywords {
+            let name = name.as_str();
+            match self.parameters.iter().position(|param| param.as_ref() == name) {
+                Some(index) if bound[index].is_none() => bound[index] = Some(value),
+                Some(_) => return Err(raise!(Argument, "multiple values for argument {}", name)),
+                None => return Err(raise!(Argument, "unexpected keyword argument {}", name)),
+            }
+        }
+        if let Some(index) = bound[..self.min_arity].iter().position(Option::is_none) {
+            return Err(raise!(Argument, "missing argument {}", self.parameters[index].as_ref()));
+        }
+
+        Ok((bound, rest))
+    }
+
+    // Describe the number of arguments accepted, like the builtins' arity errors
+    fn want(&self) -> String {
+        match self.max_arity {
+            Some(max) if max == self.min_arity => format!("={}", max),
+            Some(max) => format!("={}..{}", self.min_arity, max),
+            None => format!(">={}", self.min_arity),
+        }
+    }
+}
@@ -1,33 +1,293 @@
 use super::*;
 use crate::object::BuiltInFunction;
+use std::{collections::HashMap, sync::OnceLock};
 use anyhow::{anyhow, Result};
+use crate::raise;
 
-pub fn get_builtin(name: &String) -> Option<BuiltInFunction> {
-    match name.as_str() {
-        "len" => Some(len),
-        "first" => Some(first),
-        "last" => Some(last),
-        "rest" => Some(rest),
-        "push" => Some(push),
-        "puts" => Some(puts),
-        _ => None,
+// Built-in functions by name, in the order the compiler numbers them. The VM
+// indexes this table directly with the operand of OpGetBuiltin. Names with a
+// dot belong to a builtin module (`string.split`); the rest are available
+// unqualified.
+pub static BUILTINS: [(&str, BuiltInFunction); 55] = [
+    ("len", len),
+    ("puts", puts),
+    ("first", first),
+    ("last", last),
+    ("rest", rest),
+    ("push", push),
+    ("error", error),
+    ("is_error", is_error),
+    ("type", type_of),
+    ("math.abs", abs),
+    ("math.min", min),
+    ("math.max", max),
+    ("math.pow", pow),
+    ("string.split", split),
+    ("string.join", join),
+    ("string.trim", trim),
+    ("string.upper", upper),
+    ("string.lower", lower),
+    ("string.replace", replace),
+    ("string.contains", contains),
+    ("string.starts_with", starts_with),
+    ("string.ends_with", ends_with),
+    ("string.index_of", index_of),
+    ("string.repeat", repeat),
+    ("string.chars", chars),
+    ("string.lines", lines),
+    ("string.format", format),
+    ("string.reverse", reverse),
+    ("string.parse_int", parse_int),
+    ("array.pop", pop),
+    ("array.insert", insert),
+    ("array.remove", remove),
+    ("array.reverse", reverse),
+    ("array.concat", concat),
+    ("array.contains", contains),
+    ("array.index_of", index_of),
+    ("array.sort", sort),
+    ("array.unique", unique),
+    ("array.zip", zip),
+    ("array.flatten", flatten),
+    ("array.range", range),
+    ("hash.keys", keys),
+    ("hash.values", values),
+    ("hash.has_key", has_key),
+    ("hash.delete", delete),
+    ("hash.merge", merge),
+    ("hash.entries", entries),
+    ("set.union", union),
+    ("set.intersection", intersection),
+    ("set.difference", difference),
+    ("set.add", add),
+    ("set.remove", remove),
+    ("set.contains", contains),
+    ("io.read_file", read_file),
+    ("io.write_file", write_file),
+];
+
+// Builtin modules, in the order the compiler numbers them. The VM indexes this
+// table with the operand of OpGetBuiltinModule.
+pub static BUILTIN_MODULES: [&str; 6] = ["math", "string", "array", "hash", "set", "io"];
+
+// The index of each builtin in BUILTINS, by its module (empty for the
+// unqualified builtins) and its name within the module
+static BUILTIN_INDEX: OnceLock<HashMap<(&str, &str), usize>> = OnceLock::new();
+
+fn builtin_index() -> &'static HashMap<(&'static str, &'static str), usize> {
+    BUILTIN_INDEX.get_or_init(|| {
+        BUILTINS
+            .iter()
+            .enumerate()
+            .map(|(index, (name, _))| (name.split_once('.').unwrap_or(("", name)), index))
+            .collect()
+    })
+}
+
+// Look up an unqualified built-in function by name
+pub fn get_builtin(name: &str) -> Option<BuiltInFunction> {
+    builtin_index().get(&("", name)).map(|&index| BUILTINS[index].1)
+}
+
+// Look up a builtin function's index by its module and name
+pub fn builtin_member_index(module: &str, name: &str) -> Option<usize> {
+    builtin_index().get(&(module, name)).copied()
+}
+
+// Look up the builtin a method call on a value resolves to, in the builtin
+// module for the value's type
+pub fn get_builtin_method(receiver: &Object, name: &str) -> Option<BuiltInFunction> {
+    let module = match receiver {
+        Object::Integer(_) => "math",
+        Object::String(_) => "string",
+        Object::Array(_) => "array",
+        Object::Hash(_) => "hash",
+        Object::Set(_) => "set",
+        _ => return None,
+    };
+    builtin_member_index(module, name).map(|index| BUILTINS[index].1)
+}
+
+thread_local! {
+    // The builtin module objects, in the order of BUILTIN_MODULES. They are
+    // built once, the first time a module is used, and shared from then on.
+    static MODULE_OBJECTS: Vec<Object> = BUILTIN_MODULES.iter().map(|name| build_module(name)).collect();
+}
+
+// Build the object for a builtin module, with its functions as members
+fn build_module(name: &str) -> Object {
+    let members = BUILTINS
+        .iter()
+        .filter_map(|(builtin, function)| {
+            let member = builtin.strip_prefix(name)?.strip_prefix('.')?;
+            Some((member.to_string(), Object::BuiltInFunction(*function)))
+        })
+        .collect();
+    Object::Module(Rc::new(Module {
+        name: name.to_string(),
+        members,
+    }))
+}
+
+// Get the object for a builtin module by its index in BUILTIN_MODULES
+pub fn get_builtin_module_at(index: usize) -> Option<Object> {
+    MODULE_OBJECTS.with(|modules| modules.get(index).cloned())
+}
+
+// Get the object for a builtin module by name
+pub fn get_builtin_module(name: &str) -> Option<Object> {
+    let index = BUILTIN_MODULES.iter().position(|module| *module == name)?;
+    get_builtin_module_at(index)
+}
+
+// Check that a builtin was called with the expected number of arguments
+fn check_arity(args: &[Object], want: usize) -> Result<()> {
+    if args.len() != want {
+        return Err(raise!(
+            Argument,
+            "wrong number of arguments. got={}, want={}",
+            args.len(),
+            want
+        ));
+    }
+    Ok(())
+}
+
+// Get a string argument or produce a type error for the named builtin
+fn expect_string<'a>(name: &str, arg: &'a Object) -> Result<&'a str> {
+    match arg {
+        Object::String(value) => Ok(value),
+        _ => Err(raise!(
+            Argument,
+            "argument to `{}` must be STRING, got {}",
+            name,
+            arg.type_name()
+        )),
     }
 }
 
-pub static BUILTINS: [&str; 6] = ["len", "puts", "first", "last", "rest", "push"];
+// Get an array argument or produce a type error for the named builtin
+fn expect_array<'a>(name: &str, arg: &'a Object) -> Result<&'a Vec<Object>> {
+    match arg {
+        Object::Array(values) => Ok(values),
+        _ => Err(raise!(
+            Argument,
+            "argument to `{}` must be ARRAY, got {}",
+            name,
+            arg.type_name()
+        )),
+    }
+}
+
+// Take ownership of an array argument, so that it can be updated in place
+// when it is not shared
+fn into_array(name: &str, arg: Object) -> Result<Rc<Vec<Object>>> {
+    match arg {
+        Object::Array(values) => Ok(values),
+        _ => Err(raise!(
+            Argument,
+            "argument to `{}` must be ARRAY, got {}",
+            name,
+            arg.type_name()
+        )),
+    }
+}
+
+// Get a hash argument or produce a type error for the named builtin
+fn expect_hash<'a>(name: &str, arg: &'a Object) -> Result<&'a OrderedMap<HashKey, Object>> {
+    match arg {
+        Object::Hash(pairs) => Ok(pairs),
+        _ => Err(raise!(
+            Argument,
+            "argument to `{}` must be HASH, got {}",
+            name,
+            arg.type_name()
+        )),
+    }
+}
+
+// Take ownership of a hash argument, so that it can be updated in place
+// when it is not shared
+fn into_hash(name: &str, arg: Object) -> Result<Rc<OrderedMap<HashKey, Object>>> {
+    match arg {
+        Object::Hash(pairs) => Ok(pairs),
+        _ => Err(raise!(
+            Argument,
+            "argument to `{}` must be HASH, got {}",
+            name,
+            arg.type_name()
+        )),
+    }
+}
+
+// Get a set argument or produce a type error for the named builtin
+fn expect_set<'a>(name: &str, arg: &'a Object) -> Result<&'a OrderedSet<HashKey>> {
+    match arg {
+        Object::Set(elements) => Ok(elements),
+        _ => Err(raise!(
+            Argument,
+            "argument to `{}` must be SET, got {}",
+            name,
+            arg.type_name()
+        )),
+    }
+}
+
+// Take ownership of a set argument, so that it can be updated in place
+// when it is not shared
+fn into_set(name: &str, arg: Object) -> Result<Rc<OrderedSet<HashKey>>> {
+    match arg {
+        Object::Set(elements) => Ok(elements),
+        _ => Err(raise!(
+            Argument,
+            "argument to `{}` must be SET, got {}",
+            name,
+            arg.type_name()
+        )),
+    }
+}
+
+// Convert an argument into a hash key
+fn expect_hash_key(arg: &Object) -> Result<HashKey> {
+    let key: Option<HashKey> = arg.clone().into();
+    key.ok_or_else(|| raise!(Type, "unusable as hash key: {}", arg.type_name()))
+}
+
+// Get an integer argument or produce a type error for the named builtin
+fn expect_integer(name: &str, arg: &Object) -> Result<i64> {
+    match arg {
+        Object::Integer(value) => Ok(*value),
+        _ => Err(raise!(
+            Argument,
+            "argument to `{}` must be INTEGER, got {}",
+            name,
+            arg.type_name()
+        )),
+    }
+}
+
+// Wrap a list of strings into an array of string objects
+fn string_array<'a>(values: impl Iterator<Item = &'a str>) -> Object {
+    Object::array(values.map(Object::from).collect())
+}
 
 fn len(args: Vec<Object>) -> Result<Object> {
     if args.len() != 1 {
-        return Err(anyhow!(
+        return Err(raise!(
+            Argument,
             "wrong number of arguments. got={}, want=1",
             args.len()
         ));
     }
 
     match args[0] {
-        Object::String(ref value) => Ok(Object::Integer(value.len() as i64)),
-        Object::Array(ref values) => Ok(Object::Integer(values.len() as i64)),
-        _ => Err(anyhow!(
+        Object::String(ref value) => Ok(Object::Integer(value.chars().count() as i64)),
+        Object::Array(ref values) | Object::Tuple(ref values) => {
+            Ok(Object::Integer(values.len() as i64))
+        }
+        Object::Set(ref elements) => Ok(Object::Integer(elements.len() as i64)),
+        _ => Err(raise!(
+            Argument,
             "argument to `len` not supported, got {}",
             args[0].type_name()
         )),
@@ -36,7 +296,8 @@ fn len(args: Vec<Object>) -> Result<Object> {
 
 fn first(args: Vec<Object>) -> Result<Object> {
     if args.len() != 1 {
-        return Err(anyhow!(
+        return Err(raise!(
+            Argument,
             "wrong number of arguments. got={}, want=1",
             args.len()
         ));
@@ -47,7 +308,8 @@ fn first(args: Vec<Object>) -> Result<Object> {
             [] => Ok(Object::Null),
             [first, ..] => Ok(first.clone()),
         },
-        _ => Err(anyhow!(
+        _ => Err(raise!(
+            Argument,
             "argument to `first` must be ARRAY, got {}",
             args[0].type_name()
         )),
@@ -56,7 +318,8 @@ fn first(args: Vec<Object>) -> Result<Object> {
 
 fn last(args: Vec<Object>) -> Result<Object> {
     if args.len() != 1 {
-        return Err(anyhow!(
+        return Err(raise!(
+            Argument,
             "wrong number of arguments. got={}, want=1",
             args.len()
         ));
@@ -67,7 +330,8 @@ fn last(args: Vec<Object>) -> Result<Object> {
             [] => Ok(Object::Null),
             [.., last] => Ok(last.clone()),
         },
-        _ => Err(anyhow!(
+        _ => Err(raise!(
+            Argument,
             "argument to `last` must be ARRAY, got {}",
             args[0].type_name()
         )),
@@ -76,7 +340,8 @@ fn last(args: Vec<Object>) -> Result<Object> {
 
 fn rest(args: Vec<Object>) -> Result<Object> {
     if args.len() != 1 {
-        return Err(anyhow!(
+        return Err(raise!(
+            Argument,
             "wrong number of arguments. got={}, want=1",
             args.len()
         ));
@@ -85,9 +350,10 @@ fn rest(args: Vec<Object>) -> Result<Object> {
     match args[0] {
         Object::Array(ref values) => match values.as_slice() {
             [] => Ok(Object::Null),
-            [_, rest @ ..] => Ok(Object::Array(rest.to_vec())),
+            [_, rest @ ..] => Ok(Object::array(rest.to_vec())),
         },
-        _ => Err(anyhow!(
+        _ => Err(raise!(
+            Argument,
             "argument to `rest` must be ARRAY, got {}",
             args[0].type_name()
         )),
@@ -96,28 +362,612 @@ fn rest(args: Vec<Object>) -> Result<Object> {
 
 fn push(args: Vec<Object>) -> Result<Object> {
     if args.len() != 2 {
-        return Err(anyhow!(
+        return Err(raise!(
+            Argument,
             "wrong number of arguments. got={}, want=2",
             args.len()
         ));
     }
 
+    let [array, value]: [Object; 2] = args.try_into().unwrap();
+    let mut values = into_array("push", array)?;
+    Rc::make_mut(&mut values).push(value);
+    Ok(Object::Array(values))
+}
+
+fn puts(args: Vec<Object>) -> Result<Object> {
+    for arg in args {
+        println!("{}", arg);
+    }
+    Ok(Object::Null)
+}
+
+fn split(args: Vec<Object>) -> Result<Object> {
+    check_arity(&args, 2)?;
+    let string = expect_string("split", &args[0])?;
+    let separator = expect_string("split", &args[1])?;
+
+    // An empty separator splits the string into its characters
+    if separator.is_empty() {
+        return chars(vec![args[0].clone()]);
+    }
+    Ok(string_array(string.split(separator)))
+}
+
+fn join(args: Vec<Object>) -> Result<Object> {
+    check_arity(&args, 2)?;
+    let separator = expect_string("join", &args[1])?;
+
+    match args[0] {
+        Object::Array(ref values) => Ok(values
+            .iter()
+            .map(|value| value.to_string())
+            .collect::<Vec<String>>()
+            .join(separator)
+            .into()),
+        _ => Err(raise!(
+            Argument,
+            "argument to `join` must be ARRAY, got {}",
+            args[0].type_name()
+        )),
+    }
+}
+
+fn trim(args: Vec<Object>) -> Result<Object> {
+    check_arity(&args, 1)?;
+    let string = expect_string("trim", &args[0])?;
+    Ok(string.trim().into())
+}
+
+fn upper(args: Vec<Object>) -> Result<Object> {
+    check_arity(&args, 1)?;
+    let string = expect_string("upper", &args[0])?;
+    Ok(string.to_uppercase().into())
+}
+
+fn lower(args: Vec<Object>) -> Result<Object> {
+    check_arity(&args, 1)?;
+    let string = expect_string("lower", &args[0])?;
+    Ok(string.to_lowercase().into())
+}
+
+fn replace(args: Vec<Object>) -> Result<Object> {
+    check_arity(&args, 3)?;
+    let string = expect_string("replace", &args[0])?;
+    let from = expect_string("replace", &args[1])?;
+    let to = expect_string("replace", &args[2])?;
+    Ok(string.replace(from, to).into())
+}
+
+fn contains(args: Vec<Object>) -> Result<Object> {
+    check_arity(&args, 2)?;
+
     match args[0] {
-        Object::Array(ref values) => {
-            let mut new_values = values.clone();
-            new_values.push(args[1].clone());
-            Ok(Object::Array(new_values))
+        Object::String(ref string) => {
+            let substring = expect_string("contains", &args[1])?;
+            Ok(Object::Boolean(string.contains(substring)))
         }
-        _ => Err(anyhow!(
-            "argument to `push` must be ARRAY, got {}",
+        Object::Array(ref values) => Ok(Object::Boolean(values.contains(&args[1]))),
+        Object::Set(ref elements) => {
+            Ok(Object::Boolean(elements.contains_key(&args[1].to_set_element()?)))
+        }
+        _ => Err(raise!(
+            Argument,
+            "argument to `contains` must be STRING, ARRAY or SET, got {}",
             args[0].type_name()
         )),
     }
 }
 
-fn puts(args: Vec<Object>) -> Result<Object> {
-    for arg in args {
-        println!("{}", arg);
+fn starts_with(args: Vec<Object>) -> Result<Object> {
+    check_arity(&args, 2)?;
+    let string = expect_string("starts_with", &args[0])?;
+    let prefix = expect_string("starts_with", &args[1])?;
+    Ok(Object::Boolean(string.starts_with(prefix)))
+}
+
+fn ends_with(args: Vec<Object>) -> Result<Object> {
+    check_arity(&args, 2)?;
+    let string = expect_string("ends_with", &args[0])?;
+    let suffix = expect_string("ends_with", &args[1])?;
+    Ok(Object::Boolean(string.ends_with(suffix)))
+}
+
+fn index_of(args: Vec<Object>) -> Result<Object> {
+    check_arity(&args, 2)?;
+
+    // Report the position (in code points for strings), or -1 if not found
+    let index = match args[0] {
+        Object::String(ref string) => {
+            let substring = expect_string("index_of", &args[1])?;
+            string
+                .find(substring)
+                .map(|byte_index| string[..byte_index].chars().count())
+        }
+        Object::Array(ref values) => values.iter().position(|value| *value == args[1]),
+        _ => {
+            return Err(raise!(
+                Argument,
+                "argument to `index_of` must be STRING or ARRAY, got {}",
+                args[0].type_name()
+            ))
+        }
+    };
+    Ok(Object::Integer(index.map_or(-1, |index| index as i64)))
+}
+
+// The longest string `repeat` will build, in bytes
+const MAX_REPEAT_LEN: usize = 1 << 28;
+
+fn repeat(args: Vec<Object>) -> Result<Object> {
+    check_arity(&args, 2)?;
+    let string = expect_string("repeat", &args[0])?;
+
+    match args[1] {
+        Object::Integer(count) if count >= 0 => {
+            match string.len().checked_mul(count as usize) {
+                Some(len) if len <= MAX_REPEAT_LEN => Ok(string.repeat(count as usize).into()),
+                _ => Err(raise!(
+                    Argument,
+                    "argument to `repeat` would make a string longer than {} bytes",
+                    MAX_REPEAT_LEN
+                )),
+            }
+        }
+        Object::Integer(count) => Err(raise!(
+            Argument,
+            "argument to `repeat` must be non-negative, got {}",
+            count
+        )),
+        _ => Err(raise!(
+            Argument,
+            "argument to `repeat` must be INTEGER, got {}",
+            args[1].type_name()
+        )),
+    }
+}
+
+fn chars(args: Vec<Object>) -> Result<Object> {
+    check_arity(&args, 1)?;
+    let string = expect_string("chars", &args[0])?;
+    Ok(Object::array(
+        string
+            .chars()
+            .map(|ch| ch.to_string().into())
+            .collect(),
+    ))
+}
+
+fn lines(args: Vec<Object>) -> Result<Object> {
+    check_arity(&args, 1)?;
+    let string = expect_string("lines", &args[0])?;
+    Ok(string_array(string.lines()))
+}
+
+fn format(args: Vec<Object>) -> Result<Object> {
+    if args.is_empty() {
+        return Err(raise!(Argument, "wrong number of arguments. got=0, want>=1"));
+    }
+    let template = expect_string("format", &args[0])?;
+
+    // Replace each {} with the next argument, with {{ and }} as escapes
+    let mut output = String::new();
+    let mut values = args[1..].iter();
+    let mut placeholders = 0;
+    let mut chars = template.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match (ch, chars.peek()) {
+            ('{', Some('{')) | ('}', Some('}')) => {
+                chars.next();
+                output.push(ch);
+            }
+            ('{', Some('}')) => {
+                chars.next();
+                placeholders += 1;
+                if let Some(value) = values.next() {
+                    output.push_str(&value.to_string());
+                }
+            }
+            _ => output.push(ch),
+        }
+    }
+
+    if placeholders != args.len() - 1 {
+        return Err(raise!(
+            Argument,
+            "wrong number of arguments to `format`. got={}, want={}",
+            args.len() - 1,
+            placeholders
+        ));
+    }
+    Ok(output.into())
+}
+
+// Return a copy of the array without its last element, as the array builtins
+// never change their argument. Popping an empty array gives an empty array;
+// `last` reads the element being dropped.
+fn pop(args: Vec<Object>) -> Result<Object> {
+    check_arity(&args, 1)?;
+    let mut values = into_array("pop", args.into_iter().next().unwrap())?;
+
+    Rc::make_mut(&mut values).pop();
+    Ok(Object::Array(values))
+}
+
+fn insert(args: Vec<Object>) -> Result<Object> {
+    check_arity(&args, 3)?;
+    let index = expect_integer("insert", &args[1])?;
+    let [array, _, value]: [Object; 3] = args.try_into().unwrap();
+    let mut values = into_array("insert", array)?;
+
+    // Inserting at the length appends, negative indexes count from the end
+    let position = match index {
+        index if index < 0 => sequence_index(values.len(), index),
+        index if index as usize <= values.len() => Some(index as usize),
+        _ => None,
+    };
+    let position = match position {
+        Some(position) => position,
+        None => return Err(raise!(Index, "index out of range for `insert`: {}", index)),
+    };
+    Rc::make_mut(&mut values).insert(position, value);
+    Ok(Object::Array(values))
+}
+
+fn remove(args: Vec<Object>) -> Result<Object> {
+    check_arity(&args, 2)?;
+    // Sets remove by element, arrays by index
+    if let Object::Set(_) = args[0] {
+        let element = args[1].to_set_element()?;
+        let mut elements = into_set("remove", args.into_iter().next().unwrap())?;
+        if elements.contains_key(&element) {
+            Rc::make_mut(&mut elements).remove(&element);
+        }
+        return Ok(Object::Set(elements));
+    }
+
+    let index = expect_integer("remove", &args[1])?;
+    let mut values = into_array("remove", args.into_iter().next().unwrap())?;
+
+    let position = match sequence_index(values.len(), index) {
+        Some(position) => position,
+        None => return Err(raise!(Index, "index out of range for `remove`: {}", index)),
+    };
+    Rc::make_mut(&mut values).remove(position);
+    Ok(Object::Array(values))
+}
+
+fn reverse(args: Vec<Object>) -> Result<Object> {
+    check_arity(&args, 1)?;
+
+    match args[0] {
+        Object::Array(ref values) => Ok(Object::array(values.iter().rev().cloned().collect())),
+        Object::String(ref string) => Ok(string.chars().rev().collect::<String>().into()),
+        _ => Err(raise!(
+            Argument,
+            "argument to `reverse` must be ARRAY or STRING, got {}",
+            args[0].type_name()
+        )),
+    }
+}
+
+fn concat(args: Vec<Object>) -> Result<Object> {
+    if args.is_empty() {
+        return Err(raise!(Argument, "wrong number of arguments. got=0, want>=1"));
+    }
+    let mut new_values = vec![];
+    for arg in &args {
+        new_values.extend(expect_array("concat", arg)?.iter().cloned());
+    }
+    Ok(Object::array(new_values))
+}
+
+fn sort(args: Vec<Object>) -> Result<Object> {
+    check_arity(&args, 1)?;
+    let values = expect_array("sort", &args[0])?;
+
+    // Sorting is stable and supports arrays of only integers or only strings
+    let sortable = values.iter().all(|value| matches!(value, Object::Integer(_)))
+        || values.iter().all(|value| matches!(value, Object::String(_)));
+    if !sortable {
+        return Err(raise!(
+            Argument,
+            "argument to `sort` must be ARRAY of INTEGER or STRING"
+        ));
+    }
+    let mut new_values = values.to_vec();
+    new_values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+    Ok(Object::array(new_values))
+}
+
+fn unique(args: Vec<Object>) -> Result<Object> {
+    check_arity(&args, 1)?;
+    let values = expect_array("unique", &args[0])?;
+
+    // Keep the first occurrence of each value, preserving order
+    let mut new_values: Vec<Object> = vec![];
+    for value in values {
+        if !new_values.contains(value) {
+            new_values.push(value.clone());
+        }
+    }
+    Ok(Object::array(new_values))
+}
+
+fn zip(args: Vec<Object>) -> Result<Object> {
+    check_arity(&args, 2)?;
+    let left = expect_array("zip", &args[0])?;
+    let right = expect_array("zip", &args[1])?;
+
+    // Pairs stop at the end of the shorter array
+    Ok(Object::array(
+        left.iter()
+            .zip(right.iter())
+            .map(|(a, b)| Object::array(vec![a.clone(), b.clone()]))
+            .collect(),
+    ))
+}
+
+fn flatten(args: Vec<Object>) -> Result<Object> {
+    check_arity(&args, 1)?;
+    let values = expect_array("flatten", &args[0])?;
+
+    // Flatten a single level of nesting
+    let mut new_values = vec![];
+    for value in values {
+        match value {
+            Object::Array(inner) => new_values.extend(inner.iter().cloned()),
+            _ => new_values.push(value.clone()),
+        }
+    }
+    Ok(Object::array(new_values))
+}
+
+fn range(args: Vec<Object>) -> Result<Object> {
+    let (start, stop, step) = match args.as_slice() {
+        [stop] => (0, expect_integer("range", stop)?, 1),
+        [start, stop] => (
+            expect_integer("range", start)?,
+            expect_integer("range", stop)?,
+            1,
+        ),
+        [start, stop, step] => (
+            expect_integer("range", start)?,
+            expect_integer("range", stop)?,
+            expect_integer("range", step)?,
+        ),
+        _ => {
+            return Err(raise!(
+                Argument,
+                "wrong number of arguments. got={}, want=1..3",
+                args.len()
+            ))
+        }
+    };
+    if step == 0 {
+        return Err(raise!(Argument, "argument to `range` must have a non-zero step"));
+    }
+
+    // Stop at the integer limits rather than overflow past them
+    let mut values = vec![];
+    let mut current = Some(start);
+    while let Some(value) = current {
+        if (step > 0 && value >= stop) || (step < 0 && value <= stop) {
+            break;
+        }
+        values.push(Object::Integer(value));
+        current = value.checked_add(step);
+    }
+    Ok(Object::array(values))
+}
+
+// Hash builtins return keys, values and entries in insertion order
+fn keys(args: Vec<Object>) -> Result<Object> {
+    check_arity(&args, 1)?;
+    let pairs = expect_hash("keys", &args[0])?;
+    Ok(Object::array(
+        pairs.keys().map(|key| key.clone().into()).collect(),
+    ))
+}
+
+fn values(args: Vec<Object>) -> Result<Object> {
+    check_arity(&args, 1)?;
+    let pairs = expect_hash("values", &args[0])?;
+    Ok(Object::array(pairs.values().cloned().collect()))
+}
+
+fn entries(args: Vec<Object>) -> Result<Object> {
+    check_arity(&args, 1)?;
+    let pairs = expect_hash("entries", &args[0])?;
+    Ok(Object::array(
+        pairs
+            .iter()
+            .map(|(key, value)| Object::array(vec![key.clone().into(), value.clone()]))
+            .collect(),
+    ))
+}
+
+fn has_key(args: Vec<Object>) -> Result<Object> {
+    check_arity(&args, 2)?;
+    let pairs = expect_hash("has_key", &args[0])?;
+    let key = expect_hash_key(&args[1])?;
+    Ok(Object::Boolean(pairs.contains_key(&key)))
+}
+
+fn delete(args: Vec<Object>) -> Result<Object> {
+    check_arity(&args, 2)?;
+    let key = expect_hash_key(&args[1])?;
+    let mut pairs = into_hash("delete", args.into_iter().next().unwrap())?;
+
+    if pairs.contains_key(&key) {
+        Rc::make_mut(&mut pairs).remove(&key);
+    }
+    Ok(Object::Hash(pairs))
+}
+
+fn merge(args: Vec<Object>) -> Result<Object> {
+    check_arity(&args, 2)?;
+    let [left, right]: [Object; 2] = args.try_into().unwrap();
+    let mut pairs = into_hash("merge", left)?;
+    let right = expect_hash("merge", &right)?;
+
+    // Values from the second hash take precedence, new keys are appended
+    Rc::make_mut(&mut pairs).extend(right.iter().map(|(key, value)| (key.clone(), value.clone())));
+    Ok(Object::Hash(pairs))
+}
+
+fn union(args: Vec<Object>) -> Result<Object> {
+    check_arity(&args, 2)?;
+    let [left, right]: [Object; 2] = args.try_into().unwrap();
+    let mut elements = into_set("union", left)?;
+    let right = expect_set("union", &right)?;
+
+    // Elements only in the second set are appended in its order
+    if right.keys().any(|element| !elements.contains_key(element)) {
+        Rc::make_mut(&mut elements).extend(right.keys().map(|element| (element.clone(), ())));
+    }
+    Ok(Object::Set(elements))
+}
+
+fn intersection(args: Vec<Object>) -> Result<Object> {
+    check_arity(&args, 2)?;
+    let left = expect_set("intersection", &args[0])?;
+    let right = expect_set("intersection", &args[1])?;
+    Ok(Object::set(
+        left.keys()
+            .filter(|element| right.contains_key(element))
+            .map(|element| (element.clone(), ()))
+            .collect(),
+    ))
+}
+
+fn difference(args: Vec<Object>) -> Result<Object> {
+    check_arity(&args, 2)?;
+    let left = expect_set("difference", &args[0])?;
+    let right = expect_set("difference", &args[1])?;
+    Ok(Object::set(
+        left.keys()
+            .filter(|element| !right.contains_key(element))
+            .map(|element| (element.clone(), ()))
+            .collect(),
+    ))
+}
+
+fn add(args: Vec<Object>) -> Result<Object> {
+    check_arity(&args, 2)?;
+    let element = args[1].to_set_element()?;
+    let mut elements = into_set("add", args.into_iter().next().unwrap())?;
+
+    if !elements.contains_key(&element) {
+        Rc::make_mut(&mut elements).insert(element, ());
+    }
+    Ok(Object::Set(elements))
+}
+
+// Builtins that can fail for reasons outside the script's control return an
+// error value rather than raising one, so callers can check it with `is_error`
+// or pass it on with `?`
+fn error(args: Vec<Object>) -> Result<Object> {
+    match args.as_slice() {
+        [message] => Ok(Object::error("Error", expect_string("error", message)?)),
+        [kind, message] => Ok(Object::error(
+            expect_string("error", kind)?,
+            expect_string("error", message)?,
+        )),
+        _ => Err(raise!(
+            Argument,
+            "wrong number of arguments. got={}, want=1..2",
+            args.len()
+        )),
+    }
+}
+
+fn is_error(args: Vec<Object>) -> Result<Object> {
+    check_arity(&args, 1)?;
+    Ok(Object::Boolean(matches!(args[0], Object::Error(_))))
+}
+
+// Get the name of a value's type: a struct's own name, an enum's name for its
+// variants, and the type name of any other value
+fn type_of(args: Vec<Object>) -> Result<Object> {
+    check_arity(&args, 1)?;
+    match &args[0] {
+        Object::Struct(instance) => Ok(instance.shape.name.as_str().into()),
+        Object::Variant(instance) => Ok(instance.shape.enum_name.as_deref().unwrap_or_default().into()),
+        value => Ok(value.type_name().into()),
+    }
+}
+
+fn abs(args: Vec<Object>) -> Result<Object> {
+    check_arity(&args, 1)?;
+    let value = expect_integer("abs", &args[0])?;
+    value
+        .checked_abs()
+        .map(Object::Integer)
+        .ok_or_else(|| anyhow!("integer overflow in `abs`"))
+}
+
+// Get the integers passed to `min` or `max`, either as arguments or as a
+// single array
+fn expect_integers(name: &str, args: &[Object]) -> Result<Vec<i64>> {
+    let values = match args {
+        [Object::Array(values)] => values.as_slice(),
+        args => args,
+    };
+    if values.is_empty() {
+        return Err(raise!(Argument, "argument to `{}` must not be empty", name));
+    }
+    values.iter().map(|value| expect_integer(name, value)).collect()
+}
+
+fn min(args: Vec<Object>) -> Result<Object> {
+    let values = expect_integers("min", &args)?;
+    Ok(Object::Integer(values.into_iter().min().unwrap_or_default()))
+}
+
+fn max(args: Vec<Object>) -> Result<Object> {
+    let values = expect_integers("max", &args)?;
+    Ok(Object::Integer(values.into_iter().max().unwrap_or_default()))
+}
+
+fn pow(args: Vec<Object>) -> Result<Object> {
+    check_arity(&args, 2)?;
+    let base = expect_integer("pow", &args[0])?;
+    let exponent = expect_integer("pow", &args[1])?;
+    let exponent = u32::try_from(exponent)
+        .map_err(|_| raise!(Argument, "argument to `pow` must be a non-negative exponent, got {}", exponent))?;
+    base.checked_pow(exponent)
+        .map(Object::Integer)
+        .ok_or_else(|| anyhow!("integer overflow in `pow`"))
+}
+
+fn parse_int(args: Vec<Object>) -> Result<Object> {
+    check_arity(&args, 1)?;
+    let string = expect_string("parse_int", &args[0])?;
+
+    match string.trim().parse::<i64>() {
+        Ok(value) => Ok(Object::Integer(value)),
+        Err(_) => Ok(Object::error("ValueError", format!("invalid integer: {}", string))),
+    }
+}
+
+fn read_file(args: Vec<Object>) -> Result<Object> {
+    check_arity(&args, 1)?;
+    let path = expect_string("read_file", &args[0])?;
+
+    match std::fs::read_to_string(path) {
+        Ok(contents) => Ok(contents.into()),
+        Err(err) => Ok(Object::error("IOError", format!("{}: {}", path, err))),
+    }
+}
+
+fn write_file(args: Vec<Object>) -> Result<Object> {
+    check_arity(&args, 2)?;
+    let path = expect_string("write_file", &args[0])?;
+    let contents = expect_string("write_file", &args[1])?;
+
+    match std::fs::write(path, contents) {
+        Ok(()) => Ok(Object::Null),
+        Err(err) => Ok(Object::error("IOError", format!("{}: {}", path, err))),
     }
-    Ok(Object::Null)
 }
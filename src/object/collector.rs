@@ -0,0 +1,263 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    mem,
+    rc::{Rc, Weak},
+};
+
+use super::{environment::Environment, Object};
+
+// Collect only once at least this many environments are alive
+const MIN_THRESHOLD: usize = 256;
+
+// Reference counting frees environments as soon as nothing refers to them,
+// except when they form a cycle: a function stored in the environment it
+// closes over (as every recursive `let` does) keeps that environment alive
+// forever. The collector tracks every environment the evaluator creates and
+// frees the ones that are only reachable from each other.
+//
+// Roots are found by trial deletion: an environment, array, hash, module,
+// function or error with more strong references than tracked environments
+// account for is referenced from outside (the evaluator, a Rust local, a
+// returned value).
+// Everything reachable from a root is kept, and the stores of the remaining
+// environments are cleared, which breaks their cycles.
+#[derive(Debug)]
+pub struct Collector {
+    environments: Vec<Weak<RefCell<Environment>>>,
+    threshold: usize,
+}
+
+impl Default for Collector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Collector {
+    // Create a collector with no tracked environments
+    pub fn new() -> Self {
+        Self {
+            environments: vec![],
+            threshold: MIN_THRESHOLD,
+        }
+    }
+
+    // Track a new environment, collecting first if enough have accumulated
+    pub fn track(&mut self, env: Environment) -> Rc<RefCell<Environment>> {
+        if self.environments.len() >= self.threshold {
+            // Most environments are freed by reference counting alone, so only
+            // collect when many of the tracked ones are still alive
+            self.environments.retain(|env| env.strong_count() > 0);
+            if self.environments.len() >= self.threshold / 2 {
+                self.collect();
+                self.threshold = MIN_THRESHOLD.max(self.environments.len() * 2);
+            }
+        }
+
+        let env = Rc::new(RefCell::new(env));
+        self.environments.push(Rc::downgrade(&env));
+        env
+    }
+
+    // Get the number of tracked environments that are still alive
+    pub fn live(&self) -> usize {
+        self.environments
+            .iter()
+            .filter(|env| env.strong_count() > 0)
+            .count()
+    }
+
+    // Free unreachable environment cycles, returning how many were freed
+    pub fn collect(&mut self) -> usize {
+        let environments: Vec<_> = self
+            .environments
+            .iter()
+            .filter_map(|env| env.upgrade())
+            .collect();
+
+        // Count the references that come from inside tracked environments
+        let mut graph = Graph::default();
+        for env in &environments {
+            graph.strong.insert(address(env), Rc::strong_count(env) - 1);
+        }
+        for env in &environments {
+            let env = env.borrow();
+            if let Some(outer) = &env.outer {
+                graph.reference(address(outer));
+            }
+            for value in env.values.values() {
+                graph.count(value);
+            }
+        }
+
+        // Mark everything reachable from the externally referenced nodes
+        let mut marked = HashSet::new();
+        let mut pending: Vec<Node> = vec![];
+        for env in &environments {
+            if graph.is_root(address(env)) {
+                pending.push(Node::Environment(env.clone()));
+            }
+        }
+        for node in &graph.values {
+            if graph.is_root(node.address()) {
+                pending.push(node.clone());
+            }
+        }
+        while let Some(node) = pending.pop() {
+            if marked.insert(node.address()) {
+                node.children(&mut pending);
+            }
+        }
+
+        // Break the cycles between the unmarked environments
+        let mut garbage = vec![];
+        for env in &environments {
+            if !marked.contains(&address(env)) {
+                let mut env = env.borrow_mut();
+                garbage.push((mem::take(&mut env.values), env.outer.take()));
+            }
+        }
+        let freed = garbage.len();
+        drop(garbage);
+        drop(environments);
+
+        self.environments.retain(|env| env.strong_count() > 0);
+        freed
+    }
+}
+
+// The address of a shared allocation, used to identify it in the graph
+fn address<T>(rc: &Rc<T>) -> usize {
+    Rc::as_ptr(rc) as *const () as usize
+}
+
+// A shared allocation that can lead to an environment
+#[derive(Clone)]
+enum Node {
+    Environment(Rc<RefCell<Environment>>),
+    Object(Object),
+}
+
+impl Node {
+    fn address(&self) -> usize {
+        match self {
+            Node::Environment(env) => address(env),
+            Node::Object(Object::Array(values) | Object::Tuple(values)) => address(values),
+            Node::Object(Object::Hash(pairs)) => address(pairs),
+            Node::Object(Object::Module(module)) => address(module),
+            Node::Object(Object::Struct(instance) | Object::Variant(instance)) => address(instance),
+            Node::Object(Object::Function(function)) => address(function),
+            Node::Object(Object::Error(error)) => address(error),
+            Node::Object(_) => 0,
+        }
+    }
+
+    // Queue the nodes directly reachable from this one
+    fn children(&self, pending: &mut Vec<Node>) {
+        match self {
+            Node::Environment(env) => {
+                let env = env.borrow();
+                if let Some(outer) = &env.outer {
+                    pending.push(Node::Environment(outer.clone()));
+                }
+                for value in env.values.values() {
+                    push_object(value, pending);
+                }
+            }
+            Node::Object(Object::Array(values) | Object::Tuple(values)) => {
+                values.iter().for_each(|value| push_object(value, pending));
+            }
+            Node::Object(Object::Hash(pairs)) => {
+                pairs.values().for_each(|value| push_object(value, pending));
+            }
+            Node::Object(Object::Module(module)) => {
+                module.members.values().for_each(|value| push_object(value, pending));
+            }
+            Node::Object(Object::Struct(instance) | Object::Variant(instance)) => {
+                instance.fields.iter().for_each(|value| push_object(value, pending));
+            }
+            Node::Object(Object::Function(function)) => {
+                pending.push(Node::Environment(function.env.clone()));
+            }
+            Node::Object(Object::Error(error)) => push_object(&error.value, pending),
+            Node::Object(_) => {}
+        }
+    }
+}
+
+// Queue an object if it is a shared allocation, looking through return values
+fn push_object(object: &Object, pending: &mut Vec<Node>) {
+    match object {
+        Object::Array(_)
+        | Object::Tuple(_)
+        | Object::Hash(_)
+        | Object::Module(_)
+        | Object::Struct(_)
+        | Object::Variant(_)
+        | Object::Function(_)
+        | Object::Error(_) => {
+            pending.push(Node::Object(object.clone()))
+        }
+        Object::ReturnValue(value) => push_object(value, pending),
+        _ => {}
+    }
+}
+
+// Strong and internal reference counts for every node reachable from the
+// tracked environments
+#[derive(Default)]
+struct Graph {
+    strong: HashMap<usize, usize>,
+    internal: HashMap<usize, usize>,
+    values: Vec<Node>,
+}
+
+impl Graph {
+    // Record a reference to a node from inside the graph
+    fn reference(&mut self, address: usize) {
+        *self.internal.entry(address).or_default() += 1;
+    }
+
+    // Check if a node is referenced from outside the graph
+    fn is_root(&self, address: usize) -> bool {
+        self.strong[&address] > self.internal.get(&address).copied().unwrap_or(0)
+    }
+
+    // Count the references held by an object, visiting each shared value once
+    fn count(&mut self, object: &Object) {
+        let strong = match object {
+            Object::Array(values) | Object::Tuple(values) => Rc::strong_count(values),
+            Object::Hash(pairs) => Rc::strong_count(pairs),
+            Object::Module(module) => Rc::strong_count(module),
+            Object::Struct(instance) | Object::Variant(instance) => Rc::strong_count(instance),
+            Object::Function(function) => Rc::strong_count(function),
+            Object::Error(error) => Rc::strong_count(error),
+            Object::ReturnValue(value) => return self.count(value),
+            _ => return,
+        };
+
+        let node = Node::Object(object.clone());
+        let key = node.address();
+        self.reference(key);
+        if self.strong.contains_key(&key) {
+            return;
+        }
+        self.strong.insert(key, strong);
+
+        match object {
+            Object::Array(values) | Object::Tuple(values) => {
+                values.iter().for_each(|value| self.count(value))
+            }
+            Object::Hash(pairs) => pairs.values().for_each(|value| self.count(value)),
+            Object::Module(module) => module.members.values().for_each(|value| self.count(value)),
+            Object::Struct(instance) | Object::Variant(instance) => {
+                instance.fields.iter().for_each(|value| self.count(value))
+            }
+            Object::Function(function) => self.reference(address(&function.env)),
+            Object::Error(error) => self.count(&error.value),
+            _ => {}
+        }
+        self.values.push(node);
+    }
+}
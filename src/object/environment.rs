@@ -4,8 +4,8 @@ use super::Object;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Environment {
-    values: HashMap<String, Object>,
-    outer: Option<Rc<RefCell<Environment>>>,
+    pub(super) values: HashMap<String, Object>,
+    pub(super) outer: Option<Rc<RefCell<Environment>>>,
 }
 
 impl Default for Environment {
@@ -35,6 +35,17 @@ impl Environment {
         }
     }
 
+    // Get the values defined directly in this environment, ordered by name
+    pub fn bindings(&self) -> Vec<(String, Object)> {
+        let mut bindings = self
+            .values
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect::<Vec<_>>();
+        bindings.sort_by(|(left, _), (right, _)| left.cmp(right));
+        bindings
+    }
+
     // Set a value in the environment
     pub fn set(&mut self, name: &String, value: Object) {
         self.values.insert(name.to_string(), value);
@@ -0,0 +1,172 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    fmt::{self, Display, Formatter},
+    rc::Rc,
+};
+
+use super::Object;
+
+// The kind of a runtime error, which a catch block sees as the error's `kind`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorKind {
+    Name,
+    Import,
+    Type,
+    Argument,
+    Index,
+    Match,
+    Runtime,
+}
+
+impl ErrorKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorKind::Name => "NameError",
+            ErrorKind::Import => "ImportError",
+            ErrorKind::Type => "TypeError",
+            ErrorKind::Argument => "ArgumentError",
+            ErrorKind::Index => "IndexError",
+            ErrorKind::Match => "MatchError",
+            ErrorKind::Runtime => "RuntimeError",
+        }
+    }
+}
+
+// A runtime error raised by the interpreter, the VM or a builtin, tagged with
+// its kind where it's raised. It shows only its message when it's not caught.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuntimeError {
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for RuntimeError {}
+
+// Build an anyhow error of the given kind, with a message formatted like
+// `anyhow!`: `raise!(Type, "not a function: {}", name)`
+#[macro_export]
+macro_rules! raise {
+    ($kind:ident, $($arg:tt)*) => {
+        anyhow::Error::from($crate::object::error::RuntimeError {
+            kind: $crate::object::error::ErrorKind::$kind,
+            message: format!($($arg)*),
+        })
+    };
+}
+
+// The kind given to thrown values that are not already errors
+const THROWN_KIND: &str = "Error";
+
+// An error that a script can catch: either a runtime error raised by the
+// interpreter, the VM or a builtin, or a value thrown with `throw`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErrorObject {
+    pub kind: String,
+    pub message: String,
+    // The value given to `throw`, or null for errors that were not thrown
+    pub value: Object,
+}
+
+impl Display for ErrorObject {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.kind, self.message)
+    }
+}
+
+impl ErrorObject {
+    // Get a field of the error by name, for indexing (`e["message"]`)
+    pub fn field(&self, name: &str) -> Object {
+        match name {
+            "kind" => self.kind.as_str().into(),
+            "message" => self.message.as_str().into(),
+            "value" => self.value.clone(),
+            _ => Object::Null,
+        }
+    }
+}
+
+thread_local! {
+    // The error objects of the Thrown errors that are still propagating
+    static THROWN: RefCell<HashMap<u64, Rc<ErrorObject>>> = RefCell::new(HashMap::new());
+    static NEXT_THROWN: Cell<u64> = const { Cell::new(0) };
+}
+
+// An error object travelling through anyhow, so that it keeps its kind and
+// thrown value when it's caught again. Objects can't be sent between threads
+// as anyhow errors must be, so the object waits in a thread-local table until
+// it's taken back out, or until the error is dropped.
+#[derive(Debug)]
+pub struct Thrown {
+    id: u64,
+    text: String,
+}
+
+impl Thrown {
+    pub fn new(error: Rc<ErrorObject>) -> Self {
+        let id = NEXT_THROWN.with(|next| next.replace(next.get() + 1));
+        let text = error.to_string();
+        THROWN.with(|thrown| thrown.borrow_mut().insert(id, error));
+        Thrown { id, text }
+    }
+
+    // Take the error object back out of the table
+    pub fn take(self) -> Rc<ErrorObject> {
+        THROWN
+            .with(|thrown| thrown.borrow_mut().remove(&self.id))
+            .expect("thrown error is taken once")
+    }
+}
+
+impl Drop for Thrown {
+    fn drop(&mut self) {
+        THROWN.with(|thrown| thrown.borrow_mut().remove(&self.id));
+    }
+}
+
+impl Display for Thrown {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+impl std::error::Error for Thrown {}
+
+// Convert a thrown value into an error to propagate. Errors are rethrown as
+// they are; any other value is kept as the `value` of a new error, with its
+// string form as the message.
+pub fn throw(value: Object) -> anyhow::Error {
+    let error = match value {
+        Object::Error(error) => error,
+        value => Rc::new(ErrorObject {
+            kind: THROWN_KIND.to_string(),
+            message: value.to_string(),
+            value,
+        }),
+    };
+    Thrown::new(error).into()
+}
+
+// Convert a propagating error into the error object a catch block receives
+pub fn catch(err: anyhow::Error) -> Object {
+    let error = match err.downcast::<Thrown>() {
+        Ok(thrown) => thrown.take(),
+        Err(err) => {
+            let kind = err
+                .downcast_ref::<RuntimeError>()
+                .map_or(ErrorKind::Runtime, |error| error.kind);
+            Rc::new(ErrorObject {
+                kind: kind.as_str().to_string(),
+                message: err.to_string(),
+                value: Object::Null,
+            })
+        }
+    };
+    Object::Error(error)
+}
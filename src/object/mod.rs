@@ -1,17 +1,25 @@
+pub mod arguments;
 pub mod environment;
 pub mod builtins;
+pub mod collector;
+pub mod error;
+pub mod ordered_map;
 
 use anyhow::Result;
 use std::{
     cell::RefCell,
-    collections::HashMap,
+    cmp::Ordering,
     fmt::{self, Display, Formatter},
     hash::Hash,
     rc::Rc,
 };
 
-use crate::{parser::ast::Statement, code::Instructions};
+use crate::{parser::ast::{Parameter, Statement}, code::Instructions, vm::heap::Handle};
+use crate::raise;
+use arguments::Signature;
 use environment::Environment;
+use error::ErrorObject;
+use ordered_map::{OrderedMap, OrderedSet};
 
 pub type BuiltInFunction = fn(Vec<Object>) -> Result<Object>;
 
@@ -19,30 +27,147 @@ pub type BuiltInFunction = fn(Vec<Object>) -> Result<Object>;
 pub struct CompiledFunction {
     pub instructions: Instructions,
     pub num_locals: usize,
-    pub num_parameters: usize,
+    // The parameters that can be given by position or keyword, which come
+    // first in the locals, followed by the rest parameter if there is one
+    pub parameters: Vec<String>,
+    pub min_arity: usize,
+    // None if there's a rest parameter
+    pub max_arity: Option<usize>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Closure {
-    pub func: CompiledFunction,
+    pub func: Rc<CompiledFunction>,
     pub free: Vec<Object>,
 }
 
+#[derive(Debug, Clone)]
+pub struct Function {
+    pub parameters: Vec<Parameter>,
+    pub body: Statement,
+    pub env: Rc<RefCell<Environment>>,
+}
+
+// A module loaded by import, holding the top-level bindings of its file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Module {
+    pub name: String,
+    pub members: OrderedMap<String, Object>,
+}
+
+// The shape of a struct or enum variant, shared by its constructor and every
+// value it builds. Values hold their fields in declaration order, so a field
+// is found by its index in the shape rather than by hashing its name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Shape {
+    pub name: String,
+    pub fields: Vec<String>,
+    // The enum this is a variant of, if any
+    pub enum_name: Option<String>,
+}
+
+impl Shape {
+    // Get the value an enum variant's name is bound to: a constructor if it
+    // has fields, otherwise its only value
+    pub fn variant(enum_name: &str, name: &str, fields: &[String]) -> Object {
+        let shape = Rc::new(Shape {
+            name: name.to_string(),
+            fields: fields.to_vec(),
+            enum_name: Some(enum_name.to_string()),
+        });
+        if fields.is_empty() {
+            Object::Variant(Rc::new(Instance {
+                shape,
+                fields: vec![],
+            }))
+        } else {
+            Object::StructType(shape)
+        }
+    }
+
+    // Get the index of a field by name
+    pub fn field_index(&self, name: &str) -> Option<usize> {
+        self.fields.iter().position(|field| field == name)
+    }
+
+    // Get the index of a field by name, which must be one of the struct's
+    pub fn find_field(&self, name: &str) -> Result<usize> {
+        self.field_index(name)
+            .ok_or_else(|| raise!(Name, "struct {} has no field {}", self.name, name))
+    }
+
+    // Build a value of this struct from its fields, given in declaration order
+    // or by name
+    pub fn construct<K: std::ops::Deref<Target = String>>(self: &Rc<Self>, fields: Vec<Object>, keywords: Vec<(K, Object)>) -> Result<Object> {
+        let fields = if keywords.is_empty() && fields.len() == self.fields.len() {
+            fields
+        } else {
+            let signature = Signature {
+                name: Some(&self.name),
+                parameters: &self.fields,
+                min_arity: self.fields.len(),
+                max_arity: Some(self.fields.len()),
+            };
+            let (fields, _) = signature.bind(fields, keywords)?;
+            fields.into_iter().map(Option::unwrap_or_default).collect()
+        };
+        let instance = Rc::new(Instance {
+            shape: self.clone(),
+            fields,
+        });
+        match self.enum_name {
+            Some(_) => Ok(Object::Variant(instance)),
+            None => Ok(Object::Struct(instance)),
+        }
+    }
+}
+
+// A value of a struct or enum variant
 #[derive(Debug, Clone, PartialEq)]
+pub struct Instance {
+    pub shape: Rc<Shape>,
+    pub fields: Vec<Object>,
+}
+
+impl Instance {
+    // Get a field by name, which must be one of the struct's
+    pub fn field(&self, name: &str) -> Result<&Object> {
+        self.shape.find_field(name).map(|index| &self.fields[index])
+    }
+}
+
+// Strings, collections and functions are reference counted so that copying an
+// object (onto the VM stack, into an environment or a builtin's arguments) is
+// cheap. They are immutable from scripts; builtins that produce modified copies
+// use Rc::make_mut, which only copies when the value is shared.
+#[derive(Debug, Clone, Default)]
 pub enum Object {
     Integer(i64),
     Boolean(bool),
-    String(String),
-    Array(Vec<Object>),
-    Hash(HashMap<HashKey, Object>),
+    String(Rc<String>),
+    Array(Rc<Vec<Object>>),
+    Tuple(Rc<Vec<Object>>),
+    Hash(Rc<OrderedMap<HashKey, Object>>),
+    Set(Rc<OrderedSet<HashKey>>),
     ReturnValue(Box<Object>),
-    Function(Vec<String>, Box<Statement>, Rc<RefCell<Environment>>),
+    Function(Rc<Function>),
     BuiltInFunction(BuiltInFunction),
-    CompiledFunction(CompiledFunction),
-    Closure(Closure),
+    CompiledFunction(Rc<CompiledFunction>),
+    Closure(Handle),
+    Error(Rc<ErrorObject>),
+    Module(Rc<Module>),
+    StructType(Rc<Shape>),
+    Struct(Rc<Instance>),
+    Variant(Rc<Instance>),
+    #[default]
     Null,
 }
 
+// Objects are two words: a tag plus either an inline scalar or a single pointer
+// to a reference-counted payload. The VM copies and drops objects on every
+// instruction, so keep any new variant within this size.
+const _: () = assert!(std::mem::size_of::<Object>() <= 16);
+
 impl Display for Object {
     // Pretty print objects
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
@@ -64,6 +189,16 @@ impl Display for Object {
                     .join(", ");
                 write!(f, "[{}]", values)
             }
+            Object::Tuple(values) => {
+                // A single element tuple keeps its trailing comma
+                let trailing = if values.len() == 1 { "," } else { "" };
+                let values = values
+                    .iter()
+                    .map(|value| value.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                write!(f, "({}{})", values, trailing)
+            }
             Object::Hash(values) => {
                 let values = values
                     .iter()
@@ -72,12 +207,25 @@ impl Display for Object {
                     .join(", ");
                 write!(f, "{{{}}}", values)
             }
+            Object::Set(elements) => {
+                let elements = elements
+                    .keys()
+                    .map(|element| element.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                write!(f, "#{{{}}}", elements)
+            }
             Object::ReturnValue(value) => {
                 write!(f, "{}", value)
             }
-            Object::Function(params, body, _) => {
-                let params = params.join(", ");
-                write!(f, "fn({}) {{\n{}\n}}", params, body)
+            Object::Function(func) => {
+                let params = func
+                    .parameters
+                    .iter()
+                    .map(|param| param.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                write!(f, "fn({}) {{\n{}\n}}", params, func.body)
             }
             Object::BuiltInFunction(_) => {
                 write!(f, "builtin function")
@@ -85,8 +233,44 @@ impl Display for Object {
             Object::CompiledFunction(func) => {
                 write!(f, "CompiledFunction[{:p}]", &func)
             }
-            Object::Closure(closure) => {
-                write!(f, "Closure[{:p}]", &closure)
+            Object::Closure(handle) => {
+                write!(f, "Closure[{}]", handle)
+            }
+            Object::Error(error) => {
+                write!(f, "{}", error)
+            }
+            Object::Module(module) => {
+                write!(f, "<module {}>", module.name)
+            }
+            Object::StructType(shape) => match &shape.enum_name {
+                Some(enum_name) => write!(f, "<variant {}.{}>", enum_name, shape.name),
+                None => write!(f, "<struct {}>", shape.name),
+            },
+            Object::Variant(instance) if instance.fields.is_empty() => {
+                write!(f, "{}", instance.shape.name)
+            }
+            Object::Variant(instance) => {
+                let fields = instance
+                    .fields
+                    .iter()
+                    .map(|value| value.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                write!(f, "{}({})", instance.shape.name, fields)
+            }
+            Object::Struct(instance) => {
+                let fields = instance
+                    .shape
+                    .fields
+                    .iter()
+                    .zip(&instance.fields)
+                    .map(|(name, value)| format!("{}: {}", name, value))
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                match fields.is_empty() {
+                    true => write!(f, "{} {{}}", instance.shape.name),
+                    false => write!(f, "{} {{ {} }}", instance.shape.name, fields),
+                }
             }
             Object::Null => {
                 write!(f, "null")
@@ -95,7 +279,236 @@ impl Display for Object {
     }
 }
 
+// Equality is structural and shared by == in the evaluator and the VM:
+// arrays and hashes compare element by element (hashes and sets ignore insertion order),
+// and values of different types are never equal. Functions are equal only to
+// themselves: an evaluator function is compared by its allocation and a VM
+// closure by its heap handle, so two evaluations of the same `fn` literal are
+// different functions in both back ends.
+impl PartialEq for Object {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Object::Integer(left), Object::Integer(right)) => left == right,
+            (Object::Boolean(left), Object::Boolean(right)) => left == right,
+            (Object::String(left), Object::String(right)) => left == right,
+            (Object::Array(left), Object::Array(right)) => left == right,
+            (Object::Tuple(left), Object::Tuple(right)) => left == right,
+            (Object::Hash(left), Object::Hash(right)) => left == right,
+            (Object::Set(left), Object::Set(right)) => left == right,
+            (Object::ReturnValue(left), Object::ReturnValue(right)) => left == right,
+            (Object::Function(left), Object::Function(right)) => Rc::ptr_eq(left, right),
+            (Object::BuiltInFunction(left), Object::BuiltInFunction(right)) => {
+                std::ptr::fn_addr_eq(*left, *right)
+            }
+            (Object::CompiledFunction(left), Object::CompiledFunction(right)) => left == right,
+            (Object::Closure(left), Object::Closure(right)) => left == right,
+            (Object::Error(left), Object::Error(right)) => left == right,
+            (Object::Module(left), Object::Module(right)) => left == right,
+            (Object::StructType(left), Object::StructType(right)) => left == right,
+            (Object::Struct(left), Object::Struct(right)) => left == right,
+            (Object::Variant(left), Object::Variant(right)) => left == right,
+            (Object::Null, Object::Null) => true,
+            _ => false,
+        }
+    }
+}
+
+// Ordering is shared by < and > in the evaluator and the VM: integers compare
+// numerically and strings lexicographically by code point. Other pairs are unordered.
+impl PartialOrd for Object {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (Object::Integer(left), Object::Integer(right)) => left.partial_cmp(right),
+            (Object::String(left), Object::String(right)) => left.partial_cmp(right),
+            _ => None,
+        }
+    }
+}
+
 impl Object {
+    // Create an array object
+    pub fn array(values: Vec<Object>) -> Object {
+        Object::Array(Rc::new(values))
+    }
+
+    // Create a tuple object
+    pub fn tuple(values: Vec<Object>) -> Object {
+        Object::Tuple(Rc::new(values))
+    }
+
+    // Create a hash object
+    pub fn hash(pairs: OrderedMap<HashKey, Object>) -> Object {
+        Object::Hash(Rc::new(pairs))
+    }
+
+    // Create a set object
+    pub fn set(elements: OrderedSet<HashKey>) -> Object {
+        Object::Set(Rc::new(elements))
+    }
+
+    // Create an error object
+    pub fn error(kind: &str, message: impl Into<String>) -> Object {
+        Object::Error(Rc::new(ErrorObject {
+            kind: kind.to_string(),
+            message: message.into(),
+            value: Object::Null,
+        }))
+    }
+
+    // Get a member of a module, for `.` access
+    pub fn member(&self, name: &str) -> Result<Object> {
+        match self {
+            Object::Module(module) => module
+                .members
+                .get(name)
+                .cloned()
+                .ok_or_else(|| raise!(Name, "module {} has no member {}", module.name, name)),
+            Object::Hash(pairs) => Ok(pairs.get(&HashKey::from(name)).cloned().unwrap_or_default()),
+            Object::Struct(instance) | Object::Variant(instance) => instance.field(name).cloned(),
+            Object::Error(error) => Ok(error.field(name)),
+            _ => Err(raise!(Type, "member access not supported: {}.{}", self.type_name(), name)),
+        }
+    }
+
+    // Resolve a method call `receiver.name(args)` to the function to call, and
+    // whether the receiver is passed as its first argument. A module member is
+    // called with the arguments alone. Otherwise the call is `name(receiver,
+    // args)`, with `name` the function in scope (`in_scope`, if callable) or
+    // the builtin for the receiver's type (`"a,b".split(",")` is
+    // `string.split("a,b", ",")`). A hash field is called as a last resort.
+    pub fn method(&self, name: &str, in_scope: Object) -> Result<(Object, bool)> {
+        if let Object::Module(_) = self {
+            return Ok((self.member(name)?, false));
+        }
+        if in_scope.is_callable() {
+            return Ok((in_scope, true));
+        }
+        if let Some(builtin) = builtins::get_builtin_method(self, name) {
+            return Ok((Object::BuiltInFunction(builtin), true));
+        }
+        match self {
+            Object::Hash(pairs) => match pairs.get(&HashKey::from(name)) {
+                Some(field) => Ok((field.clone(), false)),
+                None => Err(raise!(Type, "unknown method: {}.{}", self.type_name(), name)),
+            },
+            Object::Struct(instance) | Object::Variant(instance) => match instance.shape.field_index(name) {
+                Some(index) => Ok((instance.fields[index].clone(), false)),
+                None => Err(raise!(Type, "unknown method: {}.{}", instance.shape.name, name)),
+            },
+            _ => Err(raise!(Type, "unknown method: {}.{}", self.type_name(), name)),
+        }
+    }
+
+    // Get the fields of a struct or variant built by a constructor, for a
+    // constructor pattern. A unit variant stands for its own constructor.
+    pub fn constructed_by(&self, constructor: &Object) -> Result<Option<&[Object]>> {
+        let shape = match constructor {
+            Object::StructType(shape) => shape,
+            Object::Variant(unit) if unit.fields.is_empty() => &unit.shape,
+            _ => return Err(raise!(Type, "not a constructor: {}", constructor.type_name())),
+        };
+        match self {
+            Object::Struct(instance) | Object::Variant(instance) if instance.shape == *shape => {
+                Ok(Some(&instance.fields))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    // Check if a value can be called
+    pub fn is_callable(&self) -> bool {
+        matches!(
+            self,
+            Object::Function(_) | Object::BuiltInFunction(_) | Object::Closure(_) | Object::StructType(_)
+        )
+    }
+
+    // Check if a value is a member of a collection, for the `in` operator:
+    // an element of a set or array, a key of a hash, or a substring of a string
+    pub fn contains(&self, value: &Object) -> Result<bool> {
+        match (self, value) {
+            (Object::Set(elements), _) => Ok(elements.contains_key(&value.to_set_element()?)),
+            (Object::Hash(pairs), _) => {
+                let key: Option<HashKey> = value.clone().into();
+                match key {
+                    Some(key) => Ok(pairs.contains_key(&key)),
+                    None => Err(raise!(Type, "unusable as hash key: {}", value.type_name())),
+                }
+            }
+            (Object::Array(values), _) => Ok(values.contains(value)),
+            (Object::String(string), Object::String(substring)) => {
+                Ok(string.contains(substring.as_str()))
+            }
+            _ => Err(raise!(
+                Type,
+                "unsupported types for in: {} {}",
+                value.type_name(),
+                self.type_name()
+            )),
+        }
+    }
+
+    // Convert an object into a set element, which must be usable as a hash key
+    pub fn to_set_element(&self) -> Result<HashKey> {
+        let element: Option<HashKey> = self.clone().into();
+        element.ok_or_else(|| raise!(Type, "unusable as set element: {}", self.type_name()))
+    }
+
+    // Destructure an array or tuple into exactly `len` values
+    pub fn destructure_sequence(&self, len: usize) -> Result<Vec<Object>> {
+        match self {
+            Object::Array(values) | Object::Tuple(values) if values.len() == len => {
+                Ok(values.to_vec())
+            }
+            Object::Array(values) | Object::Tuple(values) => Err(raise!(
+                Type,
+                "cannot destructure {} of length {} into {} names",
+                self.type_name(),
+                values.len(),
+                len
+            )),
+            _ => Err(raise!(
+                Type,
+                "cannot destructure {} into names, expected ARRAY or TUPLE",
+                self.type_name()
+            )),
+        }
+    }
+
+    // Destructure a hash into the values of the given string keys
+    pub fn destructure_hash(&self, keys: &[&str]) -> Result<Vec<Object>> {
+        let pairs = match self {
+            Object::Hash(pairs) => pairs,
+            _ => {
+                return Err(raise!(
+                    Type,
+                    "cannot destructure {} into keys, expected HASH",
+                    self.type_name()
+                ))
+            }
+        };
+        keys.iter()
+            .map(|key| {
+                pairs
+                    .get(&HashKey::from(*key))
+                    .cloned()
+                    .ok_or_else(|| raise!(Type, "cannot destructure HASH: missing key {}", key))
+            })
+            .collect()
+    }
+
+    // Compare two objects, producing an error if they have no ordering
+    pub fn compare(&self, other: &Object) -> Result<Ordering> {
+        self.partial_cmp(other).ok_or_else(|| {
+            raise!(
+                Type,
+                "unsupported types for comparison: {} {}",
+                self.type_name(),
+                other.type_name()
+            )
+        })
+    }
+
     // Check if an object is truthy
     pub fn is_truthy(&self) -> bool {
         match self {
@@ -111,29 +524,82 @@ impl Object {
             Object::Boolean(_) => "BOOLEAN",
             Object::String(_) => "STRING",
             Object::Array(_) => "ARRAY",
+            Object::Tuple(_) => "TUPLE",
             Object::Hash(_) => "HASH",
+            Object::Set(_) => "SET",
             Object::ReturnValue(_) => "RETURN_VALUE",
-            Object::Function(_, _, _) => "FUNCTION",
+            Object::Function(_) => "FUNCTION",
             Object::BuiltInFunction(_) => "BUILTIN",
             Object::CompiledFunction(_) => "COMPILED_FUNCTION",
             Object::Closure(_) => "CLOSURE",
+            Object::Error(_) => "ERROR",
+            Object::Module(_) => "MODULE",
+            Object::StructType(_) => "STRUCT_TYPE",
+            Object::Struct(_) => "STRUCT",
+            Object::Variant(_) => "VARIANT",
             Object::Null => "NULL",
         }
         .to_string()
     }
 }
 
+// Strings are sequences of Unicode code points: `len`, indexing and slicing
+// all count code points rather than bytes, so they agree on non-ASCII text.
+
+// Resolve a (possibly negative) index into a sequence of the given length
+pub fn sequence_index(len: usize, index: i64) -> Option<usize> {
+    let index = if index < 0 { len as i64 + index } else { index };
+    if index < 0 || index >= len as i64 {
+        return None;
+    }
+    Some(index as usize)
+}
+
+// Resolve Python-style slice bounds, clamped to a sequence of the given length
+pub fn slice_bounds(len: usize, start: Option<i64>, stop: Option<i64>) -> (usize, usize) {
+    let clamp = |bound: i64| {
+        let bound = if bound < 0 { len as i64 + bound } else { bound };
+        bound.clamp(0, len as i64) as usize
+    };
+    let start = start.map_or(0, clamp);
+    let stop = stop.map_or(len, clamp);
+    (start, stop.max(start))
+}
+
+// A string key holds the same Rc as the string object it was made from, so
+// a key written as a literal shares the compiler's interned constant with
+// every other use of that literal. Strings built at run time are not
+// interned: a table of them would keep every key ever used alive for the
+// rest of the program.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum HashKey {
     Integer(i64),
     Boolean(bool),
-    String(String),
+    String(Rc<String>),
+}
+
+impl From<&str> for Object {
+    fn from(value: &str) -> Self {
+        Object::String(Rc::new(value.to_string()))
+    }
 }
 
-impl Into<Object> for HashKey {
+impl From<String> for Object {
+    fn from(value: String) -> Self {
+        Object::String(Rc::new(value))
+    }
+}
+
+impl From<&str> for HashKey {
+    fn from(value: &str) -> Self {
+        HashKey::String(Rc::new(value.to_string()))
+    }
+}
+
+impl From<HashKey> for Object {
     // Convert hash keys into objects
-    fn into(self) -> Object {
-        match self {
+    fn from(key: HashKey) -> Self {
+        match key {
             HashKey::Integer(value) => Object::Integer(value),
             HashKey::Boolean(value) => Object::Boolean(value),
             HashKey::String(value) => Object::String(value),
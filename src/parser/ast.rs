@@ -8,10 +8,48 @@ pub enum Node<'a> {
     Expression(&'a Expression),
 }
 
+// The names a let statement binds: a single name, the elements of an array or
+// tuple (`let [x, y] = ...`), or the values of string keys in a hash
+// (`let {name, age} = ...`)
+#[derive(Debug, PartialEq, Clone)]
+pub enum Binding {
+    Name(String),
+    Sequence(Vec<String>),
+    Hash(Vec<String>),
+}
+
+impl Binding {
+    // Get the names bound, in order
+    pub fn names(&self) -> &[String] {
+        match self {
+            Binding::Name(name) => std::slice::from_ref(name),
+            Binding::Sequence(names) | Binding::Hash(names) => names,
+        }
+    }
+}
+
+impl Display for Binding {
+    // Pretty print bindings
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Binding::Name(name) => write!(f, "{}", name),
+            Binding::Sequence(names) => write!(f, "[{}]", names.join(", ")),
+            Binding::Hash(names) => write!(f, "{{{}}}", names.join(", ")),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum Statement {
-    Let(String, Expression),
+    Let(Binding, Expression),
     Return(Expression),
+    Throw(Expression),
+    // An import of the file at a path, bound to a name
+    Import(String, String),
+    // A struct declaration: its name and field names
+    Struct(String, Vec<String>),
+    // An enum declaration: its name and its variants' names and field names
+    Enum(String, Vec<(String, Vec<String>)>),
     Expression(Expression),
     Block(Vec<Statement>),
 }
@@ -26,6 +64,32 @@ impl Display for Statement {
             Statement::Return(value) => {
                 write!(f, "return {value};")
             }
+            Statement::Throw(value) => {
+                write!(f, "throw {value};")
+            }
+            Statement::Import(path, name) => {
+                write!(f, "import {path:?} as {name};")
+            }
+            Statement::Struct(name, fields) if fields.is_empty() => {
+                write!(f, "struct {name} {{}}")
+            }
+            Statement::Struct(name, fields) => {
+                write!(f, "struct {name} {{ {} }}", fields.join(", "))
+            }
+            Statement::Enum(name, variants) if variants.is_empty() => {
+                write!(f, "enum {name} {{}}")
+            }
+            Statement::Enum(name, variants) => {
+                let variants = variants
+                    .iter()
+                    .map(|(variant, fields)| match fields.is_empty() {
+                        true => variant.clone(),
+                        false => format!("{}({})", variant, fields.join(", ")),
+                    })
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                write!(f, "enum {name} {{ {variants} }}")
+            }
             Statement::Expression(value) => {
                 write!(f, "{}", value)
             }
@@ -48,14 +112,137 @@ pub enum Expression {
     Boolean(bool),
     String(String),
     Array(Vec<Expression>),
+    Tuple(Vec<Expression>),
     Hash(Vec<(Expression, Expression)>),
+    Set(Vec<Expression>),
     Prefix(Token, Box<Expression>),
     Infix(Token, Box<Expression>, Box<Expression>),
-    If(Box<Expression>, Box<Statement>, Option<Box<Statement>>),
-    Function(Vec<String>, Box<Statement>, Option<String>),
-    Call(Box<Expression>, Vec<Expression>),
+    // The (condition, consequence) branches of an if / else if chain, then the
+    // final else block
+    If(Vec<(Expression, Statement)>, Option<Box<Statement>>),
+    Function(Vec<Parameter>, Box<Statement>, Option<String>),
+    // The function, its positional arguments, then its keyword arguments
+    Call(Box<Expression>, Vec<Expression>, Vec<(String, Expression)>),
     Index(Box<Expression>, Box<Expression>),
-    SliceIndex(Box<Expression>, Option<Box<Expression>>, Option<Box<Expression>>)
+    SliceIndex(Box<Expression>, Option<Box<Expression>>, Option<Box<Expression>>),
+    Match(Box<Expression>, Vec<MatchArm>),
+    // A try block, then an optional catch block with the name the error is
+    // bound to, then an optional finally block. At least one of the two is present.
+    Try(Box<Statement>, Option<(String, Box<Statement>)>, Option<Box<Statement>>),
+    // A postfix `?`, which returns an error value from the enclosing function
+    // and otherwise evaluates to the value
+    Propagate(Box<Expression>),
+    Member(Box<Expression>, String),
+}
+
+// A function parameter. Optional parameters follow the required ones, and
+// their defaults are evaluated on each call that leaves them out. A rest
+// parameter comes last and collects the remaining arguments into an array.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Parameter {
+    Required(String),
+    Optional(String, Expression),
+    Rest(String),
+}
+
+impl Parameter {
+    // Get the name the parameter is bound to
+    pub fn name(&self) -> &String {
+        match self {
+            Parameter::Required(name) | Parameter::Optional(name, _) | Parameter::Rest(name) => name,
+        }
+    }
+}
+
+impl Display for Parameter {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Parameter::Required(name) => write!(f, "{}", name),
+            Parameter::Optional(name, default) => write!(f, "{} = {}", name, default),
+            Parameter::Rest(name) => write!(f, "...{}", name),
+        }
+    }
+}
+
+// A pattern in a match arm. Literals are integers, strings and booleans, and
+// hash keys are literals too. Arrays and tuples match values of exactly their
+// length, or at least their length with a trailing `..rest` (or `..`) pattern,
+// which is matched against the remaining elements. Hashes match when every
+// listed key is present and its value matches; other keys are ignored.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Pattern {
+    Wildcard,
+    Identifier(String),
+    Literal(Expression),
+    Array(Vec<Pattern>, Option<Box<Pattern>>),
+    Tuple(Vec<Pattern>, Option<Box<Pattern>>),
+    Hash(Vec<(Expression, Pattern)>),
+    // A struct or enum variant built by the named constructor, with patterns
+    // for its fields. A unit variant is matched by its name alone.
+    Constructor(String, Vec<Pattern>),
+}
+
+// An arm of a match expression, taken when its pattern matches and its guard
+// (if any) is truthy
+#[derive(Debug, PartialEq, Clone)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub guard: Option<Expression>,
+    pub body: Statement,
+}
+
+impl Display for Pattern {
+    // Pretty print patterns
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        // Format sequence elements followed by the rest pattern
+        let elements = |elements: &[Pattern], rest: &Option<Box<Pattern>>| {
+            let mut elements = elements.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+            match rest.as_deref() {
+                Some(Pattern::Wildcard) => elements.push("..".to_string()),
+                Some(rest) => elements.push(format!("..{}", rest)),
+                None => {}
+            }
+            elements
+        };
+        match self {
+            Pattern::Wildcard => write!(f, "_"),
+            Pattern::Identifier(name) => write!(f, "{}", name),
+            Pattern::Literal(value) => write!(f, "{}", value),
+            Pattern::Array(values, rest) => write!(f, "[{}]", elements(values, rest).join(", ")),
+            Pattern::Tuple(values, rest) => {
+                let values = elements(values, rest);
+                let trailing = if values.len() == 1 { "," } else { "" };
+                write!(f, "({}{})", values.join(", "), trailing)
+            }
+            Pattern::Hash(pairs) => {
+                let pairs = pairs
+                    .iter()
+                    .map(|(key, value)| format!("{}: {}", key, value))
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                write!(f, "{{{}}}", pairs)
+            }
+            Pattern::Constructor(name, fields) if fields.is_empty() => write!(f, "{}", name),
+            Pattern::Constructor(name, fields) => {
+                let fields = fields.iter().map(|field| field.to_string()).collect::<Vec<_>>();
+                write!(f, "{}({})", name, fields.join(", "))
+            }
+        }
+    }
+}
+
+impl Display for MatchArm {
+    // Pretty print match arms
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.pattern)?;
+        if let Some(guard) = &self.guard {
+            write!(f, " if {}", guard)?;
+        }
+        match &self.body {
+            Statement::Block(_) => write!(f, " => {{{}}}", self.body),
+            body => write!(f, " => {}", body),
+        }
+    }
 }
 
 impl Display for Expression {
@@ -82,6 +269,16 @@ impl Display for Expression {
                     .join(", ");
                 write!(f, "[{}]", values)
             }
+            Expression::Tuple(values) => {
+                // A single element tuple keeps its trailing comma
+                let trailing = if values.len() == 1 { "," } else { "" };
+                let values = values
+                    .iter()
+                    .map(|value| format!("{}", value))
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                write!(f, "({}{})", values, trailing)
+            }
             Expression::Hash(values) => {
                 let values = values
                     .iter()
@@ -90,26 +287,37 @@ impl Display for Expression {
                     .join(", ");
                 write!(f, "{}", values)
             }
+            Expression::Set(values) => {
+                let values = values
+                    .iter()
+                    .map(|value| format!("{}", value))
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                write!(f, "#{{{}}}", values)
+            }
             Expression::Prefix(op, right) => {
                 write!(f, "({}{})", op, right)
             }
             Expression::Infix(op, left, right) => {
                 write!(f, "({} {} {})", left, op, right)
             }
-            Expression::If(condition, consequence, Some(alternative)) => {
-                write!(
-                    f,
-                    "if ({}) {{{}}} else {{{}}}",
-                    condition, consequence, alternative
-                )
-            }
-            Expression::If(condition, consequence, None) => {
-                write!(f, "if ({}) {{{}}}", condition, consequence)
+            Expression::If(branches, alternative) => {
+                let branches = branches
+                    .iter()
+                    .map(|(condition, consequence)| {
+                        format!("if ({}) {{{}}}", condition, consequence)
+                    })
+                    .collect::<Vec<String>>()
+                    .join(" else ");
+                match alternative {
+                    Some(alternative) => write!(f, "{} else {{{}}}", branches, alternative),
+                    None => write!(f, "{}", branches),
+                }
             }
             Expression::Function(params, body, name) => {
                 let params = params
                     .iter()
-                    .map(|p| format!("{}", p))
+                    .map(|p| p.to_string())
                     .collect::<Vec<String>>()
                     .join(", ");
                 if let Some(name) = name {
@@ -118,10 +326,11 @@ impl Display for Expression {
                     write!(f, "fn({}) {{{}}}", params, body)
                 }
             }
-            Expression::Call(function, args) => {
+            Expression::Call(function, args, keywords) => {
                 let args = args
                     .iter()
                     .map(|a| format!("{}", a))
+                    .chain(keywords.iter().map(|(name, a)| format!("{} = {}", name, a)))
                     .collect::<Vec<String>>()
                     .join(", ");
                 write!(f, "{}({})", function, args)
@@ -145,6 +354,30 @@ impl Display for Expression {
                     }
                 }
             }
+            Expression::Match(subject, arms) => {
+                let arms = arms
+                    .iter()
+                    .map(|arm| arm.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                write!(f, "match ({}) {{{}}}", subject, arms)
+            }
+            Expression::Propagate(value) => {
+                write!(f, "({}?)", value)
+            }
+            Expression::Member(object, name) => {
+                write!(f, "({}.{})", object, name)
+            }
+            Expression::Try(block, catch, finally) => {
+                write!(f, "try {{{}}}", block)?;
+                if let Some((name, body)) = catch {
+                    write!(f, " catch ({}) {{{}}}", name, body)?;
+                }
+                if let Some(finally) = finally {
+                    write!(f, " finally {{{}}}", finally)?;
+                }
+                Ok(())
+            }
         }
     }
 }
@@ -3,7 +3,7 @@ pub mod ast;
 #[cfg(test)]
 mod tests;
 
-use crate::lexer::token::Token;
+use crate::lexer::token::{Span, Token};
 use crate::lexer::Lexer;
 use crate::token;
 use anyhow::{anyhow, Result};
@@ -20,15 +20,17 @@ pub enum Precedence {
     Prefix,      // -X or !X
     Call,        // myFunction(X)
     Index,       // array[index]
+    Member,      // object.member
 }
 
 impl From<&Token> for Precedence {
     fn from(token: &Token) -> Self {
         match token {
-            token!('[') => Precedence::Index,
+            token!('[') | token!(?) => Precedence::Index,
+            token!(.) => Precedence::Member,
             token!(:) => Precedence::Lowest,
             token!(==) | token!(!=) => Precedence::Equals,
-            token!(<) | token!(>) => Precedence::LessGreater,
+            token!(<) | token!(>) | token!(IN) => Precedence::LessGreater,
             token!(+) | token!(-) => Precedence::Sum,
             token!(*) | token!(/) => Precedence::Product,
             token!('(') => Precedence::Call,
@@ -41,6 +43,8 @@ pub struct Parser {
     lexer: Lexer,
     cur_token: Token,
     peek_token: Token,
+    cur_span: Span,
+    peek_span: Span,
     pub errors: Vec<String>,
 }
 
@@ -50,6 +54,8 @@ impl Parser {
             lexer,
             cur_token: Token::Illegal,
             peek_token: Token::Illegal,
+            cur_span: Span { line: 1, column: 1 },
+            peek_span: Span { line: 1, column: 1 },
             errors: Vec::new(),
         };
 
@@ -62,8 +68,9 @@ impl Parser {
 
     // Advance the parser by one token
     fn next_token(&mut self) {
-        self.cur_token = self.peek_token.clone();
-        self.peek_token = self.lexer.next_token();
+        let next = self.lexer.next_spanned_token();
+        self.cur_token = std::mem::replace(&mut self.peek_token, next.token);
+        self.cur_span = std::mem::replace(&mut self.peek_span, next.span);
     }
 
     // Parse an entire program
@@ -80,7 +87,7 @@ impl Parser {
             self.next_token();
         }
 
-        return program;
+        program
     }
 
     // Parse a statement
@@ -88,23 +95,37 @@ impl Parser {
         match self.cur_token {
             Token::Let => self.parse_let_statement(),
             Token::Return => self.parse_return_statement(),
+            Token::Throw => self.parse_throw_statement(),
+            Token::Import => self.parse_import_statement(),
+            Token::Struct => self.parse_struct_statement(),
+            Token::Enum => self.parse_enum_statement(),
             _ => self.parse_expression_statement(),
         }
     }
 
     // Parse a let statement
     fn parse_let_statement(&mut self) -> Result<Statement> {
-        // Parse the name of the variable
-        let name = match self.peek_token {
-            Token::Ident(ref name) => name.clone(),
+        // Parse the name of the variable, or the names to destructure into
+        let binding = match self.peek_token {
+            Token::Ident(ref name) => Binding::Name(name.clone()),
+            token!('[') => {
+                self.next_token();
+                Binding::Sequence(self.parse_binding_names(token!(']'))?)
+            }
+            token!('{') => {
+                self.next_token();
+                Binding::Hash(self.parse_binding_names(token!('}'))?)
+            }
             _ => {
                 return Err(anyhow!(
-                    "Expected next token to be IDENT, got {:?} instead",
+                    "Expected next token to be IDENT, [ or {{, got {:?} instead",
                     self.peek_token
                 ))
             }
         };
-        self.next_token();
+        if let Binding::Name(_) = binding {
+            self.next_token();
+        }
 
         // Parse the assignment operator
         if self.peek_token != Token::Assign {
@@ -120,11 +141,11 @@ impl Parser {
         let value = self.parse_expression(Precedence::Lowest)?;
 
         // Parse function name
-        let value = match value {
-            Expression::Function(parameters, body, _) => {
+        let value = match (value, &binding) {
+            (Expression::Function(parameters, body, _), Binding::Name(name)) => {
                 Expression::Function(parameters, body, Some(name.clone()))
             }
-            _ => value,
+            (value, _) => value,
         };
 
         // Semi-colon is optional
@@ -132,7 +153,41 @@ impl Parser {
             self.next_token();
         }
 
-        Ok(Statement::Let(name, value))
+        Ok(Statement::Let(binding, value))
+    }
+
+    // Parse the names of a destructuring let, up to the closing token
+    fn parse_binding_names(&mut self, end: Token) -> Result<Vec<String>> {
+        let mut names = Vec::new();
+
+        while self.peek_token != end {
+            self.next_token();
+
+            // Parse the name
+            match self.cur_token {
+                Token::Ident(ref name) => names.push(name.clone()),
+                _ => {
+                    return Err(anyhow!(
+                        "Expected next token to be IDENT, got {:?} instead",
+                        self.cur_token
+                    ))
+                }
+            };
+
+            // Parse the comma
+            if self.peek_token != token!(,) && self.peek_token != end {
+                return Err(anyhow!(
+                    "Expected next token to be , or {} got {:?} instead",
+                    end,
+                    self.peek_token
+                ));
+            } else if self.peek_token == token!(,) {
+                self.next_token();
+            }
+        }
+        self.next_token();
+
+        Ok(names)
     }
 
     // Parse a return statement
@@ -150,6 +205,128 @@ impl Parser {
         Ok(Statement::Return(value))
     }
 
+    // Parse a throw statement
+    fn parse_throw_statement(&mut self) -> Result<Statement> {
+        self.next_token();
+
+        // Parse the expression
+        let value = self.parse_expression(Precedence::Lowest)?;
+
+        // Semi-colon is optional
+        if self.peek_token == token!(;) {
+            self.next_token();
+        }
+
+        Ok(Statement::Throw(value))
+    }
+
+    // Parse an import statement
+    fn parse_import_statement(&mut self) -> Result<Statement> {
+        // Parse the path of the file
+        let path = match self.peek_token {
+            Token::String(ref path) => path.clone(),
+            _ => {
+                return Err(anyhow!(
+                    "Expected next token to be STRING, got {:?} instead",
+                    self.peek_token
+                ))
+            }
+        };
+        self.next_token();
+
+        // Parse the name the module is bound to
+        self.expect_peek(token!(AS))?;
+        let name = self.parse_name()?;
+
+        // Semi-colon is optional
+        if self.peek_token == token!(;) {
+            self.next_token();
+        }
+
+        Ok(Statement::Import(path, name))
+    }
+
+    // Parse a struct declaration
+    fn parse_struct_statement(&mut self) -> Result<Statement> {
+        let name = self.parse_name()?;
+
+        // Parse the field names
+        self.expect_peek(token!('{'))?;
+        let fields = self.parse_binding_names(token!('}'))?;
+        for (i, field) in fields.iter().enumerate() {
+            if fields[..i].contains(field) {
+                return Err(anyhow!("duplicate field {} in struct {}", field, name));
+            }
+        }
+
+        // Semi-colon is optional
+        if self.peek_token == token!(;) {
+            self.next_token();
+        }
+
+        Ok(Statement::Struct(name, fields))
+    }
+
+    // Parse an enum declaration
+    fn parse_enum_statement(&mut self) -> Result<Statement> {
+        let name = self.parse_name()?;
+        self.expect_peek(token!('{'))?;
+
+        // Parse the variants, each with its field names if it has any
+        let mut variants: Vec<(String, Vec<String>)> = Vec::new();
+        while self.peek_token != token!('}') {
+            let variant = self.parse_name()?;
+            if variants.iter().any(|(other, _)| *other == variant) {
+                return Err(anyhow!("duplicate variant {} in enum {}", variant, name));
+            }
+            let fields = match self.peek_token {
+                token!('(') => {
+                    self.next_token();
+                    self.parse_binding_names(token!(')'))?
+                }
+                _ => vec![],
+            };
+            for (i, field) in fields.iter().enumerate() {
+                if fields[..i].contains(field) {
+                    return Err(anyhow!("duplicate field {} in variant {}", field, variant));
+                }
+            }
+            variants.push((variant, fields));
+
+            if self.peek_token != token!(,) && self.peek_token != token!('}') {
+                return Err(anyhow!(
+                    "Expected next token to be , or }} got {:?} instead",
+                    self.peek_token
+                ));
+            } else if self.peek_token == token!(,) {
+                self.next_token();
+            }
+        }
+        self.next_token();
+
+        // Semi-colon is optional
+        if self.peek_token == token!(;) {
+            self.next_token();
+        }
+
+        Ok(Statement::Enum(name, variants))
+    }
+
+    // Parse an identifier in the next token, returning its name
+    fn parse_name(&mut self) -> Result<String> {
+        let name = match self.peek_token {
+            Token::Ident(ref name) => name.clone(),
+            _ => {
+                return Err(anyhow!(
+                    "Expected next token to be IDENT, got {:?} instead",
+                    self.peek_token
+                ))
+            }
+        };
+        self.next_token();
+        Ok(name)
+    }
+
     // Parse an expression statement
     fn parse_expression_statement(&mut self) -> Result<Statement> {
         let expression = self.parse_expression(Precedence::Lowest)?;
@@ -171,6 +348,10 @@ impl Parser {
         // Parse all statements until the closing brace
         while self.cur_token != token!('}') {
             match self.parse_statement() {
+                // Modules are loaded once, so imports cannot be conditional
+                Ok(Statement::Import(..)) => {
+                    self.errors.push("import is only allowed at the top level".to_string())
+                }
                 Ok(stmt) => statements.push(stmt),
                 Err(err) => self.errors.push(err.to_string()),
             };
@@ -188,10 +369,7 @@ impl Parser {
         let mut left = prefix;
         while self.peek_token != token!(;) && precedence < Precedence::from(&self.peek_token) {
             // Parse the infix
-            let infix = match self.parse_infix(&left) {
-                Ok(infix) => infix,
-                Err(err) => return Err(err),
-            };
+            let infix = self.parse_infix(&left)?;
 
             // Update the left side of the expression
             left = infix;
@@ -210,10 +388,13 @@ impl Parser {
             token!(FALSE) => self.parse_boolean(false),
             token!('[') => self.parse_array(),
             token!('{') => self.parse_hash(),
+            token!("#{") => self.parse_set(),
             token!(!) | token!(-) => self.parse_prefix_expression(),
             token!('(') => self.parse_group(),
             token!(IF) => self.parse_if(),
             token!(FUNCTION) => self.parse_function(),
+            token!(MATCH) => self.parse_match(),
+            token!(TRY) => self.parse_try(),
             _ => Err(anyhow!(
                 "No prefix parse function for {} found",
                 self.cur_token
@@ -226,11 +407,45 @@ impl Parser {
         Ok(Expression::Identifier(value))
     }
 
-    // Parse an integer
+    // Parse an integer (decimal, or 0x / 0o / 0b prefixed, with _ separators)
     fn parse_integer(&mut self, value: String) -> Result<Expression> {
+        // Split off the radix prefix
+        let (radix, kind, digits) = match value.get(..2) {
+            Some("0x") | Some("0X") => (16, "hexadecimal", &value[2..]),
+            Some("0o") | Some("0O") => (8, "octal", &value[2..]),
+            Some("0b") | Some("0B") => (2, "binary", &value[2..]),
+            _ => (10, "decimal", value.as_str()),
+        };
+
+        // Validate the digits, ignoring separators
+        let digits = digits.replace('_', "");
+        if digits.is_empty() {
+            return Err(anyhow!(
+                "{} literal {} has no digits at {}",
+                kind,
+                value,
+                self.cur_span
+            ));
+        }
+        if let Some(invalid) = digits.chars().find(|ch| !ch.is_digit(radix)) {
+            return Err(anyhow!(
+                "invalid digit '{}' in {} literal {} at {}",
+                invalid,
+                kind,
+                value,
+                self.cur_span
+            ));
+        }
+
         // Parse the integer
-        let int = value.parse::<i64>()?;
-        Ok(Expression::Integer(int))
+        match i64::from_str_radix(&digits, radix) {
+            Ok(int) => Ok(Expression::Integer(int)),
+            Err(_) => Err(anyhow!(
+                "integer literal {} is too large for INTEGER at {}",
+                value,
+                self.cur_span
+            )),
+        }
     }
 
     // Parse a string
@@ -296,6 +511,13 @@ impl Parser {
         Ok(Expression::Hash(pairs))
     }
 
+    // Parse a set
+    fn parse_set(&mut self) -> Result<Expression> {
+        // Parse the set elements
+        let elements = self.parse_expressions(token!('}'))?;
+        Ok(Expression::Set(elements))
+    }
+
     // Parse a prefix expression
     fn parse_prefix_expression(&mut self) -> Result<Expression> {
         // Save the operator
@@ -306,11 +528,26 @@ impl Parser {
         Ok(Expression::Prefix(op, Box::new(right)))
     }
 
-    // Parse a group expression
+    // Parse a group expression, or a tuple if it contains a comma
     fn parse_group(&mut self) -> Result<Expression> {
+        // Parse the empty tuple
+        if self.peek_token == token!(')') {
+            self.next_token();
+            return Ok(Expression::Tuple(vec![]));
+        }
+
         // Parse the expression inside the parentheses
         self.next_token();
         let expression = self.parse_expression(Precedence::Lowest)?;
+
+        // Parse the remaining tuple elements, allowing a trailing comma
+        if self.peek_token == token!(,) {
+            self.next_token();
+            let mut elements = vec![expression];
+            elements.extend(self.parse_expressions(token!(')'))?);
+            return Ok(Expression::Tuple(elements));
+        }
+
         // Parse the closing parenthesis
         if self.peek_token != token!(')') {
             return Err(anyhow!(
@@ -322,8 +559,35 @@ impl Parser {
         Ok(expression)
     }
 
-    // Parse an if expression
+    // Parse an if expression, along with any else if branches and final else
     fn parse_if(&mut self) -> Result<Expression> {
+        let mut branches = vec![self.parse_if_branch()?];
+
+        // Parse the else if branches and the else body
+        let mut alternative = None;
+        while self.peek_token == token!(ELSE) {
+            self.next_token();
+            if self.peek_token == token!(IF) {
+                self.next_token();
+                branches.push(self.parse_if_branch()?);
+                continue;
+            }
+            if self.peek_token != token!('{') {
+                return Err(anyhow!(
+                    "Expected next token to be {{ or if, got {:?} instead",
+                    self.peek_token
+                ));
+            }
+            self.next_token();
+            alternative = Some(Box::new(self.parse_block_statement()?));
+            break;
+        }
+
+        Ok(Expression::If(branches, alternative))
+    }
+
+    // Parse the condition and body of an if branch
+    fn parse_if_branch(&mut self) -> Result<(Expression, Statement)> {
         // Parse the if condition
         if self.peek_token != token!('(') {
             return Err(anyhow!(
@@ -352,29 +616,242 @@ impl Parser {
         self.next_token();
         let consequence = self.parse_block_statement()?;
 
-        // Parse the else body
-        let alternative = if self.peek_token == token!(ELSE) {
+        Ok((condition, consequence))
+    }
+
+    // Parse a try expression with its catch and finally blocks
+    fn parse_try(&mut self) -> Result<Expression> {
+        self.expect_peek(token!('{'))?;
+        let block = self.parse_block_statement()?;
+
+        // Parse the catch block and the name it binds the error to
+        let catch = if self.peek_token == token!(CATCH) {
             self.next_token();
-            if self.peek_token != token!('{') {
-                return Err(anyhow!(
-                    "Expected next token to be {{, got {:?} instead",
-                    self.peek_token
-                ));
-            }
+            self.expect_peek(token!('('))?;
+            let name = self.parse_name()?;
+            self.expect_peek(token!(')'))?;
+            self.expect_peek(token!('{'))?;
+            Some((name, Box::new(self.parse_block_statement()?)))
+        } else {
+            None
+        };
+
+        // Parse the finally block
+        let finally = if self.peek_token == token!(FINALLY) {
             self.next_token();
+            self.expect_peek(token!('{'))?;
             Some(Box::new(self.parse_block_statement()?))
         } else {
             None
         };
 
-        return Ok(Expression::If(
-            Box::new(condition),
-            Box::new(consequence),
-            alternative,
-        ));
+        if catch.is_none() && finally.is_none() {
+            return Err(anyhow!(
+                "Expected next token to be catch or finally, got {:?} instead",
+                self.peek_token
+            ));
+        }
+
+        Ok(Expression::Try(Box::new(block), catch, finally))
+    }
+
+    // Parse a match expression
+    fn parse_match(&mut self) -> Result<Expression> {
+        // Parse the value being matched
+        self.expect_peek(token!('('))?;
+        self.next_token();
+        let subject = self.parse_expression(Precedence::Lowest)?;
+        self.expect_peek(token!(')'))?;
+        self.expect_peek(token!('{'))?;
+
+        // Parse the arms, separated by optional commas
+        let mut arms = Vec::new();
+        while self.peek_token != token!('}') {
+            self.next_token();
+            let pattern = self.parse_pattern()?;
+
+            // Parse the guard
+            let guard = if self.peek_token == token!(IF) {
+                self.next_token();
+                self.next_token();
+                Some(self.parse_expression(Precedence::Lowest)?)
+            } else {
+                None
+            };
+
+            // Parse the body, either a block or a single expression
+            self.expect_peek(token!(=>))?;
+            self.next_token();
+            let body = if self.cur_token == token!('{') {
+                self.parse_block_statement()?
+            } else {
+                Statement::Expression(self.parse_expression(Precedence::Lowest)?)
+            };
+
+            arms.push(MatchArm {
+                pattern,
+                guard,
+                body,
+            });
+
+            if self.peek_token == token!(,) {
+                self.next_token();
+            }
+        }
+        self.next_token();
+
+        Ok(Expression::Match(Box::new(subject), arms))
     }
 
-    // Parse a function expression
+    // Parse a match pattern starting at the current token
+    fn parse_pattern(&mut self) -> Result<Pattern> {
+        match self.cur_token {
+            Token::Ident(ref name) if name == "_" => Ok(Pattern::Wildcard),
+            // A constructor pattern, or a capitalised name for a unit variant
+            Token::Ident(ref name) if self.peek_token == token!('(') => {
+                let name = name.clone();
+                self.next_token();
+                let (fields, rest, _) = self.parse_sequence_pattern(token!(')'))?;
+                if rest.is_some() {
+                    return Err(anyhow!("rest pattern not allowed in constructor pattern {}", name));
+                }
+                Ok(Pattern::Constructor(name, fields))
+            }
+            Token::Ident(ref name) if name.starts_with(char::is_uppercase) => {
+                Ok(Pattern::Constructor(name.clone(), vec![]))
+            }
+            Token::Ident(ref name) => Ok(Pattern::Identifier(name.clone())),
+            Token::Int(_) | Token::String(_) | token!(TRUE) | token!(FALSE) | token!(-) => {
+                Ok(Pattern::Literal(self.parse_literal()?))
+            }
+            token!('[') => {
+                let (elements, rest, _) = self.parse_sequence_pattern(token!(']'))?;
+                Ok(Pattern::Array(elements, rest))
+            }
+            token!('(') => {
+                let (mut elements, rest, commas) = self.parse_sequence_pattern(token!(')'))?;
+                // A single pattern without a comma is only grouped
+                if elements.len() == 1 && rest.is_none() && commas == 0 {
+                    return Ok(elements.remove(0));
+                }
+                Ok(Pattern::Tuple(elements, rest))
+            }
+            token!('{') => {
+                let mut pairs = Vec::new();
+                while self.peek_token != token!('}') {
+                    self.next_token();
+                    let key = self.parse_literal()?;
+                    self.expect_peek(token!(:))?;
+                    self.next_token();
+                    pairs.push((key, self.parse_pattern()?));
+
+                    if self.peek_token != token!(,) && self.peek_token != token!('}') {
+                        return Err(anyhow!(
+                            "Expected next token to be , or }} got {:?} instead",
+                            self.peek_token
+                        ));
+                    } else if self.peek_token == token!(,) {
+                        self.next_token();
+                    }
+                }
+                self.next_token();
+                Ok(Pattern::Hash(pairs))
+            }
+            _ => Err(anyhow!("No pattern parse function for {} found", self.cur_token)),
+        }
+    }
+
+    // Parse the element patterns of an array or tuple pattern, up to the
+    // closing token, returning them with the rest pattern and comma count
+    fn parse_sequence_pattern(
+        &mut self,
+        end: Token,
+    ) -> Result<(Vec<Pattern>, Option<Box<Pattern>>, usize)> {
+        let mut elements = Vec::new();
+        let mut rest = None;
+        let mut commas = 0;
+
+        while self.peek_token != end {
+            self.next_token();
+
+            // Parse the rest pattern, which must come last
+            if self.cur_token == token!(..) {
+                let pattern = match self.peek_token {
+                    Token::Ident(_) => {
+                        self.next_token();
+                        self.parse_pattern()?
+                    }
+                    _ => Pattern::Wildcard,
+                };
+                rest = Some(Box::new(pattern));
+                if self.peek_token == token!(,) {
+                    self.next_token();
+                }
+                if self.peek_token != end {
+                    return Err(anyhow!(
+                        "Expected rest pattern to be last, got {:?} after it",
+                        self.peek_token
+                    ));
+                }
+                break;
+            }
+
+            elements.push(self.parse_pattern()?);
+
+            // Parse the comma
+            if self.peek_token != token!(,) && self.peek_token != end {
+                return Err(anyhow!(
+                    "Expected next token to be , or {} got {:?} instead",
+                    end,
+                    self.peek_token
+                ));
+            } else if self.peek_token == token!(,) {
+                self.next_token();
+                commas += 1;
+            }
+        }
+        self.next_token();
+
+        Ok((elements, rest, commas))
+    }
+
+    // Parse a literal in a pattern: an integer (optionally negated), string or boolean
+    fn parse_literal(&mut self) -> Result<Expression> {
+        match self.cur_token {
+            Token::Int(ref value) => self.parse_integer(value.clone()),
+            Token::String(ref value) => self.parse_string(value.clone()),
+            token!(TRUE) => self.parse_boolean(true),
+            token!(FALSE) => self.parse_boolean(false),
+            token!(-) => match self.peek_token {
+                Token::Int(ref value) => {
+                    let value = value.clone();
+                    self.next_token();
+                    match self.parse_integer(value)? {
+                        Expression::Integer(value) => Ok(Expression::Integer(-value)),
+                        _ => unreachable!(),
+                    }
+                }
+                _ => Err(anyhow!(
+                    "Expected next token to be INT, got {:?} instead",
+                    self.peek_token
+                )),
+            },
+            _ => Err(anyhow!("Expected a literal, got {:?} instead", self.cur_token)),
+        }
+    }
+
+    // Advance past the next token if it is the expected one
+    fn expect_peek(&mut self, token: Token) -> Result<()> {
+        if self.peek_token != token {
+            return Err(anyhow!(
+                "Expected next token to be {}, got {:?} instead",
+                token,
+                self.peek_token
+            ));
+        }
+        self.next_token();
+        Ok(())
+    }
     fn parse_function(&mut self) -> Result<Expression> {
         // Parse the function parameters
         if self.peek_token != token!('(') {
@@ -396,20 +873,31 @@ impl Parser {
         self.next_token();
         let body = self.parse_block_statement()?;
 
-        return Ok(Expression::Function(parameters, Box::new(body), None));
+        Ok(Expression::Function(parameters, Box::new(body), None))
     }
 
     // Parse the parameters of a function
-    fn parse_function_parameters(&mut self) -> Result<Vec<String>> {
+    fn parse_function_parameters(&mut self) -> Result<Vec<Parameter>> {
         let mut parameters = Vec::new();
 
         // Parse the parameters
         while self.peek_token != token!(')') {
             self.next_token();
 
-            // Parse the parameter name
-            match self.cur_token {
-                Token::Ident(ref value) => parameters.push(value.clone()),
+            if matches!(parameters.last(), Some(Parameter::Rest(_))) {
+                return Err(anyhow!(
+                    "Expected rest parameter to be last, got {:?} after it",
+                    self.cur_token
+                ));
+            }
+
+            // Parse the parameter, with a default value or as a rest parameter
+            let rest = self.cur_token == token!(...);
+            if rest {
+                self.next_token();
+            }
+            let name = match self.cur_token {
+                Token::Ident(ref value) => value.clone(),
                 _ => {
                     return Err(anyhow!(
                         "Expected next token to be IDENT, got {:?} instead",
@@ -417,6 +905,18 @@ impl Parser {
                     ))
                 }
             };
+            let parameter = if rest {
+                Parameter::Rest(name)
+            } else if self.peek_token == token!(=) {
+                self.next_token();
+                self.next_token();
+                Parameter::Optional(name, self.parse_expression(Precedence::Lowest)?)
+            } else if matches!(parameters.last(), Some(Parameter::Optional(..))) {
+                return Err(anyhow!("required parameter {} after optional parameter", name));
+            } else {
+                Parameter::Required(name)
+            };
+            parameters.push(parameter);
 
             // Parse the comma
             if self.peek_token != token!(,) && self.peek_token != token!(')') {
@@ -441,7 +941,7 @@ impl Parser {
         Ok(parameters)
     }
 
-    // Parse an expression list (used for arrays and function calls)
+    // Parse an expression list (used for arrays, sets, tuples and function calls)
     fn parse_expressions(&mut self, end: Token) -> Result<Vec<Expression>> {
         let mut expressions = Vec::new();
 
@@ -487,9 +987,19 @@ impl Parser {
             | token!(==)
             | token!(!=)
             | token!(<)
-            | token!(>) => self.parse_infix_expression(left.clone()),
+            | token!(>)
+            | token!(IN) => self.parse_infix_expression(left.clone()),
             token!('(') => self.parse_call(left.clone()),
             token!('[') => self.parse_index(left.clone()),
+            token!(?) => {
+                self.next_token();
+                Ok(Expression::Propagate(Box::new(left.clone())))
+            }
+            token!(.) => {
+                self.next_token();
+                let name = self.parse_name()?;
+                Ok(Expression::Member(Box::new(left.clone()), name))
+            }
             _ => Err(anyhow!(
                 "No infix parse function for {} found",
                 self.peek_token
@@ -515,11 +1025,42 @@ impl Parser {
 
     // Parse a call expression
     fn parse_call(&mut self, function: Expression) -> Result<Expression> {
-        // Parse the function arguments
         self.next_token();
-        let arguments = self.parse_expressions(token!(')'))?;
+        let mut arguments = Vec::new();
+        let mut keywords: Vec<(String, Expression)> = Vec::new();
+
+        // Parse the positional arguments, then the `name = value` keyword arguments
+        while self.peek_token != token!(')') {
+            self.next_token();
+
+            match self.cur_token.clone() {
+                Token::Ident(name) if self.peek_token == token!(=) => {
+                    if keywords.iter().any(|(keyword, _)| *keyword == name) {
+                        return Err(anyhow!("duplicate keyword argument {}", name));
+                    }
+                    self.next_token();
+                    self.next_token();
+                    keywords.push((name, self.parse_expression(Precedence::Lowest)?));
+                }
+                _ if !keywords.is_empty() => {
+                    return Err(anyhow!("positional argument after keyword argument"));
+                }
+                _ => arguments.push(self.parse_expression(Precedence::Lowest)?),
+            }
+
+            // Parse the comma
+            if self.peek_token != token!(,) && self.peek_token != token!(')') {
+                return Err(anyhow!(
+                    "Expected next token to be , or ) got {:?} instead",
+                    self.peek_token
+                ));
+            } else if self.peek_token == token!(,) {
+                self.next_token();
+            }
+        }
+        self.next_token();
 
-        Ok(Expression::Call(Box::new(function), arguments))
+        Ok(Expression::Call(Box::new(function), arguments, keywords))
     }
 
     // Parse an index expression
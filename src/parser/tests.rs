@@ -37,7 +37,7 @@ fn test_let_statements() {
 
         assert!(matches!(stmt,
             Statement::Let(
-                var_name,
+                Binding::Name(var_name),
                 expr
             ) if var_name == name && expr.to_string() == value
         ));
@@ -69,7 +69,7 @@ fn test_return_statements() {
 fn test_parser_string() {
     let program = Program {
         statements: vec![Statement::Let(
-            String::from("myVar"),
+            Binding::Name(String::from("myVar")),
             Expression::Identifier(String::from("anotherVar")),
         )],
     };
@@ -107,6 +107,57 @@ fn test_integer_literal_expression() {
     ));
 }
 
+#[test]
+fn test_integer_literal_radix_expression() {
+    let tests = vec![
+        ("1_000_000;", 1_000_000),
+        ("0x1F;", 31),
+        ("0Xff_ff;", 65535),
+        ("0o17;", 15),
+        ("0b1010;", 10),
+        ("0b_1111_0000;", 240),
+        ("9223372036854775807;", i64::MAX),
+    ];
+
+    for (input, expected) in tests {
+        let program = setup_test(String::from(input), Some(1));
+
+        let stmt = &program.statements[0];
+
+        assert!(matches!(stmt,
+            Statement::Expression(
+                Expression::Integer(value)
+            ) if *value == expected
+        ));
+    }
+}
+
+#[test]
+fn test_integer_literal_errors() {
+    let tests = vec![
+        (
+            "9223372036854775808",
+            "integer literal 9223372036854775808 is too large for INTEGER at line 1, column 1",
+        ),
+        (
+            "let x =\n  0xFFFF_FFFF_FFFF_FFFF",
+            "integer literal 0xFFFF_FFFF_FFFF_FFFF is too large for INTEGER at line 2, column 3",
+        ),
+        ("0b102", "invalid digit '2' in binary literal 0b102 at line 1, column 1"),
+        ("0o8", "invalid digit '8' in octal literal 0o8 at line 1, column 1"),
+        ("12ab", "invalid digit 'a' in decimal literal 12ab at line 1, column 1"),
+        ("0x_", "hexadecimal literal 0x_ has no digits at line 1, column 1"),
+    ];
+
+    for (input, expected) in tests {
+        let lexer = Lexer::new(String::from(input));
+        let mut parser = Parser::new(lexer);
+        parser.parse_program();
+
+        assert_eq!(parser.errors, vec![expected.to_string()]);
+    }
+}
+
 #[test]
 fn test_prefix_expressions() {
     let tests = vec![
@@ -217,6 +268,34 @@ fn test_operator_precedence() {
             "add(a * b[2], b[1], 2 * [1, 2][1])",
             "add((a * (b[2])), (b[1]), (2 * ([1, 2][1])))",
         ),
+        ("a + 1 in b == true", "(((a + 1) in b) == true)"),
+        ("!a in #{1, b}", "((!a) in #{1, b})"),
+        ("-f(x)?", "(-(f(x)?))"),
+        ("a + b? * c", "(a + ((b?) * c))"),
+        ("a[0]?[1]", "(((a[0])?)[1])"),
+        ("-m.f(x)", "(-(m.f)(x))"),
+        ("m.a[0].b", "(((m.a)[0]).b)"),
+        ("a.b.c", "((a.b).c)"),
+        ("a.f(x).g(y)", "((a.f)(x).g)(y)"),
+        ("a + b.c * d", "(a + ((b.c) * d))"),
+        ("(a + b) * c", "((a + b) * c)"),
+        ("(a, b + c)", "(a, (b + c))"),
+        ("(a,)", "(a,)"),
+        ("()", "()"),
+        ("let [a, b] = (b, a);", "let [a, b] = (b, a);"),
+        ("let {name, age} = person", "let {name, age} = person;"),
+        (
+            "match (x) { 1 => a, -1 => b, [h, ..t] => h, [..] => c, (x,) => x, (y) => y }",
+            "match (x) {1 => a, -1 => b, [h, ..t] => h, [..] => c, (x,) => x, y => y}",
+        ),
+        (
+            "match (f(x)) { {\"type\": t, 1: _} if t == 2 => { t } _ => 0 }",
+            "match (f(x)) {{type: t, 1: _} if (t == 2) => {t}, _ => 0}",
+        ),
+        (
+            "match (s) { Circle(r) => r, Rect(w, [h]) => w, Empty => 0, empty => 1 }",
+            "match (s) {Circle(r) => r, Rect(w, [h]) => w, Empty => 0, empty => 1}",
+        ),
     ];
 
     for (input, expected) in tests {
@@ -252,12 +331,12 @@ fn test_if_expression() {
     assert!(matches!(stmt,
         Statement::Expression(
             Expression::If(
-                condition,
-                consequence,
+                branches,
                 None
             )
-        ) if condition.to_string() == "(x < y)" &&
-            consequence.to_string() == "x"
+        ) if matches!(&branches[..], [(condition, consequence)]
+            if condition.to_string() == "(x < y)" &&
+                consequence.to_string() == "x")
     ));
 }
 
@@ -271,16 +350,44 @@ fn test_if_else_expression() {
     assert!(matches!(stmt,
         Statement::Expression(
             Expression::If(
-                condition,
-                consequence,
+                branches,
                 Some(alternative)
             )
-        ) if condition.to_string() == "(x < y)" &&
-            consequence.to_string() == "x" &&
+        ) if matches!(&branches[..], [(condition, consequence)]
+            if condition.to_string() == "(x < y)" &&
+                consequence.to_string() == "x") &&
             alternative.to_string() == "y"
     ));
 }
 
+#[test]
+fn test_else_if_expression() {
+    let input = String::from("if (x < y) { x } else if (x > y) { y } else if (z) { z } else { 0 }");
+
+    let program = setup_test(input, Some(1));
+
+    let stmt = &program.statements[0];
+    assert!(matches!(stmt,
+        Statement::Expression(
+            Expression::If(
+                branches,
+                Some(alternative)
+            )
+        ) if branches.len() == 3 &&
+            branches[1].0.to_string() == "(x > y)" &&
+            branches[2].1.to_string() == "z" &&
+            alternative.to_string() == "0"
+    ));
+    assert_eq!(
+        stmt.to_string(),
+        "if ((x < y)) {x} else if ((x > y)) {y} else if (z) {z} else {0}"
+    );
+
+    // The display output parses back to the same program
+    let reparsed = setup_test(stmt.to_string(), Some(1));
+    assert_eq!(reparsed.statements[0].to_string(), stmt.to_string());
+}
+
 #[test]
 fn test_fn_literal_parsing() {
     let input = String::from("fn(x, y) { x + y; }");
@@ -313,6 +420,9 @@ fn test_fn_parameter_parsing() {
         ("fn() {};", vec![]),
         ("fn(x) {};", vec!["x"]),
         ("fn(x, y, z) {};", vec!["x", "y", "z"]),
+        ("fn(x, y = 1 + 2, z = x) {};", vec!["x", "y = (1 + 2)", "z = x"]),
+        ("fn(x, ...rest) {};", vec!["x", "...rest"]),
+        ("fn(...rest) {};", vec!["...rest"]),
     ];
 
     for (input, args) in tests {
@@ -343,7 +453,8 @@ fn test_call_expression_parsing() {
         Statement::Expression(
             Expression::Call(
                 function,
-                arguments
+                arguments,
+                _
             )
         ) if function.to_string() == "add" &&
             arguments.len() == 3 &&
@@ -353,6 +464,40 @@ fn test_call_expression_parsing() {
     ));
 }
 
+#[test]
+fn test_optional_parameters_and_keyword_arguments() {
+    let program = setup_test(String::from("f(1, b = 2, c = x + 1);"), Some(1));
+    assert!(matches!(&program.statements[0],
+        Statement::Expression(Expression::Call(_, arguments, keywords))
+            if arguments.len() == 1 && keywords.len() == 2 && keywords[1].0 == "c"
+    ));
+
+    let tests = vec![
+        ("f(1, b = 2, c = x + 1)", "f(1, b = 2, c = (x + 1))"),
+        ("f(a = g(b = 1))", "f(a = g(b = 1))"),
+        ("fn(a, b = [1], ...c) { a }", "fn(a, b = [1], ...c) {a}"),
+    ];
+
+    for (input, expected) in tests {
+        let program = setup_test(String::from(input), Some(1));
+        assert_eq!(program.to_string(), expected);
+    }
+
+    let errors = vec![
+        ("f(a = 1, 2)", "positional argument after keyword argument"),
+        ("f(a = 1, a = 2)", "duplicate keyword argument a"),
+        ("fn(a = 1, b) { }", "required parameter b after optional parameter"),
+        ("fn(...a, b) { }", "Expected rest parameter to be last, got Ident(\"b\") after it"),
+        ("fn(...) { }", "Expected next token to be IDENT, got RParen instead"),
+    ];
+
+    for (input, expected) in errors {
+        let mut parser = Parser::new(Lexer::new(input.to_string()));
+        parser.parse_program();
+        assert_eq!(parser.errors.first().map(String::as_str), Some(expected));
+    }
+}
+
 #[test]
 fn test_call_expression_parameter_parsing() {
     let tests = vec![
@@ -369,7 +514,8 @@ fn test_call_expression_parameter_parsing() {
             Statement::Expression(
                 Expression::Call(
                     _,
-                    arguments
+                    arguments,
+                    _
                 )
             ) if arguments.len() == args.len() &&
                 arguments.iter().zip(args.iter()).all(|(a, b)| a.to_string() == *b)
@@ -465,7 +611,7 @@ fn test_hash_literal_string_keys() {
         ",
     );
 
-    let tests = vec![
+    let tests = [
         ("one", Expression::Integer(1)),
         ("two", Expression::Integer(2)),
         ("three", Expression::Integer(3)),
@@ -510,7 +656,7 @@ fn test_hash_literal_expressions() {
         ",
     );
     
-    let tests = vec![
+    let tests = [
         (
             "one",
             Expression::Infix(
@@ -568,3 +714,179 @@ fn test_function_literal_with_name() {
         ) if name == "myFunction"
     ));
 }
+
+#[test]
+fn test_set_literal() {
+    let input = String::from("#{1, 2 * 3, \"a\"}; #{}");
+
+    let program = setup_test(input, Some(2));
+
+    assert_eq!(
+        program.statements[0],
+        Statement::Expression(Expression::Set(vec![
+            Expression::Integer(1),
+            Expression::Infix(
+                token!(*),
+                Box::new(Expression::Integer(2)),
+                Box::new(Expression::Integer(3)),
+            ),
+            Expression::String(String::from("a")),
+        ]))
+    );
+    assert_eq!(program.statements[1], Statement::Expression(Expression::Set(vec![])));
+}
+
+#[test]
+fn test_destructuring_let() {
+    let tests = vec![
+        ("let [x, y] = f();", Binding::Sequence(vec!["x".to_string(), "y".to_string()])),
+        ("let [] = f();", Binding::Sequence(vec![])),
+        ("let {name, age} = person;", Binding::Hash(vec!["name".to_string(), "age".to_string()])),
+    ];
+
+    for (input, binding) in tests {
+        let program = setup_test(String::from(input), Some(1));
+        assert!(matches!(&program.statements[0], Statement::Let(b, _) if *b == binding));
+    }
+
+    // Destructured functions are not named after the binding
+    let program = setup_test(String::from("let [f] = [fn() { }];"), Some(1));
+    assert!(matches!(&program.statements[0],
+        Statement::Let(_, Expression::Array(values))
+            if matches!(values[0], Expression::Function(_, _, None))
+    ));
+}
+
+#[test]
+fn test_match_pattern_errors() {
+    let tests = vec![
+        ("match (x) { [..rest, y] => y }", "Expected rest pattern to be last, got Ident(\"y\") after it"),
+        ("match (x) { {a: 1} => 1 }", "Expected a literal, got Ident(\"a\") instead"),
+        ("match (x) { 1 -> 1 }", "Expected next token to be =>, got Minus instead"),
+        ("match (x) { Some(..rest) => 1 }", "rest pattern not allowed in constructor pattern Some"),
+    ];
+
+    for (input, expected) in tests {
+        let mut parser = Parser::new(Lexer::new(input.to_string()));
+        parser.parse_program();
+        assert_eq!(parser.errors.first().map(String::as_str), Some(expected));
+    }
+}
+
+#[test]
+fn test_struct_statement() {
+    let program = setup_test(String::from("struct Point { x, y }"), Some(1));
+    assert!(matches!(&program.statements[0],
+        Statement::Struct(name, fields) if name == "Point" && fields == &["x", "y"]
+    ));
+
+    let tests = vec![
+        ("struct Point { x, y };", "struct Point { x, y }"),
+        ("struct Empty {}", "struct Empty {}"),
+        ("struct Pair { first, second, }", "struct Pair { first, second }"),
+    ];
+
+    for (input, expected) in tests {
+        let program = setup_test(String::from(input), Some(1));
+        assert_eq!(program.statements[0].to_string(), expected);
+    }
+
+    let errors = vec![
+        ("struct { x }", "Expected next token to be IDENT, got LBrace instead"),
+        ("struct Point (x, y)", "Expected next token to be {, got LParen instead"),
+        ("struct Point { x, x }", "duplicate field x in struct Point"),
+    ];
+
+    for (input, expected) in errors {
+        let mut parser = Parser::new(Lexer::new(input.to_string()));
+        parser.parse_program();
+        assert_eq!(parser.errors.first().map(String::as_str), Some(expected));
+    }
+}
+
+#[test]
+fn test_enum_statement() {
+    let program = setup_test(String::from("enum Shape { Circle(r), Rect(w, h), Empty }"), Some(1));
+    assert!(matches!(&program.statements[0],
+        Statement::Enum(name, variants) if name == "Shape" && variants.len() == 3
+            && variants[1].0 == "Rect" && variants[1].1 == ["w", "h"]
+            && variants[2].0 == "Empty" && variants[2].1.is_empty()
+    ));
+
+    let tests = vec![
+        ("enum Shape { Circle(r), Rect(w, h) };", "enum Shape { Circle(r), Rect(w, h) }"),
+        ("enum Never {}", "enum Never {}"),
+        ("enum Option { Some(value), None, }", "enum Option { Some(value), None }"),
+    ];
+
+    for (input, expected) in tests {
+        let program = setup_test(String::from(input), Some(1));
+        assert_eq!(program.statements[0].to_string(), expected);
+    }
+
+    let errors = vec![
+        ("enum { A }", "Expected next token to be IDENT, got LBrace instead"),
+        ("enum Shape { A, A(x) }", "duplicate variant A in enum Shape"),
+        ("enum Shape { Rect(w, w) }", "duplicate field w in variant Rect"),
+    ];
+
+    for (input, expected) in errors {
+        let mut parser = Parser::new(Lexer::new(input.to_string()));
+        parser.parse_program();
+        assert_eq!(parser.errors.first().map(String::as_str), Some(expected));
+    }
+}
+
+#[test]
+fn test_import_statement() {
+    let program = setup_test(String::from("import \"lib/util.monkey\" as util;"), Some(1));
+    assert!(matches!(&program.statements[0],
+        Statement::Import(path, name) if path == "lib/util.monkey" && name == "util"
+    ));
+    assert_eq!(program.statements[0].to_string(), "import \"lib/util.monkey\" as util;");
+
+    let errors = vec![
+        ("import util;", "Expected next token to be STRING, got Ident(\"util\") instead"),
+        ("import \"util.monkey\";", "Expected next token to be as, got Semicolon instead"),
+        ("fn() { import \"util.monkey\" as u; }", "import is only allowed at the top level"),
+        ("m.1", "Expected next token to be IDENT, got Int(\"1\") instead"),
+    ];
+
+    for (input, expected) in errors {
+        let mut parser = Parser::new(Lexer::new(input.to_string()));
+        parser.parse_program();
+        assert_eq!(parser.errors.first().map(String::as_str), Some(expected));
+    }
+}
+
+#[test]
+fn test_try_expression() {
+    let tests = vec![
+        ("try { f() } catch (e) { 0 }", "try {f()} catch (e) {0}"),
+        ("try { f() } finally { g() }", "try {f()} finally {g()}"),
+        ("try { f() } catch (err) { throw err; } finally { g() }", "try {f()} catch (err) {throw err;} finally {g()}"),
+        ("throw \"boom\";", "throw boom;"),
+    ];
+
+    for (input, expected) in tests {
+        let program = setup_test(String::from(input), Some(1));
+        assert_eq!(program.statements[0].to_string(), expected);
+    }
+
+    let program = setup_test(String::from("let x = try { 1 } catch (e) { 2 };"), Some(1));
+    assert!(matches!(&program.statements[0],
+        Statement::Let(_, Expression::Try(_, Some((name, _)), None)) if name == "e"
+    ));
+
+    let errors = vec![
+        ("try { 1 }", "Expected next token to be catch or finally, got Eof instead"),
+        ("try { 1 } catch { 2 }", "Expected next token to be (, got LBrace instead"),
+        ("try { 1 } catch (1) { 2 }", "Expected next token to be IDENT, got Int(\"1\") instead"),
+    ];
+
+    for (input, expected) in errors {
+        let mut parser = Parser::new(Lexer::new(input.to_string()));
+        parser.parse_program();
+        assert_eq!(parser.errors.first().map(String::as_str), Some(expected));
+    }
+}
@@ -1,15 +1,17 @@
 use crate::eval::Evaluator;
 use crate::parser::Parser;
+use crate::module::ModuleLoader;
 use crate::vm::VM;
 use crate::{compiler::Compiler, lexer::Lexer};
 
 use anyhow::Result;
 use std::io::{stdout, Stdin, Write};
+use std::path::PathBuf;
 use whoami::username;
 
 const PROMPT: &str = ">> ";
 
-pub fn start(input: &mut Stdin, eval: bool) -> Result<()> {
+pub fn start(input: &mut Stdin, eval: bool, import_path: Vec<PathBuf>) -> Result<()> {
     let mut buffer = String::new();
 
     let user = username();
@@ -17,7 +19,9 @@ pub fn start(input: &mut Stdin, eval: bool) -> Result<()> {
     println!("Feel free to type in commands or 'exit' to exit the REPL");
 
     let mut evaluator = Evaluator::default();
+    evaluator.set_module_loader(ModuleLoader::new(import_path.clone(), None)?);
     let mut compiler = Compiler::new();
+    compiler.set_module_loader(ModuleLoader::new(import_path, None)?);
     let mut vm = VM::default();
 
     loop {
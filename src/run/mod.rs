@@ -1,15 +1,16 @@
 use crate::compiler::Compiler;
 use crate::eval::Evaluator;
 use crate::lexer::Lexer;
+use crate::module::ModuleLoader;
 use crate::parser::Parser;
 use crate::vm::VM;
 
 use anyhow::Result;
 use std::fs::File;
 use std::io::prelude::*;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-pub fn run_file(path: String, eval: bool) -> Result<()> {
+pub fn run_file(path: String, eval: bool, import_path: Vec<PathBuf>) -> Result<()> {
     // Open the file
     let path = Path::new(&path);
     let mut file = File::open(path)?;
@@ -33,6 +34,7 @@ pub fn run_file(path: String, eval: bool) -> Result<()> {
     if eval {
         // Use the evaluator instead of the compiler
         let mut evaluator = Evaluator::default();
+        evaluator.set_module_loader(ModuleLoader::new(import_path, Some(path))?);
         let evaluated = evaluator.eval(&program);
         if let Err(error) = evaluated {
             println!("Error occurred during evaluation:");
@@ -44,6 +46,7 @@ pub fn run_file(path: String, eval: bool) -> Result<()> {
 
     // Compile the file
     let mut compiler = Compiler::new();
+    compiler.set_module_loader(ModuleLoader::new(import_path, Some(path))?);
     let result = compiler.compile(&program);
     if let Err(error) = result {
         println!("Error occurred during compilation:");
@@ -63,3 +66,21 @@ pub fn run_file(path: String, eval: bool) -> Result<()> {
 
     Ok(())
 }
+
+pub fn dump_tokens(path: String) -> Result<()> {
+    // Open the file
+    let path = Path::new(&path);
+    let mut file = File::open(path)?;
+
+    // Read the file
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+
+    // Print each token with its position
+    let lexer = Lexer::new(contents);
+    for token in lexer {
+        println!("{}", token);
+    }
+
+    Ok(())
+}
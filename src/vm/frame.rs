@@ -1,21 +1,40 @@
-use crate::{code::Instructions, object::Closure};
+use std::rc::Rc;
+
+use super::heap::Handle;
+use crate::{code::Instructions, object::CompiledFunction};
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Frame {
-    pub cl: Closure,
+    pub cl: Handle,
+    pub func: Rc<CompiledFunction>,
     pub ip: usize,
     pub base_pointer: usize,
+    pub handlers: Vec<Handler>,
+    // Which parameters were left out of the call, for their defaults to fill
+    // in. Empty if none were.
+    pub missing: Vec<bool>,
+}
+
+// An installed try block handler: where its catch code starts, and the stack
+// height to unwind to before pushing the error
+#[derive(Debug, Clone, PartialEq)]
+pub struct Handler {
+    pub catch_ip: usize,
+    pub stack_len: usize,
 }
 
 impl Frame {
-    pub fn new(cl: Closure, base_pointer: usize) -> Self {
+    pub fn new(cl: Handle, func: Rc<CompiledFunction>, base_pointer: usize) -> Self {
         Self {
             cl,
+            func,
             ip: 0,
-            base_pointer: base_pointer,
+            base_pointer,
+            handlers: vec![],
+            missing: vec![],
         }
     }
     pub fn instructions(&self) -> &Instructions {
-        &self.cl.func.instructions
+        &self.func.instructions
     }
 }
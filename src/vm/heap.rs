@@ -0,0 +1,177 @@
+use std::{
+    collections::HashSet,
+    fmt::{self, Display, Formatter},
+    rc::Rc,
+};
+
+use anyhow::{anyhow, Result};
+
+use crate::object::{Closure, Object};
+
+// Collect once this many objects are live, unless configured otherwise
+pub const DEFAULT_GC_THRESHOLD: usize = 1024;
+
+// A reference to an object allocated on the VM heap
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Handle(u32);
+
+impl Display for Handle {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+// Counters describing the heap's activity since the VM was created
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct HeapStats {
+    pub allocations: usize,
+    pub collections: usize,
+    pub freed: usize,
+    pub live: usize,
+}
+
+// The VM heap holds closures, which are referred to by handle from the stack,
+// globals, frames and other closures. Objects are freed by a mark-and-sweep
+// collector rather than by reference counting, so cycles between closures are
+// reclaimed too. Slots of freed objects are reused by later allocations.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Heap {
+    objects: Vec<Option<Closure>>,
+    free: Vec<u32>,
+    marked: Vec<bool>,
+    threshold: usize,
+    next_collection: usize,
+    stats: HeapStats,
+}
+
+impl Default for Heap {
+    fn default() -> Self {
+        Self::new(DEFAULT_GC_THRESHOLD)
+    }
+}
+
+impl Heap {
+    // Create an empty heap that collects once `threshold` objects are live
+    pub fn new(threshold: usize) -> Self {
+        Self {
+            objects: vec![],
+            free: vec![],
+            marked: vec![],
+            threshold,
+            next_collection: threshold,
+            stats: HeapStats::default(),
+        }
+    }
+
+    // Allocate a closure, returning its handle
+    pub fn alloc(&mut self, closure: Closure) -> Handle {
+        self.stats.allocations += 1;
+        self.stats.live += 1;
+        match self.free.pop() {
+            Some(index) => {
+                self.objects[index as usize] = Some(closure);
+                Handle(index)
+            }
+            None => {
+                self.objects.push(Some(closure));
+                self.marked.push(false);
+                Handle(self.objects.len() as u32 - 1)
+            }
+        }
+    }
+
+    // Get the closure for a handle, failing if it has been freed
+    pub fn get(&self, handle: Handle) -> Result<&Closure> {
+        match self.objects.get(handle.0 as usize) {
+            Some(Some(closure)) => Ok(closure),
+            _ => Err(anyhow!("use of freed heap object {}", handle)),
+        }
+    }
+
+    // Check if enough objects are live that the next allocation should collect
+    pub fn should_collect(&self) -> bool {
+        self.stats.live >= self.next_collection
+    }
+
+    // Set the number of live objects that triggers a collection
+    pub fn set_threshold(&mut self, threshold: usize) {
+        self.threshold = threshold;
+        self.next_collection = threshold.max(self.stats.live);
+    }
+
+    // Get the heap's statistics
+    pub fn stats(&self) -> HeapStats {
+        self.stats
+    }
+
+    // Free every object that is not reachable from the given roots, returning
+    // the number of objects freed
+    pub fn collect<'a>(&mut self, roots: impl Iterator<Item = &'a Object>) -> usize {
+        // Mark the objects reachable from the roots, then from marked closures
+        let mut tracer = Tracer::default();
+        for root in roots {
+            tracer.trace(root);
+        }
+        while let Some(handle) = tracer.pending.pop() {
+            let index = handle.0 as usize;
+            if self.marked[index] {
+                continue;
+            }
+            self.marked[index] = true;
+            if let Some(closure) = &self.objects[index] {
+                closure.free.iter().for_each(|value| tracer.trace(value));
+            }
+        }
+
+        // Sweep everything left unmarked
+        let mut freed = 0;
+        for (index, object) in self.objects.iter_mut().enumerate() {
+            if self.marked[index] {
+                self.marked[index] = false;
+            } else if object.take().is_some() {
+                self.free.push(index as u32);
+                freed += 1;
+            }
+        }
+
+        self.stats.collections += 1;
+        self.stats.freed += freed;
+        self.stats.live -= freed;
+        self.next_collection = self.threshold.max(self.stats.live * 2);
+        freed
+    }
+}
+
+// Finds the handles held by values, looking inside arrays, tuples, hashes and modules. Shared
+// collections are only visited once, however many values refer to them.
+#[derive(Default)]
+struct Tracer {
+    pending: Vec<Handle>,
+    visited: HashSet<*const ()>,
+}
+
+impl Tracer {
+    // Queue the handles held by a value
+    fn trace(&mut self, value: &Object) {
+        match value {
+            Object::Closure(handle) => self.pending.push(*handle),
+            Object::Array(values) | Object::Tuple(values) if self.visited.insert(Rc::as_ptr(values) as *const ()) => {
+                values.iter().for_each(|value| self.trace(value));
+            }
+            Object::Hash(pairs) if self.visited.insert(Rc::as_ptr(pairs) as *const ()) => {
+                pairs.values().for_each(|value| self.trace(value));
+            }
+            Object::Module(module) if self.visited.insert(Rc::as_ptr(module) as *const ()) => {
+                module.members.values().for_each(|value| self.trace(value));
+            }
+            Object::Struct(instance) | Object::Variant(instance) if self.visited.insert(Rc::as_ptr(instance) as *const ()) => {
+                instance.fields.iter().for_each(|value| self.trace(value));
+            }
+            Object::Error(error) if self.visited.insert(Rc::as_ptr(error) as *const ()) => {
+                self.trace(&error.value);
+            }
+            Object::ReturnValue(value) => self.trace(value),
+            _ => {}
+        }
+    }
+}
@@ -1,23 +1,28 @@
 mod frame;
+pub mod heap;
 
-use std::collections::HashMap;
+use std::{cmp::Ordering, rc::Rc};
 
 use crate::{
     code::{read_u16, Opcode},
     compiler::Bytecode,
     object::{
-        builtins::{get_builtin, BUILTINS},
-        BuiltInFunction, Closure, CompiledFunction, HashKey, Object,
+        arguments::Signature,
+        builtins::{get_builtin_module_at, BUILTINS},
+        error,
+        ordered_map::OrderedMap, sequence_index, slice_bounds, BuiltInFunction, Closure, CompiledFunction, HashKey,
+        Module, Object, Shape,
     },
 };
 use anyhow::{anyhow, Result};
-use frame::Frame;
+use crate::raise;
+use frame::{Frame, Handler};
+use heap::{Handle, Heap, HeapStats};
 
 #[cfg(test)]
 mod tests;
 
 const STACK_SIZE: usize = 2048;
-const GLOBALS_SIZE: usize = 65536;
 const MAX_FRAMES: usize = 1024;
 const TRUE: Object = Object::Boolean(true);
 const FALSE: Object = Object::Boolean(false);
@@ -26,24 +31,32 @@ const NULL: Object = Object::Null;
 #[derive(Debug, PartialEq, Clone)]
 pub struct VM {
     pub constants: Vec<Object>,
+    // The stack grows and shrinks with pushes and pops (up to STACK_SIZE), so
+    // slots above the top never hold stale values that need dropping
     pub stack: Vec<Object>,
-    pub sp: usize,
     pub globals: Vec<Object>,
 
     frames: Vec<Frame>,
     frames_index: usize,
+    last_popped: Object,
+    heap: Heap,
+    // The struct field each OpMember last read, by its cache slot: the shape
+    // of the struct, the constant naming the field and the field's index
+    field_cache: Vec<Option<(Rc<Shape>, usize, usize)>>,
 }
 
 impl Default for VM {
     fn default() -> Self {
         VM {
             constants: vec![],
-            stack: vec![Object::Null; STACK_SIZE],
-            sp: 0,
-            globals: vec![Object::Null; GLOBALS_SIZE],
+            stack: Vec::with_capacity(STACK_SIZE),
+            globals: vec![],
 
             frames: vec![],
             frames_index: 1,
+            last_popped: Object::Null,
+            heap: Heap::default(),
+            field_cache: vec![],
         }
     }
 }
@@ -54,24 +67,30 @@ impl VM {
         let main_fn = CompiledFunction {
             instructions: bytecode.instructions,
             num_locals: 0,
-            num_parameters: 0,
+            parameters: vec![],
+            min_arity: 0,
+            max_arity: Some(0),
         };
-        let main_closure = Closure {
-            func: main_fn,
+        let main_fn = Rc::new(main_fn);
+        let mut heap = Heap::default();
+        let main_closure = heap.alloc(Closure {
+            func: main_fn.clone(),
             free: vec![],
-        };
-        let main_frame = Frame::new(main_closure, 0);
+        });
+        let main_frame = Frame::new(main_closure, main_fn, 0);
         let mut frames = Vec::with_capacity(MAX_FRAMES);
         frames.push(main_frame);
 
         VM {
             constants: bytecode.constants,
-            stack: vec![Object::Null; STACK_SIZE],
-            sp: 0,
-            globals: vec![Object::Null; GLOBALS_SIZE],
+            stack: Vec::with_capacity(STACK_SIZE),
+            globals: vec![],
 
-            frames: frames,
+            frames,
             frames_index: 1,
+            last_popped: Object::Null,
+            heap,
+            field_cache: vec![],
         }
     }
 
@@ -80,26 +99,30 @@ impl VM {
         let main_fn = CompiledFunction {
             instructions: bytecode.instructions,
             num_locals: 0,
-            num_parameters: 0,
+            parameters: vec![],
+            min_arity: 0,
+            max_arity: Some(0),
         };
-        let main_closure = Closure {
-            func: main_fn,
+        let main_fn = Rc::new(main_fn);
+        let main_closure = self.heap.alloc(Closure {
+            func: main_fn.clone(),
             free: vec![],
-        };
-        let main_frame = Frame::new(main_closure, 0);
+        });
+        let main_frame = Frame::new(main_closure, main_fn, 0);
 
         self.frames = vec![main_frame];
         self.frames_index = 1;
         self.constants = bytecode.constants;
-        self.stack = vec![Object::Null; STACK_SIZE];
-        self.sp = 0;
+        self.stack.clear();
+        self.last_popped = Object::Null;
+        self.field_cache.clear();
     }
 
     // Push an element onto the stack
+    #[inline]
     fn push(&mut self, obj: Object) -> Result<()> {
-        if self.sp < STACK_SIZE {
-            self.stack[self.sp] = obj;
-            self.sp += 1;
+        if self.stack.len() < STACK_SIZE {
+            self.stack.push(obj);
             Ok(())
         } else {
             Err(anyhow!("stack overflow"))
@@ -107,29 +130,45 @@ impl VM {
     }
 
     // Pop an element from the stack
+    #[inline]
     fn pop(&mut self) -> Result<Object> {
-        if self.sp != 0 {
-            self.sp -= 1;
-            Ok(self.stack[self.sp].clone())
-        } else {
-            Err(anyhow!("stack underflow"))
-        }
+        self.stack.pop().ok_or_else(|| anyhow!("stack underflow"))
     }
 
     // Get the last element popped from the stack
     pub fn last_popped_stack_elem(&self) -> Object {
-        return self.stack[self.sp].clone();
+        self.last_popped.clone()
+    }
+
+    // Free heap objects that are no longer reachable, returning how many were freed
+    pub fn collect_garbage(&mut self) -> usize {
+        let roots = self
+            .stack
+            .iter()
+            .chain(&self.globals)
+            .chain(&self.constants)
+            .chain([&self.last_popped]);
+        let frames = self.frames.iter().map(|frame| Object::Closure(frame.cl)).collect::<Vec<_>>();
+        self.heap.collect(roots.chain(&frames))
+    }
+
+    // Set the number of live heap objects that triggers a collection
+    pub fn set_gc_threshold(&mut self, threshold: usize) {
+        self.heap.set_threshold(threshold);
+    }
+
+    // Get statistics about heap allocations and collections
+    pub fn heap_stats(&self) -> HeapStats {
+        self.heap.stats()
     }
 
     // Get the top element of the stack
     pub fn stack_top(&self) -> Object {
-        if self.sp == 0 {
-            return Object::Null;
-        }
-        self.stack[self.sp - 1].clone()
+        self.stack.last().cloned().unwrap_or_default()
     }
 
     // Get the current frame
+    #[inline]
     pub fn current_frame(&mut self) -> &mut Frame {
         &mut self.frames[self.frames_index - 1]
     }
@@ -147,11 +186,38 @@ impl VM {
     // Pop a frame from the stack
     pub fn pop_frame(&mut self) -> Result<Frame> {
         self.frames_index -= 1;
-        self.frames.pop().ok_or(anyhow!("frame underflow"))
+        self.frames.pop().ok_or_else(|| anyhow!("frame underflow"))
     }
 
-    // Run the VM
+    // Run the VM. An error unwinds to the innermost try block's handler, in the
+    // current frame or a calling one; without one, it stops the VM.
     pub fn run(&mut self) -> Result<()> {
+        loop {
+            match self.execute() {
+                Ok(()) => return Ok(()),
+                Err(err) => self.unwind(err)?,
+            }
+        }
+    }
+
+    // Drop the frames above the innermost handler and the stack above its
+    // height, then continue at its catch code with the error on the stack
+    fn unwind(&mut self, err: anyhow::Error) -> Result<()> {
+        let Some(index) = self.frames.iter().rposition(|frame| !frame.handlers.is_empty()) else {
+            return Err(err);
+        };
+        self.frames.truncate(index + 1);
+        self.frames_index = index + 1;
+
+        let frame = self.current_frame();
+        let handler = frame.handlers.pop().ok_or_else(|| anyhow!("handler underflow"))?;
+        frame.ip = handler.catch_ip;
+        self.stack.truncate(handler.stack_len);
+        self.push(error::catch(err))
+    }
+
+    // Execute instructions until the program finishes or an error is raised
+    fn execute(&mut self) -> Result<()> {
         while self.current_frame().ip < self.current_frame().instructions().len() {
             let mut ip = self.current_frame().ip;
             let ins = self.current_frame().instructions();
@@ -165,7 +231,7 @@ impl VM {
                     self.push(constant)?;
                 }
                 Opcode::OpPop => {
-                    self.pop()?;
+                    self.last_popped = self.pop()?;
                 }
                 Opcode::OpAdd | Opcode::OpSub | Opcode::OpMul | Opcode::OpDiv => {
                     self.exec_binary_op(op)?;
@@ -203,29 +269,164 @@ impl VM {
                 Opcode::OpSetGlobal => {
                     let global_index = read_u16(&ins[ip + 1..ip + 3]) as usize;
                     ip += 2;
+                    // Globals grow on demand rather than being pre-allocated
+                    if global_index >= self.globals.len() {
+                        self.globals.resize(global_index + 1, Object::Null);
+                    }
                     self.globals[global_index] = self.pop()?;
 
-                    self.stack[self.sp] = Object::Null;
+                    self.last_popped = Object::Null;
                 }
                 Opcode::OpGetGlobal => {
                     let global_index = read_u16(&ins[ip + 1..ip + 3]) as usize;
                     ip += 2;
-                    self.push(self.globals[global_index].clone())?;
+                    let global = self.globals.get(global_index).cloned().unwrap_or_default();
+                    self.push(global)?;
                 }
                 Opcode::OpArray => {
                     let num_elements = read_u16(&ins[ip + 1..ip + 3]) as usize;
                     ip += 2;
-                    let array = self.build_array(self.sp - num_elements, self.sp)?;
-                    self.sp -= num_elements;
+                    let array = self.build_array(num_elements)?;
                     self.push(array)?;
                 }
                 Opcode::OpHash => {
                     let num_elements = read_u16(&ins[ip + 1..ip + 3]) as usize;
                     ip += 2;
-                    let hash = self.build_hash(self.sp - num_elements, self.sp)?;
-                    self.sp -= num_elements;
+                    let hash = self.build_hash(num_elements)?;
                     self.push(hash)?;
                 }
+                Opcode::OpSet => {
+                    let num_elements = read_u16(&ins[ip + 1..ip + 3]) as usize;
+                    ip += 2;
+                    let set = self.build_set(num_elements)?;
+                    self.push(set)?;
+                }
+                Opcode::OpIn => {
+                    let collection = self.pop()?;
+                    let value = self.pop()?;
+                    self.push(Object::Boolean(collection.contains(&value)?))?;
+                }
+                Opcode::OpTuple => {
+                    let num_elements = read_u16(&ins[ip + 1..ip + 3]) as usize;
+                    ip += 2;
+                    let elements = self.stack.split_off(self.stack.len() - num_elements);
+                    self.push(Object::tuple(elements))?;
+                }
+                Opcode::OpDestructure => {
+                    let num_names = read_u16(&ins[ip + 1..ip + 3]) as usize;
+                    ip += 2;
+                    let values = self.pop()?.destructure_sequence(num_names)?;
+                    self.push_destructured(values)?;
+                }
+                Opcode::OpDestructureHash => {
+                    let num_names = read_u16(&ins[ip + 1..ip + 3]) as usize;
+                    ip += 2;
+                    let keys = self.stack.split_off(self.stack.len() - num_names);
+                    let keys = keys
+                        .iter()
+                        .map(|key| match key {
+                            Object::String(key) => Ok(key.as_str()),
+                            _ => Err(anyhow!("destructuring key must be a string")),
+                        })
+                        .collect::<Result<Vec<_>>>()?;
+                    let values = self.pop()?.destructure_hash(&keys)?;
+                    self.push_destructured(values)?;
+                }
+                Opcode::OpMatchArray | Opcode::OpMatchTuple => {
+                    let len = read_u16(&ins[ip + 1..ip + 3]) as usize;
+                    let rest = ins[ip + 3] != 0;
+                    ip += 3;
+                    let matched = match (op, self.pop()?) {
+                        (Opcode::OpMatchArray, Object::Array(values))
+                        | (Opcode::OpMatchTuple, Object::Tuple(values)) => {
+                            values.len() == len || rest && values.len() > len
+                        }
+                        _ => false,
+                    };
+                    self.push(Object::Boolean(matched))?;
+                }
+                Opcode::OpMatchConstructor => {
+                    let len = read_u16(&ins[ip + 1..ip + 3]) as usize;
+                    ip += 2;
+                    let constructor = self.pop()?;
+                    let value = self.pop()?;
+                    let matched = matches!(
+                        value.constructed_by(&constructor)?,
+                        Some(fields) if fields.len() == len
+                    );
+                    self.push(Object::Boolean(matched))?;
+                }
+                Opcode::OpField => {
+                    let index = read_u16(&ins[ip + 1..ip + 3]) as usize;
+                    ip += 2;
+                    let field = match self.pop()? {
+                        Object::Struct(instance) | Object::Variant(instance) => {
+                            instance.fields[index].clone()
+                        }
+                        value => return Err(anyhow!("fields not supported: {}", value.type_name())),
+                    };
+                    self.push(field)?;
+                }
+                Opcode::OpDefault => {
+                    let local_index = ins[ip + 1] as usize;
+                    let pos = read_u16(&ins[ip + 2..ip + 4]) as usize;
+                    ip += 3;
+                    if !self.current_frame().missing.get(local_index).is_some_and(|&missing| missing) {
+                        ip = pos - 1;
+                    }
+                }
+                Opcode::OpMatchHash => {
+                    let matched = matches!(self.pop()?, Object::Hash(_));
+                    self.push(Object::Boolean(matched))?;
+                }
+                Opcode::OpMatchFail => {
+                    let value = self.pop()?;
+                    return Err(raise!(Match, "no match arm for value: {}", value));
+                }
+                Opcode::OpTry => {
+                    let catch_ip = read_u16(&ins[ip + 1..ip + 3]) as usize;
+                    ip += 2;
+                    let stack_len = self.stack.len();
+                    self.current_frame().handlers.push(Handler { catch_ip, stack_len });
+                }
+                Opcode::OpEndTry => {
+                    self.current_frame().handlers.pop();
+                }
+                Opcode::OpThrow => {
+                    let value = self.pop()?;
+                    return Err(error::throw(value));
+                }
+                Opcode::OpUndefined => {
+                    let name_index = read_u16(&ins[ip + 1..ip + 3]) as usize;
+                    let name = self.member_name(name_index)?;
+                    return Err(raise!(Name, "identifier not found: {}", name));
+                }
+                Opcode::OpJumpNotError => {
+                    let pos = read_u16(&ins[ip + 1..ip + 3]) as usize;
+                    ip += 2;
+                    if !matches!(self.stack.last(), Some(Object::Error(_))) {
+                        ip = pos - 1;
+                    }
+                }
+                Opcode::OpModule => {
+                    let num_members = read_u16(&ins[ip + 1..ip + 3]) as usize;
+                    ip += 2;
+                    let module = self.build_module(num_members)?;
+                    self.push(module)?;
+                }
+                Opcode::OpMember => {
+                    let name_index = read_u16(&ins[ip + 1..ip + 3]) as usize;
+                    let cache_index = read_u16(&ins[ip + 3..ip + 5]) as usize;
+                    ip += 4;
+                    let member = match self.pop()? {
+                        Object::Struct(instance) | Object::Variant(instance) => {
+                            let index = self.field_index(name_index, cache_index, &instance.shape)?;
+                            instance.fields[index].clone()
+                        }
+                        object => object.member(&self.member_name(name_index)?)?,
+                    };
+                    self.push(member)?;
+                }
                 Opcode::OpIndex => {
                     let index = self.pop()?;
                     let left = self.pop()?;
@@ -241,23 +442,60 @@ impl VM {
                     let num_args = ins[ip + 1] as usize;
                     self.current_frame().ip += 1;
 
-                    self.exec_call(num_args)?;
+                    self.exec_call(num_args, None)?;
+
+                    continue;
+                }
+                Opcode::OpCallKeywords => {
+                    let num_args = ins[ip + 1] as usize;
+                    let names_index = read_u16(&ins[ip + 2..ip + 4]) as usize;
+                    self.current_frame().ip += 3;
+
+                    let names = self.keyword_names(names_index)?;
+                    self.exec_call(num_args, Some(names))?;
+
+                    continue;
+                }
+                Opcode::OpCallMethod => {
+                    let name_index = read_u16(&ins[ip + 1..ip + 3]) as usize;
+                    let num_args = ins[ip + 3] as usize;
+                    self.current_frame().ip += 3;
+
+                    self.exec_method_call(name_index, num_args, None)?;
+
+                    continue;
+                }
+                Opcode::OpCallMethodKeywords => {
+                    let name_index = read_u16(&ins[ip + 1..ip + 3]) as usize;
+                    let num_args = ins[ip + 3] as usize;
+                    let names_index = read_u16(&ins[ip + 4..ip + 6]) as usize;
+                    self.current_frame().ip += 5;
+
+                    let names = self.keyword_names(names_index)?;
+                    self.exec_method_call(name_index, num_args, Some(names))?;
 
                     continue;
                 }
                 Opcode::OpReturnValue => {
                     let return_value = self.pop()?;
 
+                    // Returning from the main program ends it with the value
+                    if self.frames_index == 1 {
+                        self.stack.clear();
+                        self.last_popped = return_value;
+                        return Ok(());
+                    }
+
                     let frame = self.pop_frame()?;
                     ip = self.current_frame().ip;
-                    self.sp = frame.base_pointer - 1;
+                    self.stack.truncate(frame.base_pointer - 1);
 
                     self.push(return_value)?;
                 }
                 Opcode::OpReturn => {
                     let frame = self.pop_frame()?;
                     ip = self.current_frame().ip;
-                    self.sp = frame.base_pointer - 1;
+                    self.stack.truncate(frame.base_pointer - 1);
 
                     self.push(NULL.clone())?;
                 }
@@ -268,8 +506,8 @@ impl VM {
                     let frame_base_pointer = self.current_frame().base_pointer;
 
                     self.stack[frame_base_pointer + local_index] = self.pop()?;
-                    
-                    self.stack[self.sp] = Object::Null;
+
+                    self.last_popped = Object::Null;
                 }
                 Opcode::OpGetLocal => {
                     let local_index = ins[ip + 1] as usize;
@@ -283,9 +521,18 @@ impl VM {
                     let builtin_index = ins[ip + 1] as usize;
                     ip += 1;
 
-                    let builtin = get_builtin(&BUILTINS[builtin_index].to_string())
-                        .ok_or(anyhow!("builtin not found: {}", builtin_index))?;
-                    self.push(Object::BuiltInFunction(builtin))?;
+                    let (_, builtin) = BUILTINS
+                        .get(builtin_index)
+                        .ok_or_else(|| anyhow!("builtin not found: {}", builtin_index))?;
+                    self.push(Object::BuiltInFunction(*builtin))?;
+                }
+                Opcode::OpGetBuiltinModule => {
+                    let module_index = ins[ip + 1] as usize;
+                    ip += 1;
+
+                    let module = get_builtin_module_at(module_index)
+                        .ok_or_else(|| anyhow!("builtin module not found: {}", module_index))?;
+                    self.push(module)?;
                 }
                 Opcode::OpClosure => {
                     let const_index = read_u16(&ins[ip + 1..ip + 3]) as usize;
@@ -298,11 +545,12 @@ impl VM {
                     let free_index = ins[ip + 1] as usize;
                     ip += 1;
 
-                    let current_closure = self.current_frame().cl.clone();
-                    self.push(current_closure.free[free_index].clone())?;
+                    let current_closure = self.current_frame().cl;
+                    let free = self.heap.get(current_closure)?.free[free_index].clone();
+                    self.push(free)?;
                 }
                 Opcode::OpCurrentClosure => {
-                    let current_closure = self.current_frame().cl.clone();
+                    let current_closure = self.current_frame().cl;
                     self.push(Object::Closure(current_closure))?;
                 }
             }
@@ -320,9 +568,10 @@ impl VM {
                 self.exec_binary_int_op(op, left, right)
             }
             (Object::String(left), Object::String(right)) => {
-                self.exec_binary_string_op(op, left, right)
+                self.exec_binary_string_op(op, &left, &right)
             }
-            (left, right) => Err(anyhow!(
+            (left, right) => Err(raise!(
+                Type,
                 "unsupported types for binary operation: {} {}",
                 left.type_name(),
                 right.type_name()
@@ -339,48 +588,29 @@ impl VM {
             Opcode::OpDiv => left / right,
             _ => return Err(anyhow!("unknown integer operator: {}", op)),
         };
-        return self.push(Object::Integer(result));
+        self.push(Object::Integer(result))
     }
 
     // Execute a binary operator on two strings
-    fn exec_binary_string_op(&mut self, op: Opcode, left: String, right: String) -> Result<()> {
+    fn exec_binary_string_op(&mut self, op: Opcode, left: &str, right: &str) -> Result<()> {
         let result = match op {
-            Opcode::OpAdd => left + &right,
+            Opcode::OpAdd => left.to_owned() + right,
             _ => return Err(anyhow!("unknown string operator: {}", op)),
         };
-        return self.push(Object::String(result));
+        self.push(result.into())
     }
 
     // Execute a comparison operator
     pub fn exec_comparison(&mut self, op: Opcode) -> Result<()> {
         let right = self.pop()?;
         let left = self.pop()?;
-        match (left, right) {
-            (Object::Integer(left), Object::Integer(right)) => {
-                self.exec_comparison_int_op(op, left, right)
-            }
-            (Object::Boolean(left), Object::Boolean(right)) => match op {
-                Opcode::OpEqual => self.push(Object::Boolean(left == right)),
-                Opcode::OpNotEqual => self.push(Object::Boolean(left != right)),
-                _ => Err(anyhow!("unknown boolean operator: {}", op)),
-            },
-            (left, right) => Err(anyhow!(
-                "unsupported types for comparison: {} {}",
-                left.type_name(),
-                right.type_name()
-            )),
-        }
-    }
-
-    // Execute a comparison operator on two integers
-    fn exec_comparison_int_op(&mut self, op: Opcode, left: i64, right: i64) -> Result<()> {
         let result = match op {
             Opcode::OpEqual => left == right,
             Opcode::OpNotEqual => left != right,
-            Opcode::OpGreaterThan => left > right,
-            _ => return Err(anyhow!("unknown integer operator: {}", op)),
+            Opcode::OpGreaterThan => left.compare(&right)? == Ordering::Greater,
+            _ => return Err(anyhow!("unknown comparison operator: {}", op)),
         };
-        return self.push(Object::Boolean(result));
+        self.push(Object::Boolean(result))
     }
 
     // Execute the prefix bang operator
@@ -398,23 +628,41 @@ impl VM {
         let operand = self.pop()?;
         match operand {
             Object::Integer(value) => self.push(Object::Integer(-value)),
-            _ => Err(anyhow!("unsupported type for negation: {}", operand.type_name())),
+            _ => Err(raise!(Type, "unsupported type for negation: {}", operand.type_name())),
         }
     }
 
-    // Build an array from the stack
-    fn build_array(&mut self, start_index: usize, end_index: usize) -> Result<Object> {
-        let mut elements = vec![];
-        self.stack[start_index..end_index].iter().for_each(|elem| {
-            elements.push(elem.clone());
-        });
-        return Ok(Object::Array(elements));
+    // Build an array from the top elements of the stack
+    fn build_array(&mut self, num_elements: usize) -> Result<Object> {
+        let elements = self.stack.split_off(self.stack.len() - num_elements);
+        Ok(Object::array(elements))
     }
 
-    // Build a hash from the stack
-    fn build_hash(&mut self, start_index: usize, end_index: usize) -> Result<Object> {
-        let mut pairs = HashMap::new();
-        let result = self.stack[start_index..end_index]
+    // Build a hash from the top elements of the stack
+    // Build a module from its name and its members' names and values
+    fn build_module(&mut self, num_members: usize) -> Result<Object> {
+        let elements = self.stack.split_off(self.stack.len() - num_members * 2);
+        let name = match self.pop()? {
+            Object::String(name) => name.to_string(),
+            _ => return Err(anyhow!("module name must be a string")),
+        };
+        let members = elements
+            .chunks_exact(2)
+            .map(|chunk| match &chunk[0] {
+                Object::String(name) => Ok((name.to_string(), chunk[1].clone())),
+                _ => Err(anyhow!("module member name must be a string")),
+            })
+            .collect::<Result<OrderedMap<String, Object>>>()?;
+        Ok(Object::Module(Rc::new(Module {
+            name,
+            members,
+        })))
+    }
+
+    fn build_hash(&mut self, num_elements: usize) -> Result<Object> {
+        let mut pairs = OrderedMap::new();
+        let elements = self.stack.split_off(self.stack.len() - num_elements);
+        let result = elements
             .chunks_exact(2)
             .try_for_each(|chunk| {
                 let key = chunk[0].clone();
@@ -423,28 +671,49 @@ impl VM {
                 let key_type = key.type_name();
                 let key: HashKey = match key.into() {
                     Some(key) => key,
-                    None => return Err(anyhow!("unusable as hash key: {}", key_type)),
+                    None => return Err(raise!(Type, "unusable as hash key: {}", key_type)),
                 };
                 pairs.insert(key, value);
                 Ok(())
             });
         match result {
             Err(err) => Err(err),
-            Ok(_) => Ok(Object::Hash(pairs)),
+            Ok(_) => Ok(Object::hash(pairs)),
         }
     }
 
+    // Build a set from the top elements of the stack
+    fn build_set(&mut self, num_elements: usize) -> Result<Object> {
+        let elements = self.stack.split_off(self.stack.len() - num_elements);
+        let elements = elements
+            .iter()
+            .map(|element| Ok((element.to_set_element()?, ())))
+            .collect::<Result<_>>()?;
+        Ok(Object::set(elements))
+    }
+
+    // Push destructured values in reverse, so the first is on top for the
+    // stores that follow
+    fn push_destructured(&mut self, values: Vec<Object>) -> Result<()> {
+        for value in values.into_iter().rev() {
+            self.push(value)?;
+        }
+        Ok(())
+    }
+
     // Execute the index operator
     fn exec_index_op(&mut self, left: Object, index: Object) -> Result<()> {
         match (left, index) {
-            (Object::Array(elements), Object::Integer(index)) => {
-                self.exec_array_index(elements, index)
+            (Object::Array(elements) | Object::Tuple(elements), Object::Integer(index)) => {
+                self.exec_array_index(&elements, index)
             }
-            (Object::Hash(pairs), index) => self.exec_hash_index(pairs, index),
+            (Object::Hash(pairs), index) => self.exec_hash_index(&pairs, index),
+            (Object::Error(error), Object::String(field)) => self.push(error.field(&field)),
             (Object::String(string), Object::Integer(index)) => {
-                self.exec_string_index(string, index)
+                self.exec_string_index(&string, index)
             }
-            (left, index) => Err(anyhow!(
+            (left, index) => Err(raise!(
+                Type,
                 "index operator not supported: {}[{}]",
                 left.type_name(),
                 index.type_name()
@@ -453,25 +722,19 @@ impl VM {
     }
 
     // Execute the index operator on an array
-    fn exec_array_index(&mut self, elements: Vec<Object>, index: i64) -> Result<()> {
-        let index = if index < 0 {
-            (elements.len() as i64) + index
-        } else {
-            index
-        };
-
-        match elements.get(index as usize) {
-            Some(elem) => self.push(elem.clone()),
+    fn exec_array_index(&mut self, elements: &[Object], index: i64) -> Result<()> {
+        match sequence_index(elements.len(), index) {
+            Some(index) => self.push(elements[index].clone()),
             None => self.push(NULL.clone()),
         }
     }
 
     // Execute the index operator on a hash
-    fn exec_hash_index(&mut self, pairs: HashMap<HashKey, Object>, index: Object) -> Result<()> {
+    fn exec_hash_index(&mut self, pairs: &OrderedMap<HashKey, Object>, index: Object) -> Result<()> {
         let index_type = index.type_name();
         let key = match index.into() {
             Some(key) => key,
-            None => return Err(anyhow!("unusable as hash key: {}", index_type)),
+            None => return Err(raise!(Type, "unusable as hash key: {}", index_type)),
         };
         match pairs.get(&key) {
             Some(value) => self.push(value.clone()),
@@ -479,16 +742,10 @@ impl VM {
         }
     }
 
-    // Execute the index operator on a string
-    fn exec_string_index(&mut self, string: String, index: i64) -> Result<()> {
-        let index = if index < 0 {
-            (string.len() as i64) + index
-        } else {
-            index
-        };
-
-        match string.chars().nth(index as usize) {
-            Some(ch) => self.push(Object::String(ch.to_string())),
+    // Execute the index operator on a string (by code point)
+    fn exec_string_index(&mut self, string: &str, index: i64) -> Result<()> {
+        match sequence_index(string.chars().count(), index) {
+            Some(index) => self.push(string.chars().skip(index).take(1).collect::<String>().into()),
             None => self.push(NULL.clone()),
         }
     }
@@ -497,12 +754,18 @@ impl VM {
     fn exec_slice_index_op(&mut self, left: Object, start: Object, stop: Object) -> Result<()> {
         match (left, start, stop) {
             (Object::Array(elements), start, stop) => {
-                self.exec_array_slice_index(elements, start, stop)
+                let (start, stop) = self.slice_bounds(elements.len(), start, stop)?;
+                self.push(Object::array(elements[start..stop].to_vec()))
+            }
+            (Object::Tuple(elements), start, stop) => {
+                let (start, stop) = self.slice_bounds(elements.len(), start, stop)?;
+                self.push(Object::tuple(elements[start..stop].to_vec()))
             }
             (Object::String(string), start, stop) => {
-                self.exec_string_slice_index(string, start, stop)
+                self.exec_string_slice_index(&string, start, stop)
             }
-            (left, start, stop) => Err(anyhow!(
+            (left, start, stop) => Err(raise!(
+                Type,
                 "slice index operator not supported: {}[{}:{}]",
                 left.type_name(),
                 start.type_name(),
@@ -511,93 +774,119 @@ impl VM {
         }
     }
 
-    // Execute the slice index operator on an array
-    fn exec_array_slice_index(
+    // Execute the slice index operator on a string (by code point)
+    fn exec_string_slice_index(
         &mut self,
-        elements: Vec<Object>,
+        string: &str,
         start: Object,
         stop: Object,
     ) -> Result<()> {
+        let (start, stop) = self.slice_bounds(string.chars().count(), start, stop)?;
+        let slice = string.chars().skip(start).take(stop - start).collect::<String>();
+        self.push(slice.into())
+    }
+
+    // Resolve slice operands into bounds for a sequence of the given length
+    fn slice_bounds(&self, len: usize, start: Object, stop: Object) -> Result<(usize, usize)> {
         let start = match start {
-            Object::Integer(start) => start,
-            Object::Null => 0,
-            _ => return Err(anyhow!("slice start must be an integer")),
+            Object::Integer(start) => Some(start),
+            Object::Null => None,
+            _ => return Err(raise!(Type, "slice start must be an integer")),
         };
         let stop = match stop {
-            Object::Integer(stop) => stop,
-            Object::Null => elements.len() as i64,
-            _ => return Err(anyhow!("slice stop must be an integer")),
+            Object::Integer(stop) => Some(stop),
+            Object::Null => None,
+            _ => return Err(raise!(Type, "slice stop must be an integer")),
         };
+        Ok(slice_bounds(len, start, stop))
+    }
 
-        let start = if start < 0 {
-            (elements.len() as i64) + start
-        } else if start > (elements.len() as i64) {
-            elements.len() as i64
-        } else {
-            start
-        };
-        let stop = if stop < 0 {
-            (elements.len() as i64) + stop
-        } else if stop > (elements.len() as i64) {
-            elements.len() as i64
-        } else {
-            stop
-        };
+    // Get the name read by an OpMember
+    fn member_name(&self, name_index: usize) -> Result<Rc<String>> {
+        match &self.constants[name_index] {
+            Object::String(name) => Ok(name.clone()),
+            _ => Err(anyhow!("member name must be a string")),
+        }
+    }
 
-        match elements.get(start as usize..stop as usize) {
-            Some(slice) => self.push(Object::Array(slice.to_vec()))?,
-            None => self.push(Object::Array(vec![]))?,
-        };
-        Ok(())
+    // Get the index of the struct field read by an OpMember. The field is
+    // looked up by name only when the instruction reads a struct of a shape
+    // other than the one it last read. A slot can be shared by more than one
+    // instruction (slot numbers wrap, and the REPL numbers each line from 0),
+    // so a hit also needs the same field name.
+    fn field_index(&mut self, name_index: usize, cache_index: usize, shape: &Rc<Shape>) -> Result<usize> {
+        if let Some(Some((cached, cached_name, index))) = self.field_cache.get(cache_index) {
+            if Rc::ptr_eq(cached, shape) && *cached_name == name_index {
+                return Ok(*index);
+            }
+        }
+
+        let index = shape.find_field(&self.member_name(name_index)?)?;
+        if cache_index >= self.field_cache.len() {
+            self.field_cache.resize(cache_index + 1, None);
+        }
+        self.field_cache[cache_index] = Some((shape.clone(), name_index, index));
+        Ok(index)
     }
 
-    // Execute the slice index operator on a string
-    fn exec_string_slice_index(
+    // Get the names of a call's keyword arguments, which are the last of its
+    // arguments
+    fn keyword_names(&self, names_index: usize) -> Result<Rc<Vec<Object>>> {
+        match &self.constants[names_index] {
+            Object::Array(names) => Ok(names.clone()),
+            _ => Err(anyhow!("keyword names must be an array")),
+        }
+    }
+
+    // Execute a method call. The stack holds the function in scope (or null),
+    // the receiver and the arguments.
+    fn exec_method_call(
         &mut self,
-        string: String,
-        start: Object,
-        stop: Object,
+        name_index: usize,
+        num_args: usize,
+        keywords: Option<Rc<Vec<Object>>>,
     ) -> Result<()> {
-        let start = match start {
-            Object::Integer(start) => start,
-            Object::Null => 0,
-            _ => return Err(anyhow!("slice start must be an integer")),
-        };
-        let stop = match stop {
-            Object::Integer(stop) => stop,
-            Object::Null => string.len() as i64,
-            _ => return Err(anyhow!("slice stop must be an integer")),
+        let name = match &self.constants[name_index] {
+            Object::String(name) => name.clone(),
+            _ => return Err(anyhow!("method name must be a string")),
         };
-
-        let start = if start < 0 {
-            (string.len() as i64) + start
-        } else if start > (string.len() as i64) {
-            string.len() as i64
+        let receiver = self.stack.len() - 1 - num_args;
+        let in_scope = std::mem::take(&mut self.stack[receiver - 1]);
+        let (function, with_receiver) = self.stack[receiver].method(&name, in_scope)?;
+        self.stack[receiver - 1] = function;
+        if with_receiver {
+            self.exec_call(num_args + 1, keywords)
         } else {
-            start
-        };
-        let stop = if stop < 0 {
-            (string.len() as i64) + stop
-        } else if stop > (string.len() as i64) {
-            string.len() as i64
-        } else {
-            stop
-        };
-
-        match string.get(start as usize..stop as usize) {
-            Some(slice) => self.push(Object::String(slice.to_string()))?,
-            None => self.push(Object::String("".to_string()))?,
-        };
-        Ok(())
+            self.stack.remove(receiver);
+            self.exec_call(num_args, keywords)
+        }
     }
 
-    // Execute a function call
-    fn exec_call(&mut self, num_args: usize) -> Result<()> {
-        let callee = self.stack[self.sp - 1 - num_args].clone();
+    // Execute a function call, with the names of its keyword arguments if it
+    // has any
+    fn exec_call(&mut self, num_args: usize, keywords: Option<Rc<Vec<Object>>>) -> Result<()> {
+        let callee = self.stack[self.stack.len() - 1 - num_args].clone();
         match callee {
-            Object::Closure(cl) => self.call_closure(cl, num_args),
-            Object::BuiltInFunction(builtin_fn) => self.call_builtin(builtin_fn, num_args),
-            _ => Err(anyhow!("calling non-closure and non-builtin")),
+            Object::Closure(cl) if keywords.is_none() => self.call_closure(cl, num_args, vec![]),
+            Object::Closure(cl) => {
+                let keywords = self.pop_keywords(keywords)?;
+                self.call_closure(cl, num_args - keywords.len(), keywords)
+            }
+            Object::BuiltInFunction(builtin_fn) if keywords.is_none() => {
+                self.call_builtin(builtin_fn, num_args)
+            }
+            Object::BuiltInFunction(_) => {
+                Err(raise!(Argument, "keyword arguments not supported by builtin functions"))
+            }
+            Object::StructType(shape) => {
+                let keywords = self.pop_keywords(keywords)?;
+                let fields = self.stack.split_off(self.stack.len() - (num_args - keywords.len()));
+                self.current_frame().ip += 1;
+                let value = shape.construct(fields, keywords)?;
+                self.pop()?;
+                self.push(value)
+            }
+            _ => Err(raise!(Type, "calling non-closure and non-builtin")),
         }
     }
 
@@ -606,47 +895,84 @@ impl VM {
         let constant = self.constants[const_index].clone();
 
         if let Object::CompiledFunction(func) = constant {
-            let mut free = vec![];
-            for i in 0..num_free {
-                free.push(self.stack[self.sp - num_free + i].clone());
+            // Collect before the free variables leave the stack, so they are still roots
+            if self.heap.should_collect() {
+                self.collect_garbage();
             }
-            self.sp -= num_free;
+            let free = self.stack.split_off(self.stack.len() - num_free);
 
-            let cl = Closure { func, free };
+            let cl = self.heap.alloc(Closure { func, free });
             self.push(Object::Closure(cl))?;
 
             return Ok(());
         }
 
-        return Err(anyhow!("not a function: {:?}", constant));
+        Err(raise!(Type, "not a function: {:?}", constant))
     }
 
-    // Call a closure
-    fn call_closure(&mut self, cl: Closure, num_args: usize) -> Result<()> {
-        let num_locals = cl.func.num_locals;
-        let num_params = cl.func.num_parameters;
+    // Pop the keyword arguments of a call off the stack, paired with their names
+    fn pop_keywords(&mut self, names: Option<Rc<Vec<Object>>>) -> Result<Vec<(Rc<String>, Object)>> {
+        let Some(names) = names else {
+            return Ok(vec![]);
+        };
+        let values = self.stack.split_off(self.stack.len() - names.len());
+        names
+            .iter()
+            .zip(values)
+            .map(|(name, value)| match name {
+                Object::String(name) => Ok((name.clone(), value)),
+                _ => Err(anyhow!("keyword name must be a string")),
+            })
+            .collect()
+    }
 
-        if num_args != num_params {
-            return Err(anyhow!(
-                "wrong number of arguments: want={}, got={}",
-                num_params,
-                num_args
-            ));
+    // Call a closure. Unless the positional arguments fill exactly the
+    // parameters, the arguments are bound to the parameters first, leaving
+    // any that were left out for the function's defaults.
+    fn call_closure(&mut self, cl: Handle, num_args: usize, keywords: Vec<(Rc<String>, Object)>) -> Result<()> {
+        let func = self.heap.get(cl)?.func.clone();
+        let num_locals = func.num_locals;
+        let base_pointer = self.stack.len() - num_args;
+
+        let mut missing = vec![];
+        if !keywords.is_empty() || func.max_arity != Some(num_args) {
+            let signature = Signature {
+                name: None,
+                parameters: &func.parameters,
+                min_arity: func.min_arity,
+                max_arity: func.max_arity,
+            };
+            let args = self.stack.split_off(base_pointer);
+            let (bound, rest) = signature.bind(args, keywords)?;
+            if bound.iter().any(Option::is_none) {
+                missing = bound.iter().map(Option::is_none).collect();
+            }
+            self.stack.extend(bound.into_iter().map(Option::unwrap_or_default));
+            if func.max_arity.is_none() {
+                self.stack.push(Object::array(rest));
+            }
         }
 
-        let frame = Frame::new(cl, self.sp - num_args);
-        self.sp = frame.base_pointer + num_locals;
+        let mut frame = Frame::new(cl, func, base_pointer);
+        frame.missing = missing;
+        if frame.base_pointer + num_locals > STACK_SIZE {
+            return Err(anyhow!("stack overflow"));
+        }
+        // Reserve slots for the locals above the arguments
+        self.stack.resize(frame.base_pointer + num_locals, Object::Null);
         self.push_frame(frame)?;
         Ok(())
     }
 
     // Call a built-in function
     fn call_builtin(&mut self, builtin: BuiltInFunction, num_args: usize) -> Result<()> {
-        let args = self.stack[self.sp - num_args..self.sp].to_vec();
+        // Move the arguments off the stack so that unshared collections can be
+        // updated in place by the builtin
+        let args = self.stack.split_off(self.stack.len() - num_args);
         self.current_frame().ip += 1;
 
         let result = builtin(args)?;
-        self.sp = self.sp - num_args - 1;
+        self.pop()?;
         self.push(result)?;
         Ok(())
     }
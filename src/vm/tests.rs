@@ -1,9 +1,9 @@
-use std::collections::HashMap;
-
 use crate::code::instructions_string;
 use crate::compiler::Compiler;
+use crate::eval::Evaluator;
 use crate::lexer::Lexer;
-use crate::object::HashKey;
+use crate::module::ModuleLoader;
+use crate::object::{error::ErrorObject, HashKey};
 use crate::parser::ast::{Node, Program};
 use crate::parser::Parser;
 
@@ -108,6 +108,58 @@ fn test_boolean_expressions() {
     run_vm_tests(tests);
 }
 
+#[test]
+fn test_comparison_parity() {
+    // Both back ends share one equality and ordering model
+    let tests = vec![
+        (r#""a" == "a""#, Ok(Object::Boolean(true))),
+        (r#""a" != "a""#, Ok(Object::Boolean(false))),
+        (r#""a" == "b""#, Ok(Object::Boolean(false))),
+        (r#""a" < "b""#, Ok(Object::Boolean(true))),
+        (r#""b" > "a""#, Ok(Object::Boolean(true))),
+        (r#""apple" < "apricot""#, Ok(Object::Boolean(true))),
+        (r#""Z" < "a""#, Ok(Object::Boolean(true))),
+        (r#""ab" > "abc""#, Ok(Object::Boolean(false))),
+        (r#""é" > "z""#, Ok(Object::Boolean(true))),
+        (r#"[1, 2] == [1, 2]"#, Ok(Object::Boolean(true))),
+        (r#"[1, 2] == [2, 1]"#, Ok(Object::Boolean(false))),
+        (r#"[1, [2, "x"]] == [1, [2, "x"]]"#, Ok(Object::Boolean(true))),
+        (r#"[] != []"#, Ok(Object::Boolean(false))),
+        (r#"{"a": 1, "b": [2]} == {"b": [2], "a": 1}"#, Ok(Object::Boolean(true))),
+        (r#"{"a": 1} == {"a": 2}"#, Ok(Object::Boolean(false))),
+        (r#"{} == {}"#, Ok(Object::Boolean(true))),
+        (r#"if (false) { 1 } == if (false) { 2 }"#, Ok(Object::Boolean(true))),
+        (r#"1 == "1""#, Ok(Object::Boolean(false))),
+        (r#"1 != "1""#, Ok(Object::Boolean(true))),
+        (r#"true == 1"#, Ok(Object::Boolean(false))),
+        (r#"[1] == {1: 1}"#, Ok(Object::Boolean(false))),
+        (r#""a" == if (false) { 1 }"#, Ok(Object::Boolean(false))),
+        (r#"let f = fn(x) { x }; f == f"#, Ok(Object::Boolean(true))),
+        (r#"fn(x) { x } == fn(x) { x }"#, Ok(Object::Boolean(false))),
+        (r#"let f = fn(x) { x }; [f] == [f]"#, Ok(Object::Boolean(true))),
+        (r#"let make = fn() { fn(x) { x } }; make() == make()"#, Ok(Object::Boolean(false))),
+        (r#"let make = fn() { fn(x) { x } }; array.contains([make()], make())"#, Ok(Object::Boolean(false))),
+        (r#"len == len"#, Ok(Object::Boolean(true))),
+        (r#"len == puts"#, Ok(Object::Boolean(false))),
+        (r#"1 < 2"#, Ok(Object::Boolean(true))),
+        (r#"2 > 1"#, Ok(Object::Boolean(true))),
+        (r#"1 > 1"#, Ok(Object::Boolean(false))),
+        (r#"true > false"#, Err(anyhow!("unsupported types for comparison: BOOLEAN BOOLEAN"))),
+        (r#"[1] > [0]"#, Err(anyhow!("unsupported types for comparison: ARRAY ARRAY"))),
+        (r#"1 > "1""#, Err(anyhow!("unsupported types for comparison: INTEGER STRING"))),
+        (r#"array.sort(["b", "é", "a"])"#, Ok(Object::array(vec![Object::from("a"), Object::from("b"), Object::from("é")]))),
+    ];
+
+    for (input, expected) in tests {
+        for result in run_both(input) {
+            match &expected {
+                Ok(expected) => assert_eq!(&result.unwrap(), expected, "{}", input),
+                Err(expected) => assert_eq!(result.unwrap_err().to_string(), expected.to_string()),
+            }
+        }
+    }
+}
+
 #[test]
 fn test_conditionals() {
     let tests = vec![
@@ -121,6 +173,12 @@ fn test_conditionals() {
         make_test_ok!("if (1 > 2) { 10 }", Object::Null),
         make_test_ok!("if (false) { 10 }", Object::Null),
         make_test_int!("if ((if (false) { 10 })) { 10 } else { 20 }", 20),
+        make_test_int!("if (1 > 2) { 10 } else if (2 > 1) { 20 } else { 30 }", 20),
+        make_test_int!("if (1 > 2) { 10 } else if (2 > 3) { 20 } else { 30 }", 30),
+        make_test_ok!("if (1 > 2) { 10 } else if (2 > 3) { 20 }", Object::Null),
+        make_test_int!("if (true) { 10 }; if (false) { 20 }; 30", 30),
+        make_test_int!("let f = fn(x) { if (x) { 1 }; let y = 2; y }; f(false)", 2),
+        make_test_int!("let f = fn(x) { if (x) { } else if (true) { 1 } }; f(false)", 1),
     ];
 
     run_vm_tests(tests);
@@ -140,11 +198,11 @@ fn test_global_let_statements() {
 #[test]
 fn test_string_expressions() {
     let tests = vec![
-        make_test_ok!("\"monkey\"", Object::String(String::from("monkey"))),
-        make_test_ok!("\"mon\" + \"key\"", Object::String(String::from("monkey"))),
+        make_test_ok!("\"monkey\"", Object::from("monkey")),
+        make_test_ok!("\"mon\" + \"key\"", Object::from("monkey")),
         make_test_ok!(
             "\"mon\" + \"key\" + \"banana\"",
-            Object::String(String::from("monkeybanana"))
+            Object::from("monkeybanana")
         ),
     ];
 
@@ -154,10 +212,10 @@ fn test_string_expressions() {
 #[test]
 fn test_array_literals() {
     let tests = vec![
-        make_test_ok!("[]", Object::Array(vec![])),
+        make_test_ok!("[]", Object::array(vec![])),
         make_test_ok!(
             "[1, 2, 3]",
-            Object::Array(vec![
+            Object::array(vec![
                 Object::Integer(1),
                 Object::Integer(2),
                 Object::Integer(3),
@@ -165,7 +223,7 @@ fn test_array_literals() {
         ),
         make_test_ok!(
             "[1 + 2, 3 * 4, 5 + 6]",
-            Object::Array(vec![
+            Object::array(vec![
                 Object::Integer(3),
                 Object::Integer(12),
                 Object::Integer(11),
@@ -179,17 +237,17 @@ fn test_array_literals() {
 #[test]
 fn test_hash_literals() {
     let tests = vec![
-        make_test_ok!("{}", Object::Hash(HashMap::new())),
+        make_test_ok!("{}", Object::hash(OrderedMap::new())),
         make_test_ok!(
             "{1: 2, 2: 3}",
-            Object::Hash(HashMap::from([
+            Object::hash(OrderedMap::from([
                 (HashKey::Integer(1), Object::Integer(2)),
                 (HashKey::Integer(2), Object::Integer(3)),
             ]))
         ),
         make_test_ok!(
             "{1 + 1: 2 * 2, 3 + 3: 4 * 4}",
-            Object::Hash(HashMap::from([
+            Object::hash(OrderedMap::from([
                 (HashKey::Integer(2), Object::Integer(4)),
                 (HashKey::Integer(6), Object::Integer(16)),
             ]))
@@ -199,6 +257,484 @@ fn test_hash_literals() {
     run_vm_tests(tests);
 }
 
+#[test]
+fn test_shared_collections() {
+    // Collections are shared between bindings, so updates must never be visible
+    // through another reference
+    let tests = vec![
+        (r#"let a = [1, 2]; let b = push(a, 3); [a, b]"#, "[[1, 2], [1, 2, 3]]"),
+        (r#"let a = [1, 2]; let b = a; let c = array.pop(b); [a, b, c]"#, "[[1, 2], [1, 2], [1]]"),
+        (r#"let a = [3, 1]; let b = [a, array.insert(a, 0, 2), array.remove(a, 0)]; [a, b]"#, "[[3, 1], [[3, 1], [2, 3, 1], [1]]]"),
+        (r#"let h = {"a": 1}; let g = hash.merge(h, {"b": 2}); [h, g, hash.delete(g, "a"), g]"#, "[{a: 1}, {a: 1, b: 2}, {b: 2}, {a: 1, b: 2}]"),
+        (r#"let f = fn(xs) { push(xs, 0) }; let a = [1]; [f(a), f(a), a]"#, "[[1, 0], [1, 0], [1]]"),
+        (r#"push(push(push([], 1), 2), 3)"#, "[1, 2, 3]"),
+    ];
+
+    for (input, expected) in tests {
+        let program = parse(input.to_string());
+        let mut compiler = Compiler::new();
+        compiler.compile(&program).unwrap();
+
+        let mut vm = VM::new(compiler.bytecode());
+        vm.run().unwrap();
+        assert_eq!(vm.last_popped_stack_elem().to_string(), expected);
+    }
+}
+
+#[test]
+fn test_sets() {
+    let tests = vec![
+        (r#"#{3, 1, 2, 1}"#, "#{3, 1, 2}"),
+        (r#"#{}"#, "#{}"),
+        (r#"#{1, 2} == #{2, 1}"#, "true"),
+        (r#"#{1, 2} == #{1}"#, "false"),
+        (r#"2 in #{1, 2}"#, "true"),
+        (r#""a" in #{1, 2}"#, "false"),
+        (r#"1 + 1 in #{1, 2} == true"#, "true"),
+        (r#""b" in {"a": 1, "b": 2}"#, "true"),
+        (r#"3 in [1, 2]"#, "false"),
+        (r#""ell" in "hello""#, "true"),
+        (r#"set.union(#{1, 2}, #{3, 2})"#, "#{1, 2, 3}"),
+        (r#"set.intersection(#{1, 2, 3}, #{3, 2, 4})"#, "#{2, 3}"),
+        (r#"set.difference(#{1, 2, 3}, #{2})"#, "#{1, 3}"),
+        (r#"let s = #{1}; [set.add(s, 2), set.add(s, 1), s]"#, "[#{1, 2}, #{1}, #{1}]"),
+        (r#"let s = #{1, 2}; [set.remove(s, 1), set.remove(s, 3), s]"#, "[#{2}, #{1, 2}, #{1, 2}]"),
+        (r#"[len(#{1, 2, 2}), set.contains(#{true}, true)]"#, "[2, true]"),
+    ];
+
+    for (input, expected) in tests {
+        let program = parse(input.to_string());
+        let mut compiler = Compiler::new();
+        compiler.compile(&program).unwrap();
+
+        let mut vm = VM::new(compiler.bytecode());
+        vm.run().unwrap();
+        assert_eq!(vm.last_popped_stack_elem().to_string(), expected);
+    }
+
+    let errors = vec![
+        make_test_err!(r#"#{[1]}"#, "unusable as set element: ARRAY"),
+        make_test_err!(r#"1 in 2"#, "unsupported types for in: INTEGER INTEGER"),
+        make_test_err!(r#"[1] in #{1}"#, "unusable as set element: ARRAY"),
+        make_test_err!(r#"set.union(#{1}, [1])"#, "argument to `union` must be SET, got ARRAY"),
+    ];
+
+    run_vm_tests(errors);
+}
+
+#[test]
+fn test_tuples_and_destructuring() {
+    let tests = [
+        (r#"(1, 2)"#, "(1, 2)"),
+        (r#"(1,)"#, "(1,)"),
+        (r#"()"#, "()"),
+        (r#"[(1, 2) == (1, 2), (1, 2) == [1, 2], (1,) == (1, 2)]"#, "[true, false, false]"),
+        (r#"[(1, "a")[1], (1, 2, 3)[1:], len((1, 2))]"#, "[a, (2, 3), 2]"),
+        (r#"let divmod = fn(a, b) { (a / b, a - a / b * b) }; let [q, r] = divmod(7, 2); [q, r]"#, "[3, 1]"),
+        (r#"let [a, b] = [1, 2]; let [a, b] = (b, a); [a, b]"#, "[2, 1]"),
+        (r#"let person = {"name": "Ann", "age": 30}; let {name, age} = person; [name, age]"#, "[Ann, 30]"),
+        (r#"let f = fn(p) { let {x, y} = p; let [a, b] = (y, x); a - b }; f({"x": 1, "y": 5})"#, "4"),
+        (r#"let f = fn() { let [a, b] = (1, 2); fn() { a + b } }; f()()"#, "3"),
+    ];
+
+    let errors = [
+        (r#"let [a, b] = [1];"#, "cannot destructure ARRAY of length 1 into 2 names"),
+        (r#"let [a] = 1;"#, "cannot destructure INTEGER into names, expected ARRAY or TUPLE"),
+        (r#"let {a} = {"b": 1};"#, "cannot destructure HASH: missing key a"),
+        (r#"let {a} = [1];"#, "cannot destructure ARRAY into keys, expected HASH"),
+    ];
+
+    run_parity_tests(&tests, &errors);
+}
+
+#[test]
+fn test_match_expressions() {
+    let tests = vec![
+        (r#"match (1) { 1 => "one", _ => "other" }"#, "one"),
+        (r#"match (5) { 1 => "one", _ => "other" }"#, "other"),
+        (r#"match (-2) { -2 => "negative", _ => "other" }"#, "negative"),
+        (r#"match ("hi") { "hi" => true, _ => false }"#, "true"),
+        (r#"match (true) { false => 0, true => 1 }"#, "1"),
+        (r#"let sum = fn(xs) { match (xs) { [] => 0, [x, ..rest] => x + sum(rest) } }; sum([1, 2, 3, 4])"#, "10"),
+        (r#"match ([1, 2]) { [a] => a, [a, b] => a * 10 + b }"#, "12"),
+        (r#"match ([1, [2, 3]]) { [_, [x, y]] => x + y, _ => 0 }"#, "5"),
+        (r#"match ([1, 2, 3]) { [x, ..] => x }"#, "1"),
+        (r#"match ([1, 2, 3]) { [first, ..rest] => rest }"#, "[2, 3]"),
+        (r#"match ((1, 2, 3)) { (_, ..rest) => rest }"#, "(2, 3)"),
+        (r#"match ((1, "a")) { (1, s) => s, _ => "none" }"#, "a"),
+        (r#"match ([1, 2]) { (a, b) => "tuple", [a, b] => "array" }"#, "array"),
+        (r#"let area = fn(s) { match (s) { {"type": "square", "side": n} => n * n, {"type": "rect", "w": w, "h": h} => w * h } }; [area({"type": "square", "side": 3}), area({"type": "rect", "w": 2, "h": 5, "extra": 1})]"#, "[9, 10]"),
+        (r#"let sign = fn(n) { match (n) { 0 => "zero", x if x < 0 => "negative", _ => "positive" } }; [sign(0), sign(-4), sign(7)]"#, "[zero, negative, positive]"),
+        (r#"match (3) { n => { let d = n * 2; d + 1 } }"#, "7"),
+        (r#"match (1) { 1 => { let x = 1; } }"#, "null"),
+        (r#"let f = fn(x) { match (x) { 1 => { return "early"; }, _ => "late" }; "after" }; [f(1), f(2)]"#, "[early, after]"),
+        (r#"match (match (2) { 2 => [2] }) { [n] if match (n) { 2 => true, _ => false } => "two", _ => "other" }"#, "two"),
+    ];
+
+    for (input, expected) in tests {
+        let program = parse(input.to_string());
+        let mut compiler = Compiler::new();
+        compiler.compile(&program).unwrap();
+
+        let mut vm = VM::new(compiler.bytecode());
+        vm.run().unwrap();
+        assert_eq!(vm.last_popped_stack_elem().to_string(), expected);
+    }
+
+    let errors = vec![
+        make_test_err!(r#"match (3) { 1 => "one", 2 => "two" }"#, "no match arm for value: 3"),
+        make_test_err!(r#"match ([1, 2]) { [x] => x, {"a": a} => a }"#, "no match arm for value: [1, 2]"),
+    ];
+
+    run_vm_tests(errors);
+}
+
+#[test]
+fn test_match_arm_scope() {
+    let tests = [
+        ("let x = 1; match (5) { x if x > 10 => 0, _ => 1 }; x", "1"),
+        ("let x = 1; match (5) { x => x }; x", "1"),
+        ("let x = 1; match ([5, 6]) { [x, 7] => 0, [_, x] => x }", "6"),
+        ("let f = fn(x) { match (5) { x if x > 10 => 0, _ => x } }; f(1)", "1"),
+        ("let x = 1; let f = fn() { match (5) { x if x > 10 => 0, _ => 1 }; x }; f()", "1"),
+        ("let x = 1; match (5) { n => { let x = n; x } } + x", "6"),
+        ("let fs = match (2) { n if n > 1 => [fn() { n }], _ => [] }; fs[0]()", "2"),
+    ];
+
+    run_parity_tests(&tests, &[]);
+}
+
+#[test]
+fn test_members_and_methods() {
+    let tests = vec![
+        (r#"let h = {"name": "monkey"}; [h.name, h.missing]"#, "[monkey, null]"),
+        (r#"let h = {"a": {"b": 2}}; h.a.b + 1"#, "3"),
+        (r#"let h = {"greet": fn(x) { "hi " + x }}; h.greet("you")"#, "hi you"),
+        (r#""a,b".split(",")"#, "[a, b]"),
+        (r#"[3, 1, 2].sort().reverse()"#, "[3, 2, 1]"),
+        (r#"let n = -3; [n.abs(), [1, 2].len(), #{1}.add(2), {"a": 1}.keys()]"#, "[3, 2, #{1, 2}, [a]]"),
+        (r#"let double = fn(x) { x * 2 }; 5.double()"#, "10"),
+        (r#"let add = fn(a, b) { a + b }; let f = fn(x) { x.add(1) }; f(2)"#, "3"),
+        (r#"let len = fn(x) { "mine" }; [1].len()"#, "mine"),
+        (r#"let h = {"keys": fn() { 1 }}; h.keys()"#, "[keys]"),
+        (r#"let e = try { throw "boom"; } catch (e) { e }; [e.kind, e.message]"#, "[Error, boom]"),
+        (r#"let f = fn() { error("bad") }; f().message"#, "bad"),
+        (r#"try { {}.nope() } catch (e) { e.kind }"#, "TypeError"),
+    ];
+
+    for (input, expected) in tests {
+        let mut compiler = Compiler::new();
+        compiler.compile(&parse(input.to_string())).unwrap();
+        let mut vm = VM::new(compiler.bytecode());
+        vm.run().unwrap();
+        assert_eq!(vm.last_popped_stack_elem().to_string(), expected);
+    }
+
+    run_vm_tests(vec![
+        make_test_err!(r#"{}.nope()"#, "unknown method: HASH.nope"),
+        make_test_err!(r#"1.name"#, "member access not supported: INTEGER.name"),
+        make_test_err!(r#"true.f()"#, "unknown method: BOOLEAN.f"),
+    ]);
+}
+
+#[test]
+fn test_structs() {
+    let tests = vec![
+        (r#"struct Point { x, y }; let p = Point(1, 2); [p.x, p.y]"#, "[1, 2]"),
+        (r#"struct Point { x, y }; Point(1, "a")"#, "Point { x: 1, y: a }"),
+        (r#"struct Point { x, y }; Point"#, "<struct Point>"),
+        (r#"struct Empty {}; Empty()"#, "Empty {}"),
+        (r#"struct Point { x, y }; [Point(1, 2) == Point(1, 2), Point(1, 2) != Point(2, 1)]"#, "[true, true]"),
+        (r#"struct A { x }; struct B { x }; A(1) == B(1)"#, "false"),
+        (r#"struct Line { from, to }; struct Point { x, y }; let l = Line(Point(0, 0), Point(3, 4)); l.to.y - l.from.y"#, "4"),
+        (r#"struct Counter { step, next }; let c = Counter(2, fn(n) { n + 2 }); c.next(1)"#, "3"),
+        (r#"struct Point { x, y }; let norm = fn(p) { p.x * p.x + p.y * p.y }; Point(3, 4).norm()"#, "25"),
+        (r#"struct Point { x, y }; let make = fn(x) { Point(x, x) }; make(2) == make(2)"#, "true"),
+        (r#"struct Point { x, y }; try { Point(1, 2).z } catch (e) { e.kind }"#, "NameError"),
+        (r#"struct A { x, y }; struct B { y }; let gety = fn(v) { v.y }; [gety(A(1, 2)), gety(B(3)), gety(A(4, 5)), gety({"y": 6})]"#, "[2, 3, 5, 6]"),
+        (r#"struct A { y }; struct B { x }; let gety = fn(v) { v.y }; gety(A(1)); try { gety(B(2)) } catch (e) { e.message }"#, "struct B has no field y"),
+    ];
+
+    for (input, expected) in tests {
+        let mut compiler = Compiler::new();
+        compiler.compile(&parse(input.to_string())).unwrap();
+        let mut vm = VM::new(compiler.bytecode());
+        vm.run().unwrap();
+        assert_eq!(vm.last_popped_stack_elem().to_string(), expected);
+    }
+
+    run_vm_tests(vec![
+        make_test_err!(r#"struct Point { x, y }; Point(1, 2).z"#, "struct Point has no field z"),
+        make_test_err!(r#"struct Point { x, y }; Point(1)"#, "wrong number of arguments to Point: want=2, got=1"),
+        make_test_err!(r#"struct Point { x, y }; Point(1, 2).f()"#, "unknown method: Point.f"),
+    ]);
+}
+
+#[test]
+fn test_field_cache_slots_wrap() {
+    // The first and the last member access share a field cache slot once the
+    // slot numbers wrap, and read different fields of the same shape
+    let input = "struct Point { x, y }; let p = Point(1, 2); p.x;".to_string()
+        + &"p.x;".repeat(u16::MAX as usize)
+        + "p.y";
+
+    let mut compiler = Compiler::new();
+    compiler.compile(&parse(input)).unwrap();
+    let mut vm = VM::new(compiler.bytecode());
+    vm.run().unwrap();
+    assert_eq!(vm.last_popped_stack_elem().to_string(), "2");
+}
+
+#[test]
+fn test_enums() {
+    let tests = vec![
+        (r#"enum Shape { Circle(r), Rect(w, h) }; [Circle(2), Rect(1, 2)]"#, "[Circle(2), Rect(1, 2)]"),
+        (r#"enum Shape { Circle(r), Empty }; [Circle, Empty]"#, "[<variant Shape.Circle>, Empty]"),
+        (r#"enum Shape { Circle(r), Rect(w, h) }; [Circle(1) == Circle(1), Circle(1) != Circle(2), Circle(1) == Rect(1, 1)]"#, "[true, true, false]"),
+        (r#"enum Shape { Circle(r), Empty }; [Empty == Empty, Circle(1) == Empty]"#, "[true, false]"),
+        (r#"enum A { X }; let a = X; enum B { X }; a == X"#, "false"),
+        (r#"enum Shape { Circle(r), Rect(w, h) }; let r = Rect(3, 4); [r.w, r.h]"#, "[3, 4]"),
+        (r#"enum Shape { Circle(r), Rect(w, h), Empty }; let area = fn(s) { match (s) { Circle(r) => 3 * r * r, Rect(w, h) => w * h, Empty => 0 } }; [area(Circle(2)), area(Rect(3, 4)), area(Empty)]"#, "[12, 12, 0]"),
+        (r#"enum Shape { Circle(r), Rect(w, h) }; match (Rect(2, 2)) { Rect(w, h) if w != h => "oblong", Rect(_, _) => "square" }"#, "square"),
+        (r#"enum Option { Some(value), None }; match (Some(Some(1))) { Some(None) => 0, Some(Some(x)) => x }"#, "1"),
+        (r#"enum Option { Some(value), None }; match (Some([1, 2])) { Some([a, b]) => a + b, None => 0 }"#, "3"),
+        (r#"struct Point { x, y }; match (Point(1, 2)) { Point(1, y) => y, _ => 0 }"#, "2"),
+        (r#"enum Shape { Circle(r) }; match (1) { Circle(r) => r, _ => "other" }"#, "other"),
+        (r#"enum Shape { Circle(r), Empty }; struct Point { x, y }; [type(Circle(1)), type(Empty), type(Point(1, 2)), type(1)]"#, "[Shape, Shape, Point, INTEGER]"),
+        (r#"enum Shape { Circle(r) }; try { match (Circle(1)) { Circle(0) => 0 } } catch (e) { e.kind }"#, "MatchError"),
+        (r#"match (1) { 1 => "one", Missing(x) => x }"#, "one"),
+        (r#"try { match (1) { Missing => 1, _ => 2 } } catch (e) { [e.kind, e.message] }"#, "[NameError, identifier not found: Missing]"),
+    ];
+
+    for (input, expected) in tests {
+        let mut compiler = Compiler::new();
+        compiler.compile(&parse(input.to_string())).unwrap();
+        let mut vm = VM::new(compiler.bytecode());
+        vm.run().unwrap();
+        assert_eq!(vm.last_popped_stack_elem().to_string(), expected);
+    }
+
+    run_vm_tests(vec![
+        make_test_err!(r#"enum Shape { Circle(r) }; Circle(1, 2)"#, "wrong number of arguments to Circle: want=1, got=2"),
+        make_test_err!(r#"enum Shape { Circle(r) }; Circle(1).d"#, "struct Circle has no field d"),
+        make_test_err!(r#"let Circle = 1; match (1) { Circle(r) => r }"#, "not a constructor: INTEGER"),
+        make_test_err!(r#"match (1) { Missing => 1 }"#, "identifier not found: Missing"),
+    ]);
+}
+
+#[test]
+fn test_exceptions() {
+    let tests = vec![
+        (r#"try { 1 } catch (e) { 2 }"#, "1"),
+        (r#"try { throw "boom"; 1 } catch (e) { e }"#, "Error: boom"),
+        (r#"try { throw "boom" } catch (e) { [e["kind"], e["message"]] }"#, "[Error, boom]"),
+        (r#"try { throw 42 } catch (e) { e["message"] }"#, "42"),
+        (r#"try { throw 42 } catch (e) { e["value"] + 1 }"#, "43"),
+        (r#"try { throw [1, "a"] } catch (e) { e.value }"#, "[1, a]"),
+        (r#"let f = try { throw fn(x) { x * 2 } } catch (e) { e.value }; f(21)"#, "42"),
+        (r#"try { try { throw {"a": 1} } catch (e) { throw e } } catch (e) { e.value["a"] }"#, "1"),
+        (r#"try { try { throw 1 } finally { try { throw 2 } catch (e) { e } } } catch (e) { e.value }"#, "1"),
+        (r#"try { 1 + true } catch (e) { e.value }"#, "null"),
+        (r#"try { 1 + true } catch (e) { e["kind"] }"#, "TypeError"),
+        (r#"try { 1(2) } catch (e) { e["kind"] }"#, "TypeError"),
+        (r#"try { len(1) } catch (e) { e["kind"] }"#, "ArgumentError"),
+        (r#"try { array.insert([], 5, 1) } catch (e) { e["kind"] }"#, "IndexError"),
+        (r#"try { math.pow(2, 64) } catch (e) { [e["kind"], e["message"]] }"#, "[RuntimeError, integer overflow in `pow`]"),
+        (r#"try { match (3) { 1 => 1 } } catch (e) { e["kind"] }"#, "MatchError"),
+        (r#"let f = fn(x) { if (x > 2) { throw "too big" }; x }; let g = fn(x) { f(x) * 10 }; [try { g(1) } catch (e) { 0 }, try { g(5) } catch (e) { e["message"] }]"#, "[10, too big]"),
+        (r#"try { 1 } finally { 2 }"#, "1"),
+        (r#"let f = fn() { let x = try { throw "a" } catch (e) { 1 } finally { 2 }; x }; f()"#, "1"),
+        (r#"try { try { throw "inner" } finally { 1 } } catch (e) { e["message"] }"#, "inner"),
+        (r#"try { try { throw "inner" } catch (e) { throw e["message"] + "!" } finally { 1 } } catch (e) { e["message"] }"#, "inner!"),
+        (r#"try { try { 1 + true } catch (e) { throw e } } catch (e) { e["kind"] }"#, "TypeError"),
+        (r#"let f = fn() { try { return 1; } finally { let y = 2; }; 2 }; f()"#, "1"),
+        (r#"let f = fn() { try { return 1; } finally { return 2; } }; f()"#, "2"),
+        (r#"let f = fn() { try { throw "x" } catch (e) { return e["message"]; }; "after" }; f()"#, "x"),
+        (r#"let f = fn(n) { if (n == 0) { throw "bottom" }; f(n - 1) }; try { f(50) } catch (e) { e["message"] }"#, "bottom"),
+        (r#"let e = "outer"; try { throw "inner" } catch (e) { e }; e["message"]"#, "inner"),
+        (r#"[1, try { throw "x" } catch (e) { 2 }, 3]"#, "[1, 2, 3]"),
+        (r#"try { throw "x" } catch (e) { }"#, "null"),
+    ];
+
+    for (input, expected) in tests {
+        let program = parse(input.to_string());
+        let mut compiler = Compiler::new();
+        compiler.compile(&program).unwrap();
+
+        let mut vm = VM::new(compiler.bytecode());
+        vm.run().unwrap();
+        assert_eq!(vm.last_popped_stack_elem().to_string(), expected);
+    }
+
+    let errors = vec![
+        make_test_err!(r#"throw "boom""#, "Error: boom"),
+        make_test_err!(r#"try { throw "boom" } finally { 1 }"#, "Error: boom"),
+        make_test_err!(r#"try { 1(2) } catch (e) { throw e }"#, "TypeError: calling non-closure and non-builtin"),
+        make_test_err!(r#"let f = fn() { try { 1 } catch (e) { 2 } }; f(); [][0] + 1"#, "unsupported types for binary operation: NULL INTEGER"),
+    ];
+
+    run_vm_tests(errors);
+}
+
+#[test]
+fn test_error_values() {
+    let tests = vec![
+        (r#"string.parse_int("42")"#, "42"),
+        (r#"string.parse_int(" -7 ")"#, "-7"),
+        (r#"string.parse_int("4x2")"#, "ValueError: invalid integer: 4x2"),
+        (r#"is_error(string.parse_int("x"))"#, "true"),
+        (r#"is_error(string.parse_int("1"))"#, "false"),
+        (r#"error("bad")"#, "Error: bad"),
+        (r#"error("ParseError", "bad")["kind"]"#, "ParseError"),
+        (r#"let f = fn(s) { let n = string.parse_int(s)?; n * 2 }; [f("21"), f("x")]"#, "[42, ValueError: invalid integer: x]"),
+        (r#"let f = fn(a, b) { string.parse_int(a)? + string.parse_int(b)? }; [f("1", "2"), f("1", "b")]"#, "[3, ValueError: invalid integer: b]"),
+        (r#"let f = fn(s) { [string.parse_int(s)?] }; let g = fn(s) { f(s)?[0] + 1 }; [g("1"), g("x")["message"]]"#, "[2, invalid integer: x]"),
+        (r#"let f = fn() { try { error("inner")? } catch (e) { "caught" } }; f()"#, "Error: inner"),
+        (r#"let f = fn() { try { error("inner")? } finally { 1 } }; f()"#, "Error: inner"),
+        (r#"let x = string.parse_int("x")?; 1"#, "ValueError: invalid integer: x"),
+        (r#"let f = fn(s) { -string.parse_int(s)? }; f("3")"#, "-3"),
+        (r#"is_error(io.read_file("/nonexistent/file.txt"))"#, "true"),
+        (r#"io.read_file("/nonexistent/file.txt")["kind"]"#, "IOError"),
+    ];
+
+    for (input, expected) in tests {
+        let program = parse(input.to_string());
+        let mut compiler = Compiler::new();
+        compiler.compile(&program).unwrap();
+
+        let mut vm = VM::new(compiler.bytecode());
+        vm.run().unwrap();
+        assert_eq!(vm.last_popped_stack_elem().to_string(), expected);
+    }
+
+    let path = std::env::temp_dir().join("monkey_vm_test_error_values.txt");
+    let input = format!(
+        r#"let path = "{}"; io.write_file(path, "contents"); io.read_file(path)"#,
+        path.display()
+    );
+    run_vm_tests(vec![make_test_ok!(input, Object::from("contents"))]);
+    std::fs::remove_file(path).unwrap();
+}
+
+// Write the modules imported by the module tests into a new directory
+fn write_test_modules(dir: &std::path::Path) {
+    let _ = std::fs::remove_dir_all(dir);
+    std::fs::create_dir_all(dir.join("lib")).unwrap();
+    let marker = dir.join("marker.txt");
+    let util = format!(
+        r#"let seen = io.read_file("{0}"); io.write_file("{0}", "seen"); let name = "util"; let double = fn(x) {{ x * 2 }};"#,
+        marker.display()
+    );
+    std::fs::write(dir.join("util.monkey"), util).unwrap();
+    std::fs::write(
+        dir.join("lib").join("shapes.monkey"),
+        r#"import "../util.monkey" as u; let util_seen = u.seen; let area = fn(r) { u.double(r) * r };"#,
+    )
+    .unwrap();
+    std::fs::write(dir.join("cycle.monkey"), r#"import "main.monkey" as m;"#).unwrap();
+    std::fs::write(
+        dir.join("sign.monkey"),
+        r#"let sign = match (-2) { n if n < 0 => "negative", _ => "other" };"#,
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_modules() {
+    let dir = std::env::temp_dir().join("monkey_vm_test_modules");
+    write_test_modules(&dir);
+    let dir = dir.canonicalize().unwrap();
+    let tests: Vec<(&str, Result<&str, &str>)> = vec![
+        (r#"import "util.monkey" as u; u.double(21)"#, Ok("42")),
+        (r#"import "util.monkey" as u; let double = fn(x) { 0 }; u.double(21)"#, Ok("42")),
+        (r#"import "util.monkey" as u; [u.name, u]"#, Ok("[util, <module util>]")),
+        (r#"import "shapes.monkey" as s; s.area(3)"#, Ok("18")),
+        (r#"import "util.monkey" as u; import "shapes.monkey" as s; [is_error(u.seen), is_error(s.util_seen)]"#, Ok("[true, true]")),
+        (r#"import "util.monkey" as u; let name = "main"; [name, u.name]"#, Ok("[main, util]")),
+        (r#"import "cycle.monkey" as c;"#, Err("import cycle: {dir}/main.monkey -> {dir}/cycle.monkey -> {dir}/main.monkey")),
+        (r#"import "missing.monkey" as m;"#, Err("module not found: missing.monkey")),
+        (r#"import "util.monkey" as u; u.missing"#, Err("module util has no member missing")),
+        (r#"let x = 1; x.y"#, Err("member access not supported: INTEGER.y")),
+        (r#"try { let x = 1; x.y } catch (e) { e["kind"] }"#, Ok("TypeError")),
+        (r#"import "util.monkey" as u; double"#, Err("undefined variable: double")),
+        (r#"import "sign.monkey" as s; s.sign"#, Ok("negative")),
+        (r#"import "sign.monkey" as s; s.n"#, Err("module sign has no member n")),
+    ];
+
+    for (input, expected) in tests {
+        let _ = std::fs::remove_file(dir.join("marker.txt"));
+        let main = dir.join("main.monkey");
+        std::fs::write(&main, input).unwrap();
+        let search_path = vec![dir.join("lib")];
+
+        let mut compiler = Compiler::new();
+        compiler.set_module_loader(ModuleLoader::new(search_path, Some(&main)).unwrap());
+        let result = compiler.compile(&parse(input.to_string())).and_then(|_| {
+            let mut vm = VM::new(compiler.bytecode());
+            vm.run()?;
+            Ok(vm.last_popped_stack_elem())
+        });
+        match expected {
+            Ok(expected) => assert_eq!(result.unwrap().to_string(), expected),
+            Err(expected) => assert_eq!(
+                result.unwrap_err().to_string(),
+                expected.replace("{dir}", &dir.display().to_string())
+            ),
+        }
+    }
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_hash_keys_share_storage() {
+    let program = parse(r#"[{"name": 1}, {"name": 2}, "name", "na" + "me"]"#.to_string());
+    let mut compiler = Compiler::new();
+    compiler.compile(&program).unwrap();
+    let mut vm = VM::new(compiler.bytecode());
+    vm.run().unwrap();
+
+    let Object::Array(elements) = vm.last_popped_stack_elem() else { panic!("expected an array") };
+    let key = |element: &Object| match element {
+        Object::Hash(pairs) => match pairs.keys().next() {
+            Some(HashKey::String(key)) => key.clone(),
+            key => panic!("expected a string key, got {:?}", key),
+        },
+        _ => panic!("expected a hash, got {}", element),
+    };
+    let (Object::String(literal), Object::String(built)) = (&elements[2], &elements[3]) else {
+        panic!("expected strings")
+    };
+
+    // Literal keys share the interned constant, while a string built at run
+    // time is an equal key with storage of its own
+    assert!(Rc::ptr_eq(&key(&elements[0]), &key(&elements[1])));
+    assert!(Rc::ptr_eq(&key(&elements[0]), literal));
+    assert!(!Rc::ptr_eq(literal, built));
+    assert_eq!(HashKey::String(literal.clone()), HashKey::String(built.clone()));
+}
+
+#[test]
+fn test_hash_ordering() {
+    let tests = vec![
+        (r#"{"b": 1, "a": 2, 3: 3, true: 4}"#, r#"{b: 1, a: 2, 3: 3, true: 4}"#),
+        (r#"{"a": 1, "b": 2, "a": 3}"#, r#"{a: 3, b: 2}"#),
+        (r#"hash.delete({"c": 1, "b": 2, "a": 3}, "b")"#, r#"{c: 1, a: 3}"#),
+        (r#"hash.merge({"z": 1, "y": 2}, {"x": 3, "z": 4})"#, r#"{z: 4, y: 2, x: 3}"#),
+        (r#"hash.keys({"z": 1, "a": 2, "m": 3})"#, r#"[z, a, m]"#),
+        (r#"array.contains([{"b": 1, "a": 2}], {"a": 2, "b": 1})"#, "true"),
+    ];
+
+    for (input, expected) in tests {
+        let program = parse(input.to_string());
+        let mut compiler = Compiler::new();
+        compiler.compile(&program).unwrap();
+
+        let mut vm = VM::new(compiler.bytecode());
+        vm.run().unwrap();
+        assert_eq!(vm.last_popped_stack_elem().to_string(), expected);
+    }
+}
+
 #[test]
 fn test_index_expressions() {
     let tests = vec![
@@ -214,7 +750,7 @@ fn test_index_expressions() {
         make_test_ok!("{}[0]", Object::Null),
         make_test_ok!(
             "[1, 2, 3][:]",
-            Object::Array(vec![
+            Object::array(vec![
                 Object::Integer(1),
                 Object::Integer(2),
                 Object::Integer(3),
@@ -222,41 +758,60 @@ fn test_index_expressions() {
         ),
         make_test_ok!(
             "[1, 2, 3][1:]",
-            Object::Array(vec![Object::Integer(2), Object::Integer(3)])
+            Object::array(vec![Object::Integer(2), Object::Integer(3)])
         ),
-        make_test_ok!("[1, 2, 3][:1]", Object::Array(vec![Object::Integer(1)])),
-        make_test_ok!("[1, 2, 3][-1:]", Object::Array(vec![Object::Integer(3)])),
+        make_test_ok!("[1, 2, 3][:1]", Object::array(vec![Object::Integer(1)])),
+        make_test_ok!("[1, 2, 3][-1:]", Object::array(vec![Object::Integer(3)])),
         make_test_ok!(
             "[1, 2, 3][:-1]",
-            Object::Array(vec![Object::Integer(1), Object::Integer(2)])
+            Object::array(vec![Object::Integer(1), Object::Integer(2)])
         ),
-        make_test_ok!("[1, 2, 3][1:2]", Object::Array(vec![Object::Integer(2)])),
+        make_test_ok!("[1, 2, 3][1:2]", Object::array(vec![Object::Integer(2)])),
         make_test_ok!(
             "[1, 2, 3][1:3]",
-            Object::Array(vec![Object::Integer(2), Object::Integer(3)])
+            Object::array(vec![Object::Integer(2), Object::Integer(3)])
         ),
         make_test_ok!(
             "[1, 2, 3][1:4]",
-            Object::Array(vec![Object::Integer(2), Object::Integer(3)])
+            Object::array(vec![Object::Integer(2), Object::Integer(3)])
         ),
-        make_test_ok!("[1, 2, 3][4:5]", Object::Array(vec![])),
-        make_test_ok!("\"Hello\"[1]", Object::String("e".to_string())),
-        make_test_ok!("\"Hello\"[2]", Object::String("l".to_string())),
-        make_test_ok!("\"Hello\"[0]", Object::String("H".to_string())),
-        make_test_ok!("\"Hello\"[3]", Object::String("l".to_string())),
-        make_test_ok!("\"Hello\"[4]", Object::String("o".to_string())),
+        make_test_ok!("[1, 2, 3][4:5]", Object::array(vec![])),
+        make_test_ok!("\"Hello\"[1]", Object::from("e")),
+        make_test_ok!("\"Hello\"[2]", Object::from("l")),
+        make_test_ok!("\"Hello\"[0]", Object::from("H")),
+        make_test_ok!("\"Hello\"[3]", Object::from("l")),
+        make_test_ok!("\"Hello\"[4]", Object::from("o")),
         make_test_ok!("\"Hello\"[5]", Object::Null),
-        make_test_ok!("\"Hello\"[-1]", Object::String("o".to_string())),
-        make_test_ok!("\"Hello\"[1:]", Object::String("ello".to_string())),
-        make_test_ok!("\"Hello\"[:1]", Object::String("H".to_string())),
-        make_test_ok!("\"Hello\"[-1:]", Object::String("o".to_string())),
-        make_test_ok!("\"Hello\"[:-1]", Object::String("Hell".to_string())),
-        make_test_ok!("\"Hello\"[:]", Object::String("Hello".to_string())),
-        make_test_ok!("\"Hello\"[1:2]", Object::String("e".to_string())),
-        make_test_ok!("\"Hello\"[1:3]", Object::String("el".to_string())),
-        make_test_ok!("\"Hello\"[1:4]", Object::String("ell".to_string())),
-        make_test_ok!("\"Hello\"[1:5]", Object::String("ello".to_string())),
-        make_test_ok!("\"Hello\"[1:6]", Object::String("ello".to_string())),
+        make_test_ok!("\"Hello\"[-1]", Object::from("o")),
+        make_test_ok!("\"Hello\"[1:]", Object::from("ello")),
+        make_test_ok!("\"Hello\"[:1]", Object::from("H")),
+        make_test_ok!("\"Hello\"[-1:]", Object::from("o")),
+        make_test_ok!("\"Hello\"[:-1]", Object::from("Hell")),
+        make_test_ok!("\"Hello\"[:]", Object::from("Hello")),
+        make_test_ok!("\"Hello\"[1:2]", Object::from("e")),
+        make_test_ok!("\"Hello\"[1:3]", Object::from("el")),
+        make_test_ok!("\"Hello\"[1:4]", Object::from("ell")),
+        make_test_ok!("\"Hello\"[1:5]", Object::from("ello")),
+        make_test_ok!("\"Hello\"[1:6]", Object::from("ello")),
+    ];
+
+    run_vm_tests(tests);
+}
+
+#[test]
+fn test_unicode_strings() {
+    let tests = vec![
+        make_test_int!(r#"len("héllo")"#, 5),
+        make_test_int!(r#"len("日本語")"#, 3),
+        make_test_int!(r#"len("🐵🙈")"#, 2),
+        make_test_ok!(r#""héllo"[1]"#, Object::from("é")),
+        make_test_ok!(r#""日本語"[-1]"#, Object::from("語")),
+        make_test_ok!(r#""日本語"[3]"#, Object::Null),
+        make_test_ok!(r#""héllo"[1:3]"#, Object::from("él")),
+        make_test_ok!(r#""日本語"[1:]"#, Object::from("本語")),
+        make_test_ok!(r#""🐵🙈🙉"[:-1]"#, Object::from("🐵🙈")),
+        make_test_ok!(r#""日本語"[-10:2]"#, Object::from("日本")),
+        make_test_ok!(r#""日本語"[2:1]"#, Object::from("")),
     ];
 
     run_vm_tests(tests);
@@ -413,6 +968,45 @@ fn test_calling_functions_with_wrong_arguments() {
     run_vm_tests(tests);
 }
 
+#[test]
+fn test_optional_parameters() {
+    let tests = vec![
+        (r#"let f = fn(a, b = 10) { a + b }; [f(1), f(1, 2)]"#, "[11, 3]"),
+        (r#"let f = fn(a, b = a * 2) { [a, b] }; f(3)"#, "[3, 6]"),
+        (r#"let f = fn(xs = []) { push(xs, 1) }; [f(), f()]"#, "[[1], [1]]"),
+        (r#"let n = 5; let f = fn(a = n) { a }; f()"#, "5"),
+        (r#"let make = fn(n) { fn(a = n + 1) { a } }; make(1)()"#, "2"),
+        (r#"let f = fn(a, ...rest) { [a, rest] }; [f(1), f(1, 2, 3)]"#, "[[1, []], [1, [2, 3]]]"),
+        (r#"let f = fn(a, b = 2, ...rest) { [a, b, rest] }; [f(1), f(1, 5, 6, 7)]"#, "[[1, 2, []], [1, 5, [6, 7]]]"),
+        (r#"let f = fn(a, b = 2, c = 3) { [a, b, c] }; [f(1, c = 30), f(b = 20, a = 10)]"#, "[[1, 2, 30], [10, 20, 3]]"),
+        (r#"struct Point { x, y }; Point(y = 2, x = 1)"#, "Point { x: 1, y: 2 }"),
+        (r#"let sum = fn(xs, acc = 0) { if (len(xs) == 0) { acc } else { sum(rest(xs), acc + first(xs)) } }; sum([1, 2, 3])"#, "6"),
+        (r#"let scale = fn(x, by = 2) { x * by }; [3.scale(), 3.scale(by = 3)]"#, "[6, 9]"),
+        (r#"let f = fn(a) { a }; try { f(b = 1) } catch (e) { e.kind }"#, "ArgumentError"),
+        // A failed method lookup leaves no keyword names behind for the next call
+        (r#"let h = {"x": 1}; [try { h.nosuch(y = 1) } catch (e) { e }, len([1])]"#, "[TypeError: unknown method: HASH.nosuch, 1]"),
+    ];
+
+    for (input, expected) in tests {
+        let mut compiler = Compiler::new();
+        compiler.compile(&parse(input.to_string())).unwrap();
+        let mut vm = VM::new(compiler.bytecode());
+        vm.run().unwrap();
+        assert_eq!(vm.last_popped_stack_elem().to_string(), expected);
+    }
+
+    run_vm_tests(vec![
+        make_test_err!(r#"fn(a, b = 1) { a }(1, 2, 3)"#, "wrong number of arguments: want=1..2, got=3"),
+        make_test_err!(r#"fn(a, ...rest) { a }()"#, "wrong number of arguments: want>=1, got=0"),
+        make_test_err!(r#"fn(a, b = 1) { a }(b = 2)"#, "missing argument a"),
+        make_test_err!(r#"fn(a, b = 1) { a }(1, a = 2)"#, "multiple values for argument a"),
+        make_test_err!(r#"fn(a, b = 2, ...rest) { b }(1, 2, 3, b = 4)"#, "multiple values for argument b"),
+        make_test_err!(r#"fn(...rest) { rest }(a = 1)"#, "unexpected keyword argument a"),
+        make_test_err!(r#"len(x = 1)"#, "keyword arguments not supported by builtin functions"),
+        make_test_err!(r#"struct Point { x, y }; Point(1, z = 2)"#, "unexpected keyword argument z"),
+    ]);
+}
+
 #[test]
 fn test_builtin_functions() {
     let tests = vec![
@@ -430,15 +1024,146 @@ fn test_builtin_functions() {
         make_test_ok!(r#"last([1, 2, 3])"#, Object::Integer(3)),
         make_test_ok!(r#"last([])"#, Object::Null),
         make_test_err!(r#"last(1)"#, "argument to `last` must be ARRAY, got INTEGER"),
-        make_test_ok!(r#"rest([1, 2, 3])"#, Object::Array(vec![Object::Integer(2), Object::Integer(3)])),
+        make_test_ok!(r#"rest([1, 2, 3])"#, Object::array(vec![Object::Integer(2), Object::Integer(3)])),
         make_test_ok!(r#"rest([])"#, Object::Null),
-        make_test_ok!(r#"push([], 1)"#, Object::Array(vec![Object::Integer(1)])),
+        make_test_ok!(r#"push([], 1)"#, Object::array(vec![Object::Integer(1)])),
         make_test_err!(r#"push(1, 1)"#, "argument to `push` must be ARRAY, got INTEGER"),
     ];
 
     run_vm_tests(tests);
 }
 
+#[test]
+fn test_string_builtins() {
+    let tests = [
+        (r#"string.split("a,b,c", ",")"#, "[a, b, c]"),
+        (r#"string.split("héllo", "")"#, "[h, é, l, l, o]"),
+        (r#"string.join(["a", 1, true], "-")"#, "a-1-true"),
+        (r#"string.trim("  hi
+	")"#, "hi"),
+        (r#"string.upper("straße")"#, "STRASSE"),
+        (r#"string.lower("ÉCOLE")"#, "école"),
+        (r#"string.replace("a-b-c", "-", "+")"#, "a+b+c"),
+        (r#"string.contains("monkey", "key")"#, "true"),
+        (r#"string.contains("monkey", "ape")"#, "false"),
+        (r#"string.starts_with("monkey", "mon")"#, "true"),
+        (r#"string.ends_with("monkey", "mon")"#, "false"),
+        (r#"string.index_of("日本語", "語")"#, "2"),
+        (r#"string.index_of("monkey", "z")"#, "-1"),
+        (r#"string.repeat("ab", 3)"#, "ababab"),
+        (r#"string.chars("日本")"#, "[日, 本]"),
+        (r#"string.lines("one
+two
+three")"#, "[one, two, three]"),
+        (r#"string.format("{} + {} = {}", 1, 2, "three")"#, "1 + 2 = three"),
+        (r#"string.format("{{}} {}", [1, 2])"#, "{} [1, 2]"),
+    ];
+
+    let errors = [
+        (r#"string.split(1, ",")"#, "argument to `split` must be STRING, got INTEGER"),
+        (r#"string.join("abc", "-")"#, "argument to `join` must be ARRAY, got STRING"),
+        (r#"string.replace("abc", "b")"#, "wrong number of arguments. got=2, want=3"),
+        (r#"string.repeat("ab", -1)"#, "argument to `repeat` must be non-negative, got -1"),
+        (r#"string.repeat("ab", "3")"#, "argument to `repeat` must be INTEGER, got STRING"),
+        (
+            r#"string.repeat("ab", 9223372036854775807)"#,
+            "argument to `repeat` would make a string longer than 268435456 bytes",
+        ),
+        (r#"string.format("{} {}", 1)"#, "wrong number of arguments to `format`. got=1, want=2"),
+        (r#"string.format()"#, "wrong number of arguments. got=0, want>=1"),
+    ];
+
+    run_parity_tests(&tests, &errors);
+}
+
+#[test]
+fn test_builtin_modules() {
+    let tests = [
+        (r#"math.abs(-3)"#, "3"),
+        (r#"math.min(3, 1, 2)"#, "1"),
+        (r#"math.max([4, 9, 2])"#, "9"),
+        (r#"math.pow(2, 10)"#, "1024"),
+        (r#"let s = string; s.upper("a")"#, "A"),
+        (r#"let sort = array.sort; sort([2, 1])"#, "[1, 2]"),
+        (r#"let string = "abc"; len(string)"#, "3"),
+        (r#"let f = fn(array) { len(array) }; f([1, 2])"#, "2"),
+        (r#"[string, is_error(io.read_file("/nonexistent/file.txt"))]"#, "[<module string>, true]"),
+    ];
+
+    let errors = [
+        (r#"math.max()"#, "argument to `max` must not be empty"),
+        (r#"math.min(1, "a")"#, "argument to `min` must be INTEGER, got STRING"),
+        (r#"math.pow(2, -1)"#, "argument to `pow` must be a non-negative exponent, got -1"),
+        (r#"math.pow(2, 64)"#, "integer overflow in `pow`"),
+        (r#"math.nope"#, "module math has no member nope"),
+        (r#"hash.keys(set.union(#{1}, #{2}))"#, "argument to `keys` must be HASH, got SET"),
+    ];
+
+    run_parity_tests(&tests, &errors);
+
+    // Both back ends share one object per builtin module
+    match run_both("string") {
+        [Ok(Object::Module(evaluated)), Ok(Object::Module(compiled))] => {
+            assert!(Rc::ptr_eq(&evaluated, &compiled))
+        }
+        results => panic!("expected two modules, got {:?}", results),
+    }
+}
+
+#[test]
+fn test_collection_builtins() {
+    let tests = [
+        (r#"array.pop([1, 2, 3])"#, "[1, 2]"),
+        (r#"array.pop([])"#, "[]"),
+        (r#"array.insert([1, 3], 1, 2)"#, "[1, 2, 3]"),
+        (r#"array.insert([1, 2], 2, 3)"#, "[1, 2, 3]"),
+        (r#"array.insert([1, 2], -1, 9)"#, "[1, 9, 2]"),
+        (r#"array.remove([1, 2, 3], 0)"#, "[2, 3]"),
+        (r#"array.remove([1, 2, 3], -1)"#, "[1, 2]"),
+        (r#"array.reverse([1, 2, 3])"#, "[3, 2, 1]"),
+        (r#"string.reverse("héllo")"#, "olléh"),
+        (r#"array.concat([1], [], [2, 3])"#, "[1, 2, 3]"),
+        (r#"[1].concat()"#, "[1]"),
+        (r#"array.contains([1, "a", [2]], [2])"#, "true"),
+        (r#"array.contains([1, 2], 3)"#, "false"),
+        (r#"array.index_of([1, 2, 3], 3)"#, "2"),
+        (r#"array.index_of([1, 2, 3], 4)"#, "-1"),
+        (r#"array.sort([3, -1, 2])"#, "[-1, 2, 3]"),
+        (r#"array.sort(["b", "c", "a"])"#, "[a, b, c]"),
+        (r#"array.unique([1, 2, 1, 3, 2])"#, "[1, 2, 3]"),
+        (r#"array.zip([1, 2, 3], ["a", "b"])"#, "[[1, a], [2, b]]"),
+        (r#"array.flatten([1, [2, 3], [[4]]])"#, "[1, 2, 3, [4]]"),
+        (r#"array.range(3)"#, "[0, 1, 2]"),
+        (r#"array.range(1, 4)"#, "[1, 2, 3]"),
+        (r#"array.range(10, 0, -3)"#, "[10, 7, 4, 1]"),
+        (r#"array.range(9223372036854775806, 9223372036854775807, 5)"#, "[9223372036854775806]"),
+        (r#"array.range(-9223372036854775806, -9223372036854775807 - 1, -5)"#, "[-9223372036854775806]"),
+        (r#"hash.keys({"b": 1, 2: 2, "a": 3, true: 4, 1: 5})"#, "[b, 2, a, true, 1]"),
+        (r#"hash.values({"b": 1, "a": 2, 3: 3})"#, "[1, 2, 3]"),
+        (r#"hash.entries({"b": 1, "a": 2})"#, "[[b, 1], [a, 2]]"),
+        (r#"hash.has_key({"a": 1}, "a")"#, "true"),
+        (r#"hash.has_key({"a": 1}, "b")"#, "false"),
+        (r#"hash.delete({"a": 1, "b": 2}, "a")"#, "{b: 2}"),
+        (r#"hash.delete({"a": 1}, "z")"#, "{a: 1}"),
+        (r#"hash.merge({"a": 1, "b": 2}, {"b": 3, "c": 4})"#, "{a: 1, b: 3, c: 4}"),
+    ];
+
+    let errors = [
+        (r#"array.insert([1], 5, 2)"#, "index out of range for `insert`: 5"),
+        (r#"array.remove([], 0)"#, "index out of range for `remove`: 0"),
+        (r#"array.concat([1], 2)"#, "argument to `concat` must be ARRAY, got INTEGER"),
+        (r#"array.concat()"#, "wrong number of arguments. got=0, want>=1"),
+        (r#"array.contains(1, 1)"#, "argument to `contains` must be STRING, ARRAY or SET, got INTEGER"),
+        (r#"array.sort([1, "a"])"#, "argument to `sort` must be ARRAY of INTEGER or STRING"),
+        (r#"array.range(0, 1, 0)"#, "argument to `range` must have a non-zero step"),
+        (r#"array.range()"#, "wrong number of arguments. got=0, want=1..3"),
+        (r#"hash.keys([])"#, "argument to `keys` must be HASH, got ARRAY"),
+        (r#"hash.has_key({"a": 1}, [])"#, "unusable as hash key: ARRAY"),
+    ];
+
+    run_parity_tests(&tests, &errors);
+}
+
 #[test]
 fn test_closures() {
     let tests = vec![
@@ -587,6 +1312,182 @@ fn test_recursive_fibonacci() {
     run_vm_tests(tests);
 }
 
+#[test]
+fn test_stack_is_released() {
+    // Returning from a call or popping a statement leaves nothing behind on the stack
+    let tests = vec![
+        "1; 2; 3;",
+        "let f = fn(a, b) { let c = a + b; c }; f(1, 2); f(3, 4);",
+        "let g = fn() { [1, 2, {\"a\": 3}] }; g()[2];",
+        "let h = fn(x) { if (x == 0) { 0 } else { h(x - 1) } }; h(100);",
+        "len(push([1], 2)); let x = fn() { }; x();",
+    ];
+
+    for input in tests {
+        let program = parse(input.to_string());
+        let mut compiler = Compiler::new();
+        compiler.compile(&program).unwrap();
+
+        let mut vm = VM::new(compiler.bytecode());
+        vm.run().unwrap();
+        assert!(vm.stack.is_empty(), "{}", input);
+    }
+}
+
+#[test]
+fn test_recursive_closures_are_released() {
+    // Thousands of short-lived recursive closures are reclaimed by the heap as
+    // the program runs, and once it finishes only the global binding keeps a
+    // closure (and with it a compiled function) alive.
+    let input = r#"
+        let spawn = fn(n) {
+            let countdown = fn(x) { if (x == 0) { 0 } else { countdown(x - 1) } };
+            countdown(3);
+            if (n == 0) { 0 } else { spawn(n - 1) }
+        };
+    "#
+    .to_string()
+        + &"spawn(100);".repeat(50);
+
+    let program = parse(input);
+    let mut vm = {
+        let mut compiler = Compiler::new();
+        compiler.compile(&program).unwrap();
+        VM::new(compiler.bytecode())
+    };
+    vm.set_gc_threshold(64);
+    vm.run().unwrap();
+
+    let stats = vm.heap_stats();
+    assert!(stats.allocations > 5000);
+    assert!(stats.collections > 0);
+    assert!(stats.live < 500, "{:?}", stats);
+
+    vm.collect_garbage();
+    assert_eq!(vm.heap_stats().live, 2);
+    for constant in &vm.constants {
+        if let Object::CompiledFunction(func) = constant {
+            assert!(Rc::strong_count(func) <= 2);
+        }
+    }
+}
+
+#[test]
+fn test_self_referencing_closures_are_freed() {
+    // Each call leaves behind a closure that refers to itself and an array
+    // holding it, the shape that forms a reference cycle in the evaluator.
+    // With automatic collection held off they all stay on the heap until an
+    // explicit collection frees every one of them.
+    let input = r#"
+        let make = fn(n) {
+            let loop = fn(x) { if (x == 0) { [loop] } else { loop(x - 1) } };
+            [loop, loop(n)]
+        };
+        let churn = fn(n) { if (n == 0) { 0 } else { make(3); churn(n - 1) } };
+        churn(200);
+    "#;
+
+    let program = parse(input.to_string());
+    let mut compiler = Compiler::new();
+    compiler.compile(&program).unwrap();
+    let mut vm = VM::new(compiler.bytecode());
+    vm.set_gc_threshold(usize::MAX);
+    vm.run().unwrap();
+
+    // The main closure, make, churn and one loop closure per call
+    let stats = vm.heap_stats();
+    assert_eq!(stats.collections, 0);
+    assert_eq!(stats.live, 203, "{:?}", stats);
+
+    assert_eq!(vm.collect_garbage(), 200);
+    let stats = vm.heap_stats();
+    assert_eq!((stats.collections, stats.freed, stats.live), (1, 200, 3));
+}
+
+#[test]
+fn test_garbage_collection_keeps_roots() {
+    // Closures reachable from globals, arrays, hashes, structs, free variables
+    // and the stack survive collections and keep working
+    let input = r#"
+        let make = fn(n) {
+            let count = fn(x) { if (x == 0) { n } else { count(x - 1) } };
+            count
+        };
+        let adder = fn(f) { fn(x) { f(x) + 1 } };
+        struct Holder { f }
+        let kept = [make(1), {"f": make(2)}, adder(make(3)), Holder(make(5))];
+        let churn = fn(n) { if (n == 0) { 0 } else { make(n); churn(n - 1) } };
+        let hold = fn(f) { churn(100); f(0) };
+    "#
+    .to_string()
+        + &"churn(200);".repeat(10)
+        + r#"[kept[0](5), kept[1]["f"](5), kept[2](5), hold(make(4)), kept[3].f(5)]"#;
+
+    let program = parse(input);
+    let mut compiler = Compiler::new();
+    compiler.compile(&program).unwrap();
+
+    let mut vm = VM::new(compiler.bytecode());
+    vm.set_gc_threshold(16);
+    vm.run().unwrap();
+
+    assert!(vm.heap_stats().collections > 0);
+    assert_eq!(vm.last_popped_stack_elem().to_string(), "[1, 2, 4, 4, 5]");
+}
+
+#[test]
+fn test_garbage_collection_frees_handles() {
+    let mut heap = heap::Heap::new(16);
+    let func = Rc::new(CompiledFunction {
+        instructions: vec![],
+        num_locals: 0,
+        parameters: vec![],
+        min_arity: 0,
+        max_arity: Some(0),
+    });
+    let kept = heap.alloc(Closure { func: func.clone(), free: vec![] });
+    // A closure kept as the value of a caught error stays alive
+    let thrown = Object::Error(Rc::new(ErrorObject {
+        kind: "Error".to_string(),
+        message: "boom".to_string(),
+        value: Object::Closure(kept),
+    }));
+    let freed = heap.alloc(Closure { func, free: vec![] });
+
+    assert_eq!(heap.collect([thrown].iter()), 1);
+    assert!(heap.get(kept).is_ok());
+    assert_eq!(heap.get(freed).unwrap_err().to_string(), format!("use of freed heap object {}", freed));
+}
+
+// Run a program with the evaluator and then with the VM
+fn run_both(input: &str) -> [Result<Object>; 2] {
+    let program = parse(input.to_string());
+    let evaluated = Evaluator::default().eval(&program);
+
+    let mut compiler = Compiler::new();
+    let executed = compiler.compile(&program).and_then(|_| {
+        let mut vm = VM::new(compiler.bytecode());
+        vm.run().map(|_| vm.last_popped_stack_elem())
+    });
+
+    [evaluated, executed]
+}
+
+// Check that both back ends give each program the expected value, compared by
+// how it prints, or fail it with the expected error
+fn run_parity_tests(tests: &[(&str, &str)], errors: &[(&str, &str)]) {
+    for (input, expected) in tests {
+        for result in run_both(input) {
+            assert_eq!(result.unwrap().to_string(), *expected, "{}", input);
+        }
+    }
+    for (input, expected) in errors {
+        for result in run_both(input) {
+            assert_eq!(result.unwrap_err().to_string(), *expected, "{}", input);
+        }
+    }
+}
+
 fn parse(input: String) -> Program {
     let lexer = Lexer::new(input);
     let mut parser = Parser::new(lexer);